@@ -0,0 +1,14 @@
+use shader_roy_metal_sl_interface::*;
+use simple::pixel_color;
+
+#[test]
+fn test_circle_is_white_inside_and_black_outside() {
+  let input = Input {
+    window_size: Vec2::new(100.0, 100.0),
+    ..Input::default()
+  };
+  with_input(&input, || {
+    assert_eq!(pixel_color(Vec2::new(50.0, 50.0)), Vec4::one());
+    assert_eq!(pixel_color(Vec2::new(5.0, 5.0)), Vec4::new(0.0, 0.0, 0.0, 1.0));
+  });
+}
@@ -45,6 +45,60 @@ pub fn implement(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     })
     .collect();
 
+  // Only swizzles without repeated components can be assigned to
+  let assignable_names: Vec<String> = (2..=dimension)
+    .flat_map(|dim| accessor_names(dim, &axes[0..dimension]))
+    .filter(|name| has_distinct_chars(name))
+    .collect();
+  let assign_trait_name = quote::format_ident!("{}{}", "AssignTo", dimension);
+
+  let assign_method_declarations: Vec<syn::TraitItem> = assignable_names
+    .iter()
+    .flat_map(|name| {
+      let value_type = quote::format_ident!("{}{}", scalar_type_name, name.len());
+      let setter_name = quote::format_ident!("set_{}", name);
+      let mut_name = quote::format_ident!("{}_mut", name);
+      vec![
+        syn::parse_quote!(fn #setter_name(&mut self, value: #value_type);),
+        syn::parse_quote!(fn #mut_name(&mut self) -> SwizzleMut<'_, Self, #value_type>;),
+      ]
+    })
+    .collect();
+
+  let assign_method_definitions: Vec<syn::TraitItem> = assignable_names
+    .iter()
+    .flat_map(|name| {
+      let value_type = quote::format_ident!("{}{}", scalar_type_name, name.len());
+      let setter_name = quote::format_ident!("set_{}", name);
+      let mut_name = quote::format_ident!("{}_mut", name);
+      let constructor = quote::format_ident!("{}{}", scalar_fn_name, name.len());
+      let fields: syn::punctuated::Punctuated<_, syn::Token![,]> = name
+        .chars()
+        .map(|ch| {
+          let field = quote::format_ident!("{}", ch);
+          let access: syn::Expr = syn::parse_quote!(self.#field);
+          access
+        })
+        .collect();
+      let assignments: Vec<syn::Stmt> = name
+        .chars()
+        .zip(axes.chars())
+        .map(|(target_ch, value_ch)| {
+          let target_field = quote::format_ident!("{}", target_ch);
+          let value_field = quote::format_ident!("{}", value_ch);
+          syn::parse_quote!(self.#target_field = value.#value_field;)
+        })
+        .collect();
+      vec![
+        syn::parse_quote!(fn #setter_name(&mut self, value: #value_type) {#(#assignments)*}),
+        syn::parse_quote!(fn #mut_name(&mut self) -> SwizzleMut<'_, Self, #value_type> {
+          let value = (#fields).#constructor();
+          SwizzleMut::new(self, value, |target, value| target.#setter_name(value))
+        }),
+      ]
+    })
+    .collect();
+
   let result = quote::quote!(
       pub trait #trait_name {
         #(#trait_method_declarations)*
@@ -53,6 +107,14 @@ pub fn implement(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
       impl #trait_name for #vec_type {
         #(#trait_method_definitions)*
       }
+
+      pub trait #assign_trait_name: Sized {
+        #(#assign_method_declarations)*
+      }
+
+      impl #assign_trait_name for #vec_type {
+        #(#assign_method_definitions)*
+      }
   );
   // eprintln!("{}", result);
   proc_macro::TokenStream::from(result)
@@ -71,3 +133,10 @@ fn accessor_names(dims: usize, axes: &str) -> Vec<String> {
   }
   result
 }
+
+fn has_distinct_chars(name: &str) -> bool {
+  name
+    .chars()
+    .enumerate()
+    .all(|(i, ch)| !name[i + 1..].contains(ch))
+}
@@ -9,9 +9,58 @@ prelude_macros::implement_accessors! {
   // Uint4,
 }
 
+/// Mutable view of a swizzle, returned by `xz_mut()` and friends.
+///
+/// Changes are written back to the vector when the view is dropped,
+/// so compound assignment works as in MSL: `*v.xz_mut() += 1.0;`.
+pub struct SwizzleMut<'a, V, S: Copy> {
+  target: &'a mut V,
+  value: S,
+  write: fn(&mut V, S),
+}
+
+impl<'a, V, S: Copy> SwizzleMut<'a, V, S> {
+  pub fn new(target: &'a mut V, value: S, write: fn(&mut V, S)) -> Self {
+    Self {
+      target,
+      value,
+      write,
+    }
+  }
+}
+
+impl<V, S: Copy> std::ops::Deref for SwizzleMut<'_, V, S> {
+  type Target = S;
+
+  fn deref(&self) -> &S {
+    &self.value
+  }
+}
+
+impl<V, S: Copy> std::ops::DerefMut for SwizzleMut<'_, V, S> {
+  fn deref_mut(&mut self) -> &mut S {
+    &mut self.value
+  }
+}
+
+impl<V, S: Copy> Drop for SwizzleMut<'_, V, S> {
+  fn drop(&mut self) {
+    (self.write)(self.target, self.value);
+  }
+}
+
 #[test]
 fn test_accessors() {
   let _ = 1.0.vec3().x;
   1.0.vec3().xy();
   1.0.vec3().zyx();
 }
+
+#[test]
+fn test_swizzle_assignment() {
+  let mut v = 1.0.vec3();
+  v.set_xz((2.0, 3.0).vec2());
+  *v.zy_mut() += 1.0;
+  *v.yx_mut() = 5.0.vec2();
+  assert_eq!(v, (5.0, 5.0, 4.0).vec3());
+}
@@ -52,17 +52,21 @@ pub trait Common: Sized {
     t * t * (Tr::from(3.0) - Tr::from(2.0) * t)
   }
 
+  // 0 where `self < edge`, 1 otherwise
   fn step<Te, Tr, V>(self, edge: Te) -> Tr
   where
     Self: Op<Te, Tr>,
     Tr: Map<V>,
     V: PartialOrd,
-    V: num::Signed,
     V: std::convert::From<f32>,
   {
-    (self - edge)
-      .sign()
-      .map(|value| vek::partial_min(value, V::from(0.0)))
+    (self - edge).map(|value| {
+      if value < V::from(0.0) {
+        V::from(0.0)
+      } else {
+        V::from(1.0)
+      }
+    })
   }
 }
 
@@ -100,8 +104,13 @@ fn test_smoothstep() {
 
 #[test]
 fn test_step() {
-  1.0f32.step(0.3); // Rust defaults to f64 for which this is not implemented
-  1.0.vec3().step(0.3.vec3());
+  assert_eq!(1.0f32.step(0.3), 1.0); // Rust defaults to f64 for which this is not implemented
+  assert_eq!(0.3f32.step(0.3), 1.0);
+  assert_eq!(0.2f32.step(0.3), 0.0);
+  assert_eq!(
+    Vec3::new(0.2, 0.3, 0.4).step(0.3.vec3()),
+    Vec3::new(0.0, 1.0, 1.0)
+  );
   1.0.vec3().step(0.3);
 }
 
@@ -54,39 +54,39 @@ use super::*;
 // T tanpi(T x)
 // T trunc(T x)
 
-pub const MAXFLOAT: f32 = 0.0;
-pub const HUGE_VALF: f32 = 0.0;
-pub const INFINITY: f32 = 0.0;
-pub const NAN: f32 = 0.0;
-pub const M_E_F: f32 = 0.0;
-pub const M_LOG2E_F: f32 = 0.0;
-pub const M_LOG10E_F: f32 = 0.0;
-pub const M_LN2_F: f32 = 0.0;
-pub const M_LN10_F: f32 = 0.0;
-pub const M_PI_F: f32 = 0.0;
-pub const M_PI_2_F: f32 = 0.0;
-pub const M_PI_4_F: f32 = 0.0;
-pub const M_1_PI_F: f32 = 0.0;
-pub const M_2_PI_F: f32 = 0.0;
-pub const M_2_SQRTPI_F: f32 = 0.0;
-pub const M_SQRT2_F: f32 = 0.0;
-pub const M_SQRT1_2_F: f32 = 0.0;
+pub const MAXFLOAT: f32 = f32::MAX;
+pub const HUGE_VALF: f32 = f32::INFINITY;
+pub const INFINITY: f32 = f32::INFINITY;
+pub const NAN: f32 = f32::NAN;
+pub const M_E_F: f32 = std::f32::consts::E;
+pub const M_LOG2E_F: f32 = std::f32::consts::LOG2_E;
+pub const M_LOG10E_F: f32 = std::f32::consts::LOG10_E;
+pub const M_LN2_F: f32 = std::f32::consts::LN_2;
+pub const M_LN10_F: f32 = std::f32::consts::LN_10;
+pub const M_PI_F: f32 = std::f32::consts::PI;
+pub const M_PI_2_F: f32 = std::f32::consts::FRAC_PI_2;
+pub const M_PI_4_F: f32 = std::f32::consts::FRAC_PI_4;
+pub const M_1_PI_F: f32 = std::f32::consts::FRAC_1_PI;
+pub const M_2_PI_F: f32 = std::f32::consts::FRAC_2_PI;
+pub const M_2_SQRTPI_F: f32 = std::f32::consts::FRAC_2_SQRT_PI;
+pub const M_SQRT2_F: f32 = std::f32::consts::SQRT_2;
+pub const M_SQRT1_2_F: f32 = std::f32::consts::FRAC_1_SQRT_2;
 
-pub const MAXHALF: f16 = f16::from_bits(0);
-pub const HUGE_VALH: f16 = f16::from_bits(0);
-pub const M_E_H: f16 = f16::from_bits(0);
-pub const M_LOG2E_H: f16 = f16::from_bits(0);
-pub const M_LOG10E_H: f16 = f16::from_bits(0);
-pub const M_LN2_H: f16 = f16::from_bits(0);
-pub const M_LN10_H: f16 = f16::from_bits(0);
-pub const M_PI_H: f16 = f16::from_bits(0);
-pub const M_PI_2_H: f16 = f16::from_bits(0);
-pub const M_PI_4_H: f16 = f16::from_bits(0);
-pub const M_1_PI_H: f16 = f16::from_bits(0);
-pub const M_2_PI_H: f16 = f16::from_bits(0);
-pub const M_2_SQRTPI_H: f16 = f16::from_bits(0);
-pub const M_SQRT2_H: f16 = f16::from_bits(0);
-pub const M_SQRT1_2_H: f16 = f16::from_bits(0);
+pub const MAXHALF: f16 = f16::MAX;
+pub const HUGE_VALH: f16 = f16::INFINITY;
+pub const M_E_H: f16 = f16::E;
+pub const M_LOG2E_H: f16 = f16::LOG2_E;
+pub const M_LOG10E_H: f16 = f16::LOG10_E;
+pub const M_LN2_H: f16 = f16::LN_2;
+pub const M_LN10_H: f16 = f16::LN_10;
+pub const M_PI_H: f16 = f16::PI;
+pub const M_PI_2_H: f16 = f16::FRAC_PI_2;
+pub const M_PI_4_H: f16 = f16::FRAC_PI_4;
+pub const M_1_PI_H: f16 = f16::FRAC_1_PI;
+pub const M_2_PI_H: f16 = f16::FRAC_2_PI;
+pub const M_2_SQRTPI_H: f16 = f16::FRAC_2_SQRT_PI;
+pub const M_SQRT2_H: f16 = f16::SQRT_2;
+pub const M_SQRT1_2_H: f16 = f16::FRAC_1_SQRT_2;
 
 pub trait ComponentWiseMath {
   fn abs(self) -> Self;
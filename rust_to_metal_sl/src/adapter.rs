@@ -1,40 +1,33 @@
-pub fn make_rust_ast_msl_compatible(mut rust_ast: syn::File) -> syn::File {
+use super::backend::{BuiltinFunction, ShaderBackend};
+use anyhow::Result;
+
+pub fn make_rust_ast_compatible(
+  mut rust_ast: syn::File,
+  backend: &dyn ShaderBackend,
+) -> Result<syn::File> {
   use syn::visit_mut::VisitMut;
-  AstAdapter.visit_file_mut(&mut rust_ast);
-  rust_ast
+  let mut adapter = AstAdapter {
+    backend,
+    error: None,
+  };
+  adapter.visit_file_mut(&mut rust_ast);
+  if let Some(err) = adapter.error {
+    return Err(err);
+  }
+  Ok(rust_ast)
 }
 
 const DEFAULT_GENERIC_TYPE_ARG: &str = "f32";
 const GENERIC_TYPE_PREFIX: &str = "Vec";
+const MATRIX_TYPE_PREFIX: &str = "Mat";
 const GENERIC_METHOD_PREFIX: &str = "vec";
 
 lazy_static::lazy_static! {
-  static ref RUST_TO_METAL_TYPES: std::collections::HashMap<&'static str, &'static str> =
-    maplit::hashmap![
-      "i8" => "char",
-      "u8" => "uchar",
-      "i16" => "short",
-      "u16" => "ushort",
-      "i32" => "int",
-      "u32" => "uint",
-      "i32" => "long",
-      "i32" => "ulong",
-      "f16" => "half",
-      "f32" => "float",
-    ];
   static ref ACCESS_METHODS: regex::Regex = regex::Regex::new(r"^[xywz]{1,4}$").unwrap();
-  static ref RENAMED_METHODS: std::collections::HashMap<&'static str, &'static str> =
-    maplit::hashmap![
-      "clamped" => "clamp",
-      "magnitude" => "length",
-      "magnitude_squared" => "length_squared",
-      "face_forward" => "faceforward",
-      "normalized" => "normalize",
-      "reflected" => "reflect",
-      "refracted" => "refract",
-      "min" => "fmin",
-      "max" => "fmax",
-    ];
+  static ref ASSIGN_METHODS: regex::Regex =
+    regex::Regex::new(r"^set_(?P<swizzle>[xywz]{2,4})$").unwrap();
+  static ref MUT_ACCESS_METHODS: regex::Regex =
+    regex::Regex::new(r"^(?P<swizzle>[xywz]{2,4})_mut$").unwrap();
   static ref METHODS_WITH_RECEIVER_LAST: regex::RegexSet = regex::RegexSet::new(
     ["mix", "smoothstep", "step"]
       .iter()
@@ -43,10 +36,74 @@ lazy_static::lazy_static! {
   .unwrap();
 }
 
-struct AstAdapter;
+struct AstAdapter<'a> {
+  backend: &'a dyn ShaderBackend,
+  error: Option<anyhow::Error>,
+}
+
+impl AstAdapter<'_> {
+  // Keeps the first error, the AST is left unchanged where one occurs
+  fn process<T>(&mut self, result: Result<T>) -> Option<T> {
+    match result {
+      Ok(value) => Some(value),
+      Err(err) => {
+        self.error.get_or_insert(err);
+        None
+      }
+    }
+  }
+
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Result<String> {
+    self
+      .backend
+      .vector_type(rust_scalar_type, arity)
+      .ok_or_else(|| self.unsupported_type_error(rust_scalar_type, "vectors"))
+  }
+
+  fn matrix_type(&self, rust_scalar_type: &str, dimension: usize) -> Result<String> {
+    self
+      .backend
+      .matrix_type(rust_scalar_type, dimension)
+      .ok_or_else(|| self.unsupported_type_error(rust_scalar_type, "matrices"))
+  }
+
+  fn unsupported_type_error(&self, rust_scalar_type: &str, kind: &str) -> anyhow::Error {
+    anyhow::anyhow!(
+      "`{}` {} are not supported by the {} target",
+      rust_scalar_type,
+      kind,
+      self.backend.name()
+    )
+  }
+}
+
+fn parse_type_path(target_name: &str) -> Result<syn::Path> {
+  syn::parse_str(target_name)
+    .map_err(|err| anyhow::anyhow!("Invalid target type name `{}`: {}", target_name, err))
+}
 
-impl syn::visit_mut::VisitMut for AstAdapter {
+impl syn::visit_mut::VisitMut for AstAdapter<'_> {
   fn visit_expr_mut(&mut self, node: &mut syn::Expr) {
+    // `*v.xz_mut() += w` becomes `v.xz += w`
+    if let syn::Expr::Unary(syn::ExprUnary {
+      op: syn::UnOp::Deref(_),
+      expr,
+      ..
+    }) = node
+    {
+      if let syn::Expr::MethodCall(syn::ExprMethodCall {
+        receiver, method, ..
+      }) = &**expr
+      {
+        if let Some(captures) = MUT_ACCESS_METHODS.captures(&method.to_string()) {
+          let swizzle = quote::format_ident!("{}", &captures["swizzle"]);
+          *node = syn::parse_quote!(
+            #receiver.#swizzle
+          );
+        }
+      }
+    }
+
     if let syn::Expr::MethodCall(expr) = node {
       let syn::ExprMethodCall {
         receiver,
@@ -55,39 +112,58 @@ impl syn::visit_mut::VisitMut for AstAdapter {
         ..
       } = expr;
 
-      if let Some(new_method_name) = RENAMED_METHODS.get::<str>(&method.to_string()) {
+      let method_name = method.to_string();
+      let builtin_function = self.backend.builtin_function(&method_name);
+      if let Some(BuiltinFunction::Function(new_method_name)) = &builtin_function {
         *method = quote::format_ident!("{}", new_method_name);
       }
 
-      let method_name = method.to_string();
-
-      *node = if ACCESS_METHODS.is_match(&method_name) {
+      *node = if let Some(BuiltinFunction::BinaryOperator(operator)) = builtin_function {
+        let operator = parse_tokens(&operator);
+        let value = args.first();
+        syn::parse_quote!(
+          (#receiver #operator #value)
+        )
+      } else if ACCESS_METHODS.is_match(&method_name) {
         syn::parse_quote!(
           #receiver.#method
         )
+      } else if let Some(captures) = ASSIGN_METHODS.captures(&method_name) {
+        let swizzle = quote::format_ident!("{}", &captures["swizzle"]);
+        let value = args.first();
+        syn::parse_quote!(
+          #receiver.#swizzle = #value
+        )
       } else if let Some(arity_and_type_suffix) = method_name.strip_prefix(GENERIC_METHOD_PREFIX) {
         let type_name = &arity_and_type_suffix[1..];
-        let new_type_name = RUST_TO_METAL_TYPES
-          .get::<str>(if type_name == "" {
+        let arity = arity_and_type_suffix[0..1].parse().unwrap();
+        let vector_type = self.vector_type(
+          if type_name.is_empty() {
             DEFAULT_GENERIC_TYPE_ARG
           } else {
             type_name
-          })
-          .unwrap();
-        let new_method = quote::format_ident!("{}{}", new_type_name, arity_and_type_suffix[0..1]);
-        if let syn::Expr::Tuple(syn::ExprTuple {
+          },
+          arity,
+        );
+        let new_method = match self.process(vector_type) {
+          Some(vector_type) => parse_tokens(&vector_type),
+          None => return,
+        };
+        let args = if let syn::Expr::Tuple(syn::ExprTuple {
           elems: unwrapped_args,
           ..
         }) = &**receiver
         {
-          syn::parse_quote!(
-            #new_method(#unwrapped_args)
-          )
+          unwrapped_args.clone()
         } else {
-          syn::parse_quote!(
-            #new_method(#receiver)
-          )
-        }
+          std::iter::once((**receiver).clone()).collect()
+        };
+        syn::Expr::Call(syn::ExprCall {
+          attrs: vec![],
+          func: Box::new(syn::Expr::Verbatim(new_method)),
+          paren_token: Default::default(),
+          args,
+        })
       } else if METHODS_WITH_RECEIVER_LAST.is_match(&method_name) {
         syn::parse_quote!(
           #method(#args, #receiver)
@@ -102,40 +178,69 @@ impl syn::visit_mut::VisitMut for AstAdapter {
       }
     }
 
-    // For now we will erase type casts, as properly compiling them
-    // would require a full expression printer
-    if let syn::Expr::Cast(syn::ExprCast { expr, .. }) = node {
-      *node = syn::parse_quote!(#expr);
+    // `x as f32` becomes `float(x)`
+    if let syn::Expr::Cast(syn::ExprCast { expr, ty, .. }) = node {
+      *node = syn::parse_quote!(#ty(#expr));
     }
 
     // Delegate to the default impl to visit nested expressions.
     syn::visit_mut::visit_expr_mut(self, node);
+
+    // `float3(x)` becomes `(float3)(x)`
+    if !self.backend.has_scalar_vector_constructor() {
+      if let syn::Expr::Call(syn::ExprCall { func, args, .. }) = node {
+        if let (syn::Expr::Verbatim(vector_type), 1) = (&**func, args.len()) {
+          let value = args.first();
+          *node = syn::Expr::Verbatim(quote::quote!(((#vector_type)(#value))));
+        }
+      }
+    }
+
+    if !self.backend.supports_swizzle_assignment() {
+      if let Some(assignments) = swizzle_assignment_to_component_assignments(node) {
+        *node = assignments;
+      }
+    }
   }
 
-  // Convert scalar/vector types
-  // `scalar` or ```Vec`d`<`scalar`>```
+  // Convert scalar/vector/matrix types
+  // `scalar`, ```Vec`d`<`scalar`>``` or ```Mat`d`<`scalar`>```
   fn visit_path_mut(&mut self, node: &mut syn::Path) {
     let (type_name, type_args) = {
       let mut path_segments_iter = node.segments.iter();
       let ty = path_segments_iter.next().unwrap();
       let arg_list = if let syn::PathArguments::AngleBracketed(args) = &ty.arguments {
+        let args = &args.args;
         Some(quote::quote!(#args).to_string())
       } else {
         None
       };
       (ty.ident.to_string(), arg_list)
     };
-    if let Some(new_type_name) = RUST_TO_METAL_TYPES.get::<str>(&type_name) {
-      let new_type = quote::format_ident!("{}", new_type_name);
-      *node = syn::parse_quote!(#new_type);
-    }
-    if let Some(arity_suffix) = type_name.strip_prefix(GENERIC_TYPE_PREFIX) {
-      if let Some(new_type_name) =
-        RUST_TO_METAL_TYPES.get::<str>(&type_args.as_deref().unwrap_or(DEFAULT_GENERIC_TYPE_ARG))
-      {
-        let new_type = quote::format_ident!("{}{}", new_type_name, arity_suffix);
-        *node = syn::parse_quote!(#new_type);
-      }
+    let new_type_name = if let Some(Ok(arity)) = type_name
+      .strip_prefix(GENERIC_TYPE_PREFIX)
+      .map(str::parse::<usize>)
+    {
+      self.vector_type(
+        type_args.as_deref().unwrap_or(DEFAULT_GENERIC_TYPE_ARG),
+        arity,
+      )
+    } else if let Some(Ok(dimension)) = type_name
+      .strip_prefix(MATRIX_TYPE_PREFIX)
+      .map(str::parse::<usize>)
+    {
+      self.matrix_type(
+        type_args.as_deref().unwrap_or(DEFAULT_GENERIC_TYPE_ARG),
+        dimension,
+      )
+    } else if let Some(new_type_name) = self.backend.scalar_type(&type_name) {
+      Ok(new_type_name)
+    } else {
+      // Struct names are kept
+      return;
+    };
+    if let Some(new_path) = self.process(new_type_name.and_then(|name| parse_type_path(&name))) {
+      *node = new_path;
     }
   }
 
@@ -149,3 +254,95 @@ impl syn::visit_mut::VisitMut for AstAdapter {
     syn::visit_mut::visit_expr_call_mut(self, node);
   }
 }
+
+// `v.xz += w` becomes `{ let _swizzle = v.xz + w; v.x = _swizzle.x; v.z = _swizzle.y; }`
+fn swizzle_assignment_to_component_assignments(node: &syn::Expr) -> Option<syn::Expr> {
+  let (left, value): (_, syn::Expr) = match node {
+    syn::Expr::Assign(syn::ExprAssign { left, right, .. }) => (left, (**right).clone()),
+    syn::Expr::AssignOp(syn::ExprAssignOp {
+      left, op, right, ..
+    }) => {
+      // `+=` becomes `+`
+      let op = parse_tokens(quote::quote!(#op).to_string().trim_end_matches('='));
+      (left, syn::parse_quote!(#left #op (#right)))
+    }
+    _ => return None,
+  };
+  let (base, swizzle) = match &**left {
+    syn::Expr::Field(syn::ExprField {
+      base,
+      member: syn::Member::Named(swizzle),
+      ..
+    }) => (base, swizzle.to_string()),
+    _ => return None,
+  };
+  if swizzle.len() < 2 || !ACCESS_METHODS.is_match(&swizzle) {
+    return None;
+  }
+  let assignments = swizzle.chars().zip("xyzw".chars()).map(|(target, source)| {
+    let target = quote::format_ident!("{}", target.to_string());
+    let source = quote::format_ident!("{}", source.to_string());
+    quote::quote!(#base.#target = _swizzle.#source;)
+  });
+  Some(syn::parse_quote!({
+    let _swizzle = #value;
+    #(#assignments)*
+  }))
+}
+
+// Target names can be any tokens, like `vec3<f32>` in WGSL
+fn parse_tokens(target_name: &str) -> proc_macro2::TokenStream {
+  target_name.parse().unwrap()
+}
+
+#[test]
+fn test_swizzle_assignment() {
+  let config = crate::EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let msl = crate::format_msl(
+    &crate::transpile(
+      "fn f(w: Vec2) -> Vec3 {
+        let mut v = 1.0.vec3();
+        v.set_xz(w);
+        *v.xz_mut() += w;
+        v.set_foo(w);
+        v
+      }",
+      &config,
+    )
+    .unwrap(),
+  );
+  assert!(msl.contains("v.xz = w;"));
+  assert!(msl.contains("v.xz += w;"));
+  assert!(msl.contains("set_foo(v, w);"));
+}
+
+#[test]
+fn test_unsupported_vector_types() {
+  let config = crate::EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let transpile_wgsl = |source: &str| {
+    crate::transpile_with_backend(source, &config, &crate::WgslBackend)
+      .unwrap_err()
+      .to_string()
+  };
+  assert_eq!(
+    transpile_wgsl("fn f() -> u8 { (1u8, 2u8).vec2u8().x }"),
+    "`u8` vectors are not supported by the WGSL target"
+  );
+  assert_eq!(
+    transpile_wgsl("fn f(v: Vec3<i16>) -> i32 { 1 }"),
+    "`i16` vectors are not supported by the WGSL target"
+  );
+  assert_eq!(
+    transpile_wgsl("fn f(m: Mat2<u8>) -> i32 { 1 }"),
+    "`u8` matrices are not supported by the WGSL target"
+  );
+  assert!(crate::transpile("fn f() -> u8 { (1u8, 2u8).vec2u8().x }", &config).is_ok());
+}
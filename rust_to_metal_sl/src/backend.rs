@@ -0,0 +1,181 @@
+//! Extension point for targeting shading languages other than MSL.
+
+use super::EnhanceConfig;
+
+/// Provides the syntax and names specific to a target shading language.
+///
+/// Expressions are printed in the C-like syntax shared by the supported
+/// languages, after types and built-in functions have been renamed using
+/// this trait. Default implementations follow C++ (and so MSL).
+pub trait ShaderBackend {
+  /// Name of the target language in error messages, like `MSL`.
+  fn name(&self) -> &'static str;
+
+  /// Maps a Rust scalar type name (`f32`) to the target type name (`float`).
+  fn scalar_type(&self, rust_type: &str) -> Option<String>;
+
+  /// Maps a Rust vector type (`Vec3<f32>`) to the target type name (`float3`).
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Option<String>;
+
+  /// Maps a Rust square matrix type (`Mat3<f32>`) to the target type name (`float3x3`).
+  fn matrix_type(&self, _rust_scalar_type: &str, _dimension: usize) -> Option<String> {
+    None
+  }
+
+  /// Maps a Rust method name (`magnitude`) to the target built-in.
+  /// Methods without a mapping are called as functions of the same name.
+  fn builtin_function(&self, rust_method: &str) -> Option<BuiltinFunction>;
+
+  /// How the `INPUT` constant is made available to the shader functions.
+  fn input_binding(&self) -> InputBinding;
+
+  /// Declares the `INPUT` global, printed after the input struct declaration.
+  /// Only used with `InputBinding::Global`.
+  fn input_declaration(&self, _config: &EnhanceConfig, _fields: &[StructField]) -> String {
+    String::new()
+  }
+
+  /// Wraps the entry point function in the target's shader entry point.
+  /// `input_fields` are empty if the input struct isn't part of the source.
+  fn entry_point_wrapper(&self, config: &EnhanceConfig, input_fields: &[StructField]) -> String;
+
+  /// Helper functions and constants printed before the shader code.
+  fn prelude(&self) -> String {
+    String::new()
+  }
+
+  fn function_signature(&self, name: &str, params: &[String], return_type: Option<&str>) -> String;
+
+  fn parameter(&self, name: &str, ty: &str, address_space: Option<&str>) -> String;
+
+  fn local_declaration(&self, name: &str, ty: Option<&str>, is_mutable: bool) -> String;
+
+  fn for_range(
+    &self,
+    var: &str,
+    ty: Option<&str>,
+    from: &str,
+    to: &str,
+    is_inclusive: bool,
+  ) -> String;
+
+  /// Declares a mutable local copy of a parameter, for languages where
+  /// parameters are immutable. The parameter itself is renamed to `param_name`.
+  fn mutable_parameter_copy(&self, _name: &str, _param_name: &str, _ty: &str) -> Option<String> {
+    None
+  }
+
+  /// Whether `v.xz = w` can be written directly, otherwise each component
+  /// is assigned separately.
+  fn supports_swizzle_assignment(&self) -> bool {
+    true
+  }
+
+  /// Name of a variable introduced by the transpiler.
+  fn temporary_variable(&self, id: u64) -> String {
+    format!("__var__{}", id)
+  }
+
+  /// Whether local variables need to be declared with their type,
+  /// which is then inferred by the transpiler.
+  fn needs_explicit_local_types(&self) -> bool {
+    false
+  }
+
+  /// Whether scalars are converted to vectors where vectors are expected,
+  /// as in `max(v, 0.0)`. Otherwise the transpiler converts them.
+  fn has_implicit_vector_splat(&self) -> bool {
+    true
+  }
+
+  /// Whether `float3(x)` sets all components to `x`,
+  /// otherwise single argument vector constructors are printed as casts.
+  fn has_scalar_vector_constructor(&self) -> bool {
+    true
+  }
+
+  /// Function computing matrix products, for languages where `*`
+  /// multiplies matrices component-wise.
+  fn matrix_multiplication_function(&self) -> Option<String> {
+    None
+  }
+
+  /// Whether functions can be overloaded on their parameter types,
+  /// otherwise vector methods implemented in the prelude, like
+  /// `magnitude_squared`, are suffixed with the vector width.
+  fn has_function_overloading(&self) -> bool {
+    true
+  }
+
+  /// Whether functions need to be declared before their use.
+  fn needs_forward_declarations(&self) -> bool {
+    true
+  }
+
+  fn function_attribute(&self, _attribute: FunctionAttribute) -> Option<String> {
+    None
+  }
+
+  fn struct_field(&self, name: &str, ty: &str) -> String {
+    format!("{} {};", ty, name)
+  }
+
+  fn struct_end(&self) -> String {
+    "};".to_owned()
+  }
+
+  fn ternary(&self, condition: &str, then: &str, otherwise: &str) -> String {
+    format!("({}) ? {} : {}", condition, then, otherwise)
+  }
+}
+
+/// Float constants from the MSL standard library, for targets without them.
+pub(crate) const MATH_CONSTANTS: [(&str, f32); 14] = [
+  ("MAXFLOAT", f32::MAX),
+  ("M_E_F", std::f32::consts::E),
+  ("M_LOG2E_F", std::f32::consts::LOG2_E),
+  ("M_LOG10E_F", std::f32::consts::LOG10_E),
+  ("M_LN2_F", std::f32::consts::LN_2),
+  ("M_LN10_F", std::f32::consts::LN_10),
+  ("M_PI_F", std::f32::consts::PI),
+  ("M_PI_2_F", std::f32::consts::FRAC_PI_2),
+  ("M_PI_4_F", std::f32::consts::FRAC_PI_4),
+  ("M_1_PI_F", std::f32::consts::FRAC_1_PI),
+  ("M_2_PI_F", std::f32::consts::FRAC_2_PI),
+  ("M_2_SQRTPI_F", std::f32::consts::FRAC_2_SQRT_PI),
+  ("M_SQRT2_F", std::f32::consts::SQRT_2),
+  ("M_SQRT1_2_F", std::f32::consts::FRAC_1_SQRT_2),
+];
+
+pub enum BuiltinFunction {
+  /// Call a function with the given name
+  Function(String),
+  /// Apply a binary operator to the two arguments, like `%` for `fmod`
+  BinaryOperator(String),
+}
+
+pub enum InputBinding {
+  /// Passed as a reference parameter in the given address space
+  /// to every function that needs it.
+  Parameter { address_space: String },
+  /// Declared once as a global variable.
+  Global,
+}
+
+/// Rust function attributes with an equivalent in shading languages.
+pub enum FunctionAttribute {
+  /// `#[inline]`
+  Inline,
+  /// `#[inline(always)]`
+  AlwaysInline,
+  /// `#[inline(never)]`
+  NeverInline,
+  /// `#[cold]`
+  Cold,
+}
+
+pub struct StructField {
+  pub name: String,
+  /// Target type name
+  pub ty: String,
+}
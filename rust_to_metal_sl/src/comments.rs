@@ -0,0 +1,339 @@
+//! `//` comments are not part of the syn AST, so we find them in the source
+//! and attach them to the closest item or statement via span locations.
+//!
+//! Items and struct fields get a `#[comment = "..."]` attribute (fields get
+//! `#[trailing_comment = "..."]` for comments on the same line), statements
+//! are preceded by a `__comment__!("...");` marker statement (or followed by
+//! `__trailing_comment__!("...");` for comments on the same line).
+//! Comments inside of multi-line expressions (outside of blocks) are dropped.
+
+use syn::spanned::Spanned;
+
+const COMMENT_ATTRIBUTE: &str = "comment";
+const TRAILING_COMMENT_ATTRIBUTE: &str = "trailing_comment";
+const COMMENT_MACRO: &str = "__comment__";
+const TRAILING_COMMENT_MACRO: &str = "__trailing_comment__";
+
+pub enum StmtComment {
+  Leading(String),
+  Trailing(String),
+}
+
+pub fn attach_comments(mut rust_ast: syn::File, rust_source: &str) -> syn::File {
+  let mut attacher = CommentsAttacher {
+    comments: find_line_comments(rust_source),
+  };
+  let mut previous_end = proc_macro2::LineColumn { line: 0, column: 0 };
+  for item in rust_ast.items.iter_mut() {
+    let span = item.span();
+    let leading_comments = attacher.take_between(previous_end, span.start());
+    if let Some(attrs) = item_attrs_mut(item) {
+      let mut comment_attrs = leading_comments
+        .into_iter()
+        .map(|text| syn::parse_quote!(#[comment = #text]))
+        .collect::<Vec<syn::Attribute>>();
+      comment_attrs.append(attrs);
+      *attrs = comment_attrs;
+    }
+    use syn::visit_mut::VisitMut;
+    attacher.visit_item_mut(item);
+    previous_end = span.end();
+  }
+  rust_ast
+}
+
+/// Returns the MSL comment lines for `///` doc comments and attached `//` comments.
+pub fn attrs_to_comment_lines(attrs: &[syn::Attribute]) -> Vec<String> {
+  attrs
+    .iter()
+    .filter_map(|attr| {
+      let prefix = if attr.path.is_ident("doc") {
+        "///"
+      } else if attr.path.is_ident(COMMENT_ATTRIBUTE) {
+        "//"
+      } else {
+        return None;
+      };
+      match attr.parse_meta() {
+        Ok(syn::Meta::NameValue(syn::MetaNameValue {
+          lit: syn::Lit::Str(text),
+          ..
+        })) => Some(format!("{}{}", prefix, text.value())),
+        _ => None,
+      }
+    })
+    .collect()
+}
+
+/// Returns the MSL comments attached after a struct field, on its line.
+pub fn attrs_to_trailing_comments(attrs: &[syn::Attribute]) -> Vec<String> {
+  attrs
+    .iter()
+    .filter(|attr| attr.path.is_ident(TRAILING_COMMENT_ATTRIBUTE))
+    .filter_map(|attr| match attr.parse_meta() {
+      Ok(syn::Meta::NameValue(syn::MetaNameValue {
+        lit: syn::Lit::Str(text),
+        ..
+      })) => Some(format!("//{}", text.value())),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Returns the MSL comment if the statement is an attached `//` comment.
+pub fn stmt_to_comment(statement: &syn::Stmt) -> Option<StmtComment> {
+  if let syn::Stmt::Semi(syn::Expr::Macro(syn::ExprMacro { mac, .. }), _) = statement {
+    let text = mac.parse_body::<syn::LitStr>().ok()?;
+    let comment_line = format!("//{}", text.value());
+    if mac.path.is_ident(COMMENT_MACRO) {
+      return Some(StmtComment::Leading(comment_line));
+    }
+    if mac.path.is_ident(TRAILING_COMMENT_MACRO) {
+      return Some(StmtComment::Trailing(comment_line));
+    }
+  }
+  None
+}
+
+pub fn is_comment_stmt(statement: &syn::Stmt) -> bool {
+  stmt_to_comment(statement).is_some()
+}
+
+struct LineComment {
+  position: proc_macro2::LineColumn,
+  text: String,
+  is_attached: bool,
+}
+
+struct CommentsAttacher {
+  comments: Vec<LineComment>,
+}
+
+impl CommentsAttacher {
+  fn take_between(
+    &mut self,
+    from: proc_macro2::LineColumn,
+    to: proc_macro2::LineColumn,
+  ) -> Vec<String> {
+    self.take_where(|position| is_before(from, position) && is_before(position, to))
+  }
+
+  fn take_trailing(&mut self, end: proc_macro2::LineColumn) -> Vec<String> {
+    self.take_where(|position| position.line == end.line && !is_before(position, end))
+  }
+
+  fn take_where<F>(&mut self, predicate: F) -> Vec<String>
+  where
+    F: Fn(proc_macro2::LineColumn) -> bool,
+  {
+    self
+      .comments
+      .iter_mut()
+      .filter(|comment| !comment.is_attached && predicate(comment.position))
+      .map(|comment| {
+        comment.is_attached = true;
+        comment.text.clone()
+      })
+      .collect()
+  }
+}
+
+impl syn::visit_mut::VisitMut for CommentsAttacher {
+  fn visit_block_mut(&mut self, block: &mut syn::Block) {
+    let block_span = block.brace_token.span;
+    let mut previous_end = block_span.start();
+    let mut statements = vec![];
+    for mut statement in std::mem::take(&mut block.stmts) {
+      let span = statement.span();
+      statements.extend(
+        self
+          .take_between(previous_end, span.start())
+          .into_iter()
+          .map(|text| comment_stmt(COMMENT_MACRO, text)),
+      );
+      self.visit_stmt_mut(&mut statement);
+      statements.push(statement);
+      statements.extend(
+        self
+          .take_trailing(span.end())
+          .into_iter()
+          .map(|text| comment_stmt(TRAILING_COMMENT_MACRO, text)),
+      );
+      previous_end = span.end();
+    }
+    statements.extend(
+      self
+        .take_between(previous_end, block_span.end())
+        .into_iter()
+        .map(|text| comment_stmt(COMMENT_MACRO, text)),
+    );
+    block.stmts = statements;
+  }
+
+  fn visit_fields_named_mut(&mut self, fields: &mut syn::FieldsNamed) {
+    let mut previous_end = fields.brace_token.span.start();
+    for field in fields.named.iter_mut() {
+      let span = field.span();
+      // The field's span includes its doc comments, comments can be on either
+      // side of them
+      let name_start = field
+        .ident
+        .as_ref()
+        .map_or(span.start(), |ident| ident.span().start());
+      let attrs_start = field
+        .attrs
+        .first()
+        .map_or(name_start, |attr| attr.span().start());
+      let to_comment_attr =
+        |text: String| -> syn::Attribute { syn::parse_quote!(#[comment = #text]) };
+      let mut attrs: Vec<syn::Attribute> = self
+        .take_between(previous_end, attrs_start)
+        .into_iter()
+        .map(to_comment_attr)
+        .collect();
+      attrs.append(&mut field.attrs);
+      attrs.extend(
+        self
+          .take_between(attrs_start, name_start)
+          .into_iter()
+          .map(to_comment_attr),
+      );
+      // Also after the field's comma
+      attrs.extend(
+        self
+          .take_trailing(span.end())
+          .into_iter()
+          .map(|text| -> syn::Attribute { syn::parse_quote!(#[trailing_comment = #text]) }),
+      );
+      field.attrs = attrs;
+      previous_end = span.end();
+    }
+  }
+}
+
+fn comment_stmt(macro_name: &str, text: String) -> syn::Stmt {
+  let macro_name = quote::format_ident!("{}", macro_name);
+  syn::parse_quote!(#macro_name!(#text);)
+}
+
+fn item_attrs_mut(item: &mut syn::Item) -> Option<&mut Vec<syn::Attribute>> {
+  match item {
+    syn::Item::Fn(syn::ItemFn { attrs, .. }) | syn::Item::Struct(syn::ItemStruct { attrs, .. }) => {
+      Some(attrs)
+    }
+    _ => None,
+  }
+}
+
+fn is_before(a: proc_macro2::LineColumn, b: proc_macro2::LineColumn) -> bool {
+  (a.line, a.column) < (b.line, b.column)
+}
+
+// Finds `//` comments, skipping doc comments (which are already attributes)
+// and comment-like sequences inside of string and char literals.
+fn find_line_comments(rust_source: &str) -> Vec<LineComment> {
+  rust_source
+    .lines()
+    .enumerate()
+    .filter_map(|(line_index, line)| {
+      let chars: Vec<char> = line.chars().collect();
+      let mut is_in_string = false;
+      let mut i = 0;
+      while i < chars.len() {
+        match chars[i] {
+          '\\' if is_in_string => i += 1,
+          '"' => is_in_string = !is_in_string,
+          // Skips char literals like `'"'`, but not lifetimes like `'a`
+          '\'' if !is_in_string => {
+            if chars.get(i + 1) == Some(&'\\') {
+              // After the escaped character, which can be a quote
+              i += chars[i + 3..]
+                .iter()
+                .position(|ch| *ch == '\'')
+                .map_or(0, |end| end + 3);
+            } else if chars.get(i + 2) == Some(&'\'') {
+              i += 2;
+            }
+          }
+          '/' if !is_in_string && chars.get(i + 1) == Some(&'/') => {
+            let text: String = chars[i + 2..].iter().collect();
+            let is_doc_comment =
+              (text.starts_with('/') && !text.starts_with("//")) || text.starts_with('!');
+            if is_doc_comment {
+              return None;
+            }
+            return Some(LineComment {
+              position: proc_macro2::LineColumn {
+                line: line_index + 1,
+                column: i,
+              },
+              text: text.trim_end().to_owned(),
+              is_attached: false,
+            });
+          }
+          _ => {}
+        }
+        i += 1;
+      }
+      None
+    })
+    .collect()
+}
+
+#[test]
+fn test_find_line_comments() {
+  let comments = find_line_comments(
+    "/// doc\n\
+     let a = \"// not a comment\"; // trailing\n\
+     let b = '\"'; let c = '\\''; // after chars\n\
+     // leading\n\
+     //! inner doc",
+  );
+  let texts: Vec<_> = comments
+    .iter()
+    .map(|comment| comment.text.as_str())
+    .collect();
+  assert_eq!(texts, vec![" trailing", " after chars", " leading"]);
+  assert_eq!(comments[0].position.line, 2);
+}
+
+#[test]
+fn test_comments_reach_msl() {
+  let config = crate::EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let msl = crate::format_msl(
+    &crate::transpile(
+      "// Before the struct
+      struct Light {
+        /// Doc comment on a field
+        // Comment on a field
+        color: Vec3, // Trailing on a field
+        intensity: f32,
+      }
+
+      /// Doc comment on a function
+      #[inline(always)]
+      fn f(light: Light) -> Vec3 {
+        let quote = '\"'; // Trailing after a char literal
+        // Leading a statement
+        light.color * light.intensity // Trailing the returned value
+      }",
+      &config,
+    )
+    .unwrap(),
+  );
+  for expected in &[
+    "// Before the struct\nstruct Light {",
+    "  /// Doc comment on a field\n  // Comment on a field\n  float3 color; // Trailing on a field\n",
+    "/// Doc comment on a function\n",
+    "__attribute__((always_inline)) inline float3 f(Light light) {",
+    "// Trailing after a char literal\n",
+    "  // Leading a statement\n",
+    "// Trailing the returned value",
+  ] {
+    assert!(msl.contains(expected), "Missing {:?} in:\n{}", expected, msl);
+  }
+}
@@ -21,7 +21,11 @@ pub struct EnhanceConfig {
   pub param_type: String,
 }
 
-pub fn convert_constant_to_param(rust_ast: syn::File, properties: &EnhanceConfig) -> syn::File {
+pub fn convert_constant_to_param(
+  rust_ast: syn::File,
+  properties: &EnhanceConfig,
+  address_space: &str,
+) -> syn::File {
   let fn_items = FnItemsCollector::name_to_fn_items(&rust_ast);
   let is_using = {
     let mut is_using = HashMap::<String, bool>::new();
@@ -36,7 +40,7 @@ pub fn convert_constant_to_param(rust_ast: syn::File, properties: &EnhanceConfig
     is_using.insert(properties.entry_point_fn_name.to_owned(), true);
     is_using
   };
-  FnItemsEnhancer::add_params(rust_ast, is_using, properties)
+  FnItemsEnhancer::add_params(rust_ast, is_using, properties, address_space)
 }
 
 struct FnItemsCollector {
@@ -155,6 +159,7 @@ impl syn::visit::Visit<'_> for CalledFnsCollector {
 struct FnItemsEnhancer<'a> {
   is_using: HashMap<String, bool>,
   properties: &'a EnhanceConfig,
+  address_space: &'a str,
 }
 
 impl<'a> FnItemsEnhancer<'a> {
@@ -162,11 +167,13 @@ impl<'a> FnItemsEnhancer<'a> {
     mut rust_ast: syn::File,
     is_using: HashMap<String, bool>,
     properties: &'a EnhanceConfig,
+    address_space: &'a str,
   ) -> syn::File {
     use syn::visit_mut::VisitMut;
     let mut enhancer = Self {
       is_using,
       properties,
+      address_space,
     };
     enhancer.visit_file_mut(&mut rust_ast);
     rust_ast
@@ -183,8 +190,9 @@ impl syn::visit_mut::VisitMut for FnItemsEnhancer<'_> {
     if self.is_using(&fn_name) {
       let param_type = quote::format_ident!("{}", self.properties.param_type);
       let constant_name = quote::format_ident!("{}", self.properties.constant_name);
+      let address_space = quote::format_ident!("{}", self.address_space);
       item_fn.sig.inputs.push(syn::parse_quote!(
-        #[address_space(constant)] #constant_name: &#param_type
+        #[address_space(#address_space)] #constant_name: &#param_type
       ));
     }
     syn::visit_mut::visit_item_fn_mut(self, item_fn);
@@ -0,0 +1,550 @@
+//! Formats already-generated MSL (or any C-like shading language source) into
+//! stable, clang-format-like output, so that it can be diffed and committed.
+//!
+//! The source is re-tokenized and printed with consistent spacing, K&R braces
+//! and two-space indentation. At most one blank line is kept between
+//! statements and empty statements are removed. Long lines are not wrapped.
+//! Printer temporaries (`__var__N`) are renumbered per function,
+//! so adding a function doesn't rename the temporaries of the following ones.
+//!
+//! `format_wgsl` also keeps WGSL's template lists (`vec2<f32>`) and
+//! attributes (`@location(0)`) together, and puts each struct member on its
+//! own line.
+
+pub fn format_msl(msl: &str) -> String {
+  format(msl, false)
+}
+
+pub fn format_wgsl(wgsl: &str) -> String {
+  format(wgsl, true)
+}
+
+fn format(source: &str, is_wgsl: bool) -> String {
+  let mut formatter = Formatter {
+    is_wgsl,
+    output: String::new(),
+    indent: 0,
+    paren_depth: 0,
+    case_stack: vec![],
+    is_in_case_label: false,
+    has_pending_newline: false,
+    was_top_level_closed: false,
+    previous: None,
+    is_previous_unary: false,
+    is_in_initializer: false,
+    var_names: std::collections::HashMap::new(),
+  };
+  let tokens = tokenize(source, is_wgsl);
+  for (i, token) in tokens.iter().enumerate() {
+    formatter.format(token, tokens.get(i + 1).map(|next| &next.token));
+  }
+  formatter.finish()
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+  Word(String),
+  Punct(&'static str),
+  Comment(String),
+  Directive(String),
+}
+
+struct SpacedToken {
+  token: Token,
+  newlines_before: usize,
+}
+
+// `[[` and `]]` are lexed separately, since `]]` can also close two subscripts
+const PUNCTUATION: [&str; 47] = [
+  "<<=", ">>=", "->", "::", "++", "--", "&&", "||", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=",
+  "%=", "&=", "|=", "^=", "<<", ">>", "{", "}", "(", ")", "[", "]", ";", ",", ".", "?", ":", "=",
+  "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!", "~", "#",
+];
+
+// These can be both unary and binary, depending on the previous token
+const PREFIX_OPERATORS: [&str; 6] = ["-", "+", "*", "&", "!", "~"];
+
+const KEYWORDS_BEFORE_PARENS: [&str; 5] = ["if", "for", "while", "switch", "return"];
+
+// Names taking a template list in WGSL, like `vec2<f32>`, besides matrices
+const WGSL_TEMPLATE_GENERATORS: [&str; 8] = [
+  "vec2", "vec3", "vec4", "var", "array", "ptr", "atomic", "bitcast",
+];
+
+fn tokenize(source: &str, is_wgsl: bool) -> Vec<SpacedToken> {
+  let chars: Vec<char> = source.chars().collect();
+  let mut tokens = vec![];
+  let mut newlines_before = 0;
+  // For each open bracket, whether it's an attribute's `[[`
+  let mut bracket_stack = vec![];
+  let mut i = 0;
+  while i < chars.len() {
+    let ch = chars[i];
+    let rest: String = chars[i..chars.len().min(i + 3)].iter().collect();
+    let start = i;
+    let token = if ch == '\n' {
+      newlines_before += 1;
+      i += 1;
+      continue;
+    } else if ch.is_whitespace() {
+      i += 1;
+      continue;
+    } else if rest.starts_with("//") {
+      while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+      }
+      Token::Comment(
+        chars[start..i]
+          .iter()
+          .collect::<String>()
+          .trim_end()
+          .to_owned(),
+      )
+    } else if ch == '#' && is_at_line_start(&chars, i) {
+      while i < chars.len() && chars[i] != '\n' {
+        i += 1;
+      }
+      Token::Directive(
+        chars[start..i]
+          .iter()
+          .collect::<String>()
+          .trim_end()
+          .to_owned(),
+      )
+    } else if ch.is_ascii_digit() || (ch == '.' && is_digit_at(&chars, i + 1)) {
+      i += 1;
+      while i < chars.len() {
+        let is_exponent_sign =
+          (chars[i] == '-' || chars[i] == '+') && matches!(chars[i - 1], 'e' | 'E');
+        if chars[i].is_ascii_alphanumeric() || chars[i] == '.' || is_exponent_sign {
+          i += 1;
+        } else {
+          break;
+        }
+      }
+      Token::Word(chars[start..i].iter().collect())
+    } else if ch.is_alphanumeric() || ch == '_' || (is_wgsl && ch == '@') {
+      i += 1;
+      while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+        i += 1;
+      }
+      let mut word: String = chars[start..i].iter().collect();
+      if is_wgsl && is_wgsl_template_generator(&word) {
+        i = push_wgsl_template_list(&chars, i, &mut word);
+      }
+      Token::Word(word)
+    } else if ch == '"' || ch == '\'' {
+      i += 1;
+      while i < chars.len() && chars[i] != ch {
+        if chars[i] == '\\' {
+          i += 1;
+        }
+        i += 1;
+      }
+      i += 1;
+      Token::Word(chars[start..i.min(chars.len())].iter().collect())
+    } else if ch == '[' {
+      let is_attribute = rest.starts_with("[[");
+      bracket_stack.push(is_attribute);
+      if is_attribute {
+        i += 2;
+        Token::Punct("[[")
+      } else {
+        i += 1;
+        Token::Punct("[")
+      }
+    } else if ch == ']' {
+      if bracket_stack.pop() == Some(true) && rest.starts_with("]]") {
+        i += 2;
+        Token::Punct("]]")
+      } else {
+        i += 1;
+        Token::Punct("]")
+      }
+    } else if let Some(punct) = PUNCTUATION.iter().find(|punct| rest.starts_with(*punct)) {
+      i += punct.len();
+      Token::Punct(punct)
+    } else {
+      // Unknown characters are kept as they are
+      i += 1;
+      Token::Word(ch.to_string())
+    };
+    tokens.push(SpacedToken {
+      token,
+      newlines_before,
+    });
+    newlines_before = 0;
+  }
+  tokens
+}
+
+fn is_wgsl_template_generator(word: &str) -> bool {
+  let is_matrix = matches!(
+    word.as_bytes(),
+    [b'm', b'a', b't', columns, b'x', rows]
+      if (b'2'..=b'4').contains(columns) && (b'2'..=b'4').contains(rows)
+  );
+  is_matrix || WGSL_TEMPLATE_GENERATORS.contains(&word)
+}
+
+// Appends the template list starting at `i`, like `<f32>`, to the word
+// without spaces, except after commas. Returns the index after it, or `i`
+// if there is no template list.
+fn push_wgsl_template_list(chars: &[char], i: usize, word: &mut String) -> usize {
+  let start = (i..chars.len())
+    .find(|&j| !chars[j].is_whitespace())
+    .unwrap_or(chars.len());
+  if chars.get(start) != Some(&'<') {
+    return i;
+  }
+  let mut template_list = String::new();
+  let mut depth = 0;
+  for (end, ch) in chars.iter().enumerate().skip(start) {
+    match ch {
+      '<' => depth += 1,
+      '>' => depth -= 1,
+      ',' => template_list.push_str(", "),
+      _ if ch.is_alphanumeric() || *ch == '_' => template_list.push(*ch),
+      _ if ch.is_whitespace() => {}
+      _ => return i,
+    }
+    if *ch == '<' || *ch == '>' {
+      template_list.push(*ch);
+    }
+    if depth == 0 {
+      word.push_str(&template_list);
+      return end + 1;
+    }
+  }
+  i
+}
+
+fn is_at_line_start(chars: &[char], i: usize) -> bool {
+  chars[..i]
+    .iter()
+    .rev()
+    .take_while(|ch| **ch != '\n')
+    .all(|ch| ch.is_whitespace())
+}
+
+fn is_digit_at(chars: &[char], i: usize) -> bool {
+  chars.get(i).is_some_and(|ch| ch.is_ascii_digit())
+}
+
+struct Formatter {
+  is_wgsl: bool,
+  output: String,
+  indent: usize,
+  paren_depth: usize,
+  // For each open brace, whether we are inside of a `case` body
+  case_stack: Vec<bool>,
+  is_in_case_label: bool,
+  has_pending_newline: bool,
+  was_top_level_closed: bool,
+  previous: Option<Token>,
+  is_previous_unary: bool,
+  // After the `=` of a top-level declaration, until its `;`
+  is_in_initializer: bool,
+  var_names: std::collections::HashMap<String, String>,
+}
+
+impl Formatter {
+  fn format(&mut self, spaced_token: &SpacedToken, next: Option<&Token>) {
+    let SpacedToken {
+      token,
+      newlines_before,
+    } = spaced_token;
+    let is_empty_statement = *token == Token::Punct(";")
+      && self.paren_depth == 0
+      && matches!(
+        self.previous,
+        Some(Token::Punct(";")) | Some(Token::Punct("{"))
+      );
+    if is_empty_statement {
+      return;
+    }
+    if self.has_pending_newline {
+      let previous_is_closing_brace = self.previous == Some(Token::Punct("}"));
+      match token {
+        Token::Word(word) if word == "else" && previous_is_closing_brace => {
+          self.output.push_str(" else");
+          self.has_pending_newline = false;
+          self.was_top_level_closed = false;
+          self.previous = Some(token.clone());
+          return;
+        }
+        Token::Punct(";") if previous_is_closing_brace => {
+          self.output.push(';');
+          self.previous = Some(token.clone());
+          return;
+        }
+        Token::Comment(comment) if *newlines_before == 0 => {
+          self.output.push(' ');
+          self.output.push_str(comment);
+          return;
+        }
+        _ => {}
+      }
+      self.output.push('\n');
+      let wants_blank_line = *newlines_before > 1 || self.was_top_level_closed;
+      let previous_is_opening_brace = self.previous == Some(Token::Punct("{"));
+      if wants_blank_line && !previous_is_opening_brace && *token != Token::Punct("}") {
+        self.output.push('\n');
+      }
+      self.has_pending_newline = false;
+      self.was_top_level_closed = false;
+    }
+    let is_at_line_start = self.output.is_empty() || self.output.ends_with('\n');
+
+    match token {
+      Token::Directive(directive) => {
+        self.output.push_str(directive);
+        self.has_pending_newline = true;
+      }
+      Token::Comment(comment) => {
+        if is_at_line_start {
+          self.push_indent();
+        } else {
+          self.output.push(' ');
+        }
+        self.output.push_str(comment);
+        self.has_pending_newline = true;
+      }
+      Token::Punct("{") => {
+        if !is_at_line_start {
+          self.output.push(' ');
+        } else {
+          self.push_indent();
+        }
+        self.output.push('{');
+        self.indent += 1;
+        self.case_stack.push(false);
+        self.has_pending_newline = true;
+      }
+      Token::Punct("}") => {
+        if let Some(true) = self.case_stack.pop() {
+          self.indent -= 1;
+        }
+        self.indent = self.indent.saturating_sub(1);
+        if !is_at_line_start {
+          self.output.push('\n');
+        }
+        self.push_indent();
+        self.output.push('}');
+        self.has_pending_newline = true;
+        if self.indent == 0 {
+          self.was_top_level_closed = true;
+          self.var_names.clear();
+        }
+      }
+      Token::Punct(";") if self.paren_depth == 0 => {
+        self.output.push(';');
+        self.has_pending_newline = true;
+      }
+      // Separates WGSL struct members
+      Token::Punct(",") if self.is_wgsl && self.paren_depth == 0 && self.indent > 0 => {
+        self.output.push(',');
+        self.has_pending_newline = true;
+      }
+      Token::Punct(":") if self.is_in_case_label => {
+        self.output.push(':');
+        self.is_in_case_label = false;
+        self.indent += 1;
+        if let Some(is_in_case_body) = self.case_stack.last_mut() {
+          *is_in_case_body = true;
+        }
+        self.has_pending_newline = true;
+      }
+      Token::Word(word) => {
+        if (word == "case" || word == "default") && next != Some(&Token::Punct("(")) {
+          self.is_in_case_label = true;
+          if let Some(is_in_case_body) = self.case_stack.last_mut() {
+            if *is_in_case_body {
+              *is_in_case_body = false;
+              self.indent -= 1;
+            }
+          }
+        }
+        let word = self.stable_name(word);
+        self.push_spacing(is_at_line_start, token);
+        self.output.push_str(&word);
+      }
+      Token::Punct(punct) => {
+        if *punct == "(" || *punct == "[" {
+          self.paren_depth += 1;
+        }
+        if *punct == ")" || *punct == "]" {
+          self.paren_depth = self.paren_depth.saturating_sub(1);
+        }
+        self.push_spacing(is_at_line_start, token);
+        self.output.push_str(punct);
+      }
+    }
+    match token {
+      Token::Punct("=") if self.indent == 0 && self.paren_depth == 0 => {
+        self.is_in_initializer = true
+      }
+      Token::Punct(";") | Token::Punct("{") => self.is_in_initializer = false,
+      _ => {}
+    }
+    self.is_previous_unary = self.is_unary(token);
+    self.previous = Some(token.clone());
+  }
+
+  // Top-level parameter and declaration types, where `*` and `&` are pointers
+  // and references rather than operators
+  fn is_in_declaration_type(&self) -> bool {
+    self.indent == 0 && !self.is_in_initializer
+  }
+
+  // Whether the operator is a prefix operator, based on the token before it
+  fn is_unary(&self, token: &Token) -> bool {
+    match token {
+      Token::Punct("!") | Token::Punct("~") => true,
+      Token::Punct("&") | Token::Punct("*") if self.is_in_declaration_type() => false,
+      Token::Punct(punct) if PREFIX_OPERATORS.contains(punct) || ["++", "--"].contains(punct) => {
+        !self.is_previous_operand()
+      }
+      _ => false,
+    }
+  }
+
+  fn is_previous_operand(&self) -> bool {
+    match &self.previous {
+      Some(Token::Word(word)) => !KEYWORDS_BEFORE_PARENS.contains(&word.as_str()) && word != "case",
+      Some(Token::Punct(punct)) => [")", "]", "]]"].contains(punct),
+      _ => false,
+    }
+  }
+
+  fn push_spacing(&mut self, is_at_line_start: bool, token: &Token) {
+    if is_at_line_start {
+      self.push_indent();
+    } else if self.has_space_before(token) {
+      self.output.push(' ');
+    }
+  }
+
+  fn has_space_before(&self, token: &Token) -> bool {
+    let previous = match &self.previous {
+      Some(previous) => previous,
+      None => return false,
+    };
+    if self.is_previous_unary {
+      return false;
+    }
+    let is_previous_operand = self.is_previous_operand();
+    if let Token::Punct(previous) = previous {
+      if ["(", "[", "[[", ".", "::"].contains(previous) || (*previous == "->" && !self.is_wgsl) {
+        return false;
+      }
+    }
+    match token {
+      Token::Punct(punct) => match *punct {
+        ")" | "]" | "]]" | "," | ";" | "." | "::" => false,
+        // WGSL's return types and declarations: `fn f(x: f32) -> f32`
+        "->" => self.is_wgsl,
+        ":" if self.is_wgsl => false,
+        "(" | "[" | "++" | "--" => !is_previous_operand,
+        // Pointer and reference declarations: `constant Input& input`
+        "&" | "*" if self.is_in_declaration_type() && is_previous_operand => false,
+        _ => true,
+      },
+      _ => true,
+    }
+  }
+
+  fn stable_name(&mut self, word: &str) -> String {
+    if !is_printer_temporary(word) {
+      return word.to_owned();
+    }
+    let next_id = self.var_names.len();
+    self
+      .var_names
+      .entry(word.to_owned())
+      .or_insert_with(|| format!("__var__{}", next_id))
+      .clone()
+  }
+
+  fn push_indent(&mut self) {
+    self.output.push_str(&"  ".repeat(self.indent));
+  }
+
+  fn finish(mut self) -> String {
+    self.output.push('\n');
+    self.output
+  }
+}
+
+fn is_printer_temporary(word: &str) -> bool {
+  word
+    .strip_prefix("__var__")
+    .is_some_and(|id| !id.is_empty() && id.chars().all(|ch| ch.is_ascii_digit()))
+}
+
+#[test]
+fn test_format_msl() {
+  let msl = "float4 pixel_color (float2 coordinates, constant Input& INPUT){\n  \
+    auto uv = coordinates / INPUT . window_size; // 0 <-> 1\n  \
+    uv . x *= - 1.0;\n\n\n  \
+    float2 __var__7 = f (uv , 1.0);\n  \
+    if (uv . x > 1.0)\n  {\n  return float4 (uv , __var__7);\n  }\n  \
+    else\n  {\n  return float4 (! uv . x);\n  }\n}\n";
+  assert_eq!(
+    format_msl(msl),
+    "float4 pixel_color(float2 coordinates, constant Input& INPUT) {\n  \
+    auto uv = coordinates / INPUT.window_size; // 0 <-> 1\n  \
+    uv.x *= -1.0;\n\n  \
+    float2 __var__0 = f(uv, 1.0);\n  \
+    if (uv.x > 1.0) {\n    return float4(uv, __var__0);\n  \
+    } else {\n    return float4(!uv.x);\n  }\n}\n"
+  );
+}
+
+#[test]
+fn test_format_msl_is_idempotent() {
+  let msl = "#include <metal_stdlib>\nstruct Input {\n  float2 window_size;\n};\n\
+    uint f (uint i) {switch (i) {case 0: return 1; default: return i ++;}}\n\
+    void g () {for (auto i = 0; i < 3; i++) {auto a = (i > 1) ? - i : i * 2;}}";
+  let formatted = format_msl(msl);
+  assert_eq!(format_msl(&formatted), formatted);
+  assert!(formatted.contains("for (auto i = 0; i < 3; i++) {"));
+  assert!(formatted.contains("    case 0:\n      return 1;\n    default:\n"));
+  assert!(formatted.contains("auto a = (i > 1) ? -i : i * 2;"));
+}
+
+#[test]
+fn test_format_msl_brackets_and_pointers() {
+  let msl = "constant float k = 2.0 * 3.0;\n\
+    vertex float4 v (const device float4 * rect [[ buffer(0) ]], uint i [[vertex_id]]) {\n\
+    float x = a [idx [i]];\n\
+    return float4 (x * k);\n\
+    }";
+  assert_eq!(
+    format_msl(msl),
+    "constant float k = 2.0 * 3.0;\n\
+    vertex float4 v(const device float4* rect [[buffer(0)]], uint i [[vertex_id]]) {\n  \
+    float x = a[idx[i]];\n  \
+    return float4(x * k);\n\
+    }\n"
+  );
+}
+
+#[test]
+fn test_format_wgsl() {
+  let wgsl = "struct Input {\n  size: vec2 < f32 >,\n  m: mat2x2 < f32 >, a: array < vec2 < f32 > , 4 >,\n}\n\
+    @group(0) @binding(0) var < uniform > INPUT : Input;\n\
+    @fragment fn f(@builtin(position) p : vec4 < f32 >) -> @location(0) vec4 < f32 >{\n\
+    let x : f32 = INPUT . size . x;\n\
+    return vec4 < f32 > (p . xy , x , 1.0);\n}\n";
+  let formatted = format_wgsl(wgsl);
+  assert_eq!(
+    formatted,
+    "struct Input {\n  size: vec2<f32>,\n  m: mat2x2<f32>,\n  a: array<vec2<f32>, 4>,\n}\n\n\
+    @group(0) @binding(0) var<uniform> INPUT: Input;\n\
+    @fragment fn f(@builtin(position) p: vec4<f32>) -> @location(0) vec4<f32> {\n  \
+    let x: f32 = INPUT.size.x;\n  \
+    return vec4<f32>(p.xy, x, 1.0);\n}\n"
+  );
+  assert_eq!(format_wgsl(&formatted), formatted);
+}
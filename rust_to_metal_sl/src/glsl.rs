@@ -0,0 +1,241 @@
+use super::backend::{BuiltinFunction, InputBinding, ShaderBackend, StructField, MATH_CONSTANTS};
+use super::EnhanceConfig;
+
+/// OpenGL ES Shading Language 3.0, as used by ShaderToy
+///
+/// The output can be pasted directly into ShaderToy: the entry point is
+/// wrapped in `mainImage`, and `INPUT` is filled in from ShaderToy's uniforms.
+/// The input struct needs to be part of the transpiled source, so the shader
+/// should be transpiled together with the interface.
+pub struct GlslBackend;
+
+const FLOAT_TYPES: [&str; 4] = ["float", "vec2", "vec3", "vec4"];
+
+impl ShaderBackend for GlslBackend {
+  fn name(&self) -> &'static str {
+    "GLSL"
+  }
+
+  fn scalar_type(&self, rust_type: &str) -> Option<String> {
+    let glsl_type = match rust_type {
+      "bool" => "bool",
+      "i32" => "int",
+      "u32" => "uint",
+      "f32" => "float",
+      _ => return None,
+    };
+    Some(glsl_type.to_owned())
+  }
+
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Option<String> {
+    let prefix = match rust_scalar_type {
+      "bool" => "b",
+      "i32" => "i",
+      "u32" => "u",
+      "f32" => "",
+      _ => return None,
+    };
+    Some(format!("{}vec{}", prefix, arity))
+  }
+
+  fn matrix_type(&self, rust_scalar_type: &str, dimension: usize) -> Option<String> {
+    match rust_scalar_type {
+      "f32" => Some(format!("mat{}", dimension)),
+      _ => None,
+    }
+  }
+
+  fn builtin_function(&self, rust_method: &str) -> Option<BuiltinFunction> {
+    let glsl_function = match rust_method {
+      "clamped" => "clamp",
+      "magnitude" => "length",
+      "magnitude_squared" => "length_squared",
+      "face_forward" => "faceforward",
+      "normalized" => "normalize",
+      "reflected" => "reflect",
+      "refracted" => "refract",
+      "atan2" => "atan",
+      "rsqrt" => "inversesqrt",
+      _ => return None,
+    };
+    Some(BuiltinFunction::Function(glsl_function.to_owned()))
+  }
+
+  fn input_binding(&self) -> InputBinding {
+    InputBinding::Global
+  }
+
+  fn input_declaration(&self, config: &EnhanceConfig, _fields: &[StructField]) -> String {
+    format!("{} {};\n", config.param_type, config.constant_name)
+  }
+
+  fn entry_point_wrapper(&self, config: &EnhanceConfig, input_fields: &[StructField]) -> String {
+    let input_initialization = if input_fields.is_empty() {
+      String::new()
+    } else {
+      format!(
+        "  {} = {}(\n{}\n  );\n",
+        config.constant_name,
+        config.param_type,
+        input_fields
+          .iter()
+          .map(|field| format!("    {}", shader_toy_input(field)))
+          .collect::<Vec<_>>()
+          .join(",\n")
+      )
+    };
+    // ShaderToy's origin is at the bottom left, ShaderRoy's at the top left
+    format!(
+      "// ShaderToy entry point\n\
+       void mainImage(out vec4 fragColor, in vec2 fragCoord) {{\n\
+       {input_initialization}  \
+         fragColor = {entry_point}(vec2(fragCoord.x, iResolution.y - fragCoord.y));\n\
+       }}\n",
+      input_initialization = input_initialization,
+      entry_point = config.entry_point_fn_name,
+    )
+  }
+
+  fn prelude(&self) -> String {
+    let constants = MATH_CONSTANTS
+      .iter()
+      .map(|(name, value)| format!("const float {} = {:?};\n", name, value));
+    // `mod` rounds towards negative infinity, `fmod` towards zero
+    let fmod = FLOAT_TYPES.iter().map(|ty| {
+      format!(
+        "{ty} fmod({ty} x, {ty} y) {{ return x - y * trunc(x / y); }}\n",
+        ty = ty
+      )
+    });
+    let saturate = FLOAT_TYPES.iter().map(|ty| {
+      format!(
+        "{ty} saturate({ty} x) {{ return clamp(x, 0.0, 1.0); }}\n",
+        ty = ty
+      )
+    });
+    let squared = FLOAT_TYPES[1..].iter().map(|ty| {
+      format!(
+        "float length_squared({ty} x) {{ return dot(x, x); }}\n\
+         float distance_squared({ty} x, {ty} y) {{ return dot(x - y, x - y); }}\n",
+        ty = ty
+      )
+    });
+    constants
+      .chain(std::iter::once("\n".to_owned()))
+      .chain(fmod)
+      .chain(saturate)
+      .chain(squared)
+      .chain(std::iter::once("\n".to_owned()))
+      .collect()
+  }
+
+  fn function_signature(&self, name: &str, params: &[String], return_type: Option<&str>) -> String {
+    format!(
+      "{} {} ({})",
+      return_type.unwrap_or("void"),
+      name,
+      params.join(", ")
+    )
+  }
+
+  fn parameter(&self, name: &str, ty: &str, _address_space: Option<&str>) -> String {
+    format!("{} {}", ty, name)
+  }
+
+  fn local_declaration(&self, name: &str, ty: Option<&str>, _is_mutable: bool) -> String {
+    // Types are always known, see `needs_explicit_local_types`
+    format!("{} {}", ty.unwrap_or_default(), name)
+  }
+
+  fn for_range(
+    &self,
+    var: &str,
+    ty: Option<&str>,
+    from: &str,
+    to: &str,
+    is_inclusive: bool,
+  ) -> String {
+    format!(
+      "for ({declaration} = {from}; {var} <{limit} {to}; {var}++)",
+      declaration = self.local_declaration(var, ty, true),
+      var = var,
+      from = from,
+      to = to,
+      limit = if is_inclusive { "=" } else { "" },
+    )
+  }
+
+  // Identifiers containing `__` are reserved in GLSL
+  fn temporary_variable(&self, id: u64) -> String {
+    format!("_var_{}", id)
+  }
+
+  fn needs_explicit_local_types(&self) -> bool {
+    true
+  }
+
+  fn has_implicit_vector_splat(&self) -> bool {
+    false
+  }
+}
+
+// The ShaderToy uniform equivalent to the given `Input` field
+fn shader_toy_input(field: &StructField) -> String {
+  match field.name.as_str() {
+    "window_size" => "iResolution.xy",
+    "window_position" => "vec2(0.0)",
+    "cursor_position" => "vec2(iMouse.x, iResolution.y - iMouse.y)",
+    // ShaderToy only tracks the cursor while the mouse button is pressed
+    "is_cursor_inside_window" => "iMouse.z > 0.0",
+    "elapsed_time_secs" => "iTime",
+    "elapsed_time_since_last_frame_secs" => "iTimeDelta",
+    "frame_count" => "float(iFrame + 1)",
+    // ShaderToy's months start at 0 and there's no time zone
+    "year_month_day_tz" => "vec4(iDate.x, iDate.y + 1.0, iDate.z, 0.0)",
+    _ => return format!("{}(0)", field.ty),
+  }
+  .to_owned()
+}
+
+#[test]
+fn test_examples_parse_as_glsl() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let interface =
+    include_str!("../../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs");
+  // What ShaderToy wraps the pasted code in, in a version naga accepts
+  let header = "#version 450\n\
+    layout(set = 0, binding = 0) uniform ShaderToy {\n\
+      vec3 iResolution; float iTime; float iTimeDelta; int iFrame; vec4 iMouse; vec4 iDate;\n\
+    };\n";
+  let footer = "layout(location = 0) out vec4 outColor;\n\
+    void main() { mainImage(outColor, gl_FragCoord.xy); }\n";
+  // Examples go first, because of their inner attributes
+  for example in &[
+    include_str!("../../examples/simple/simple.rs").to_owned(),
+    include_str!("../../examples/mandelbrot/mandelbrot.rs").to_owned(),
+    include_str!("../../examples/two_dimensional_sdf/two_dimensional_sdf.rs").to_owned(),
+    format!(
+      "{}{}",
+      include_str!("../../examples/raymarching_eyes/raymarching_eyes.rs"),
+      include_str!("../../examples/raymarching_eyes/sdf_utils.rs")
+    ),
+  ] {
+    let glsl =
+      crate::transpile_with_backend(&format!("{}{}", example, interface), &config, &GlslBackend)
+        .unwrap();
+    let source = format!("{}{}{}", header, glsl, footer);
+    // Parsing type checks all expressions. The module isn't validated, because
+    // naga requires functions to be defined before they're called,
+    // while we rely on forward declarations.
+    naga::front::glsl::Frontend::default()
+      .parse(
+        &naga::front::glsl::Options::from(naga::ShaderStage::Fragment),
+        &source,
+      )
+      .unwrap_or_else(|errors| panic!("{:?}\n{}", errors, source));
+  }
+}
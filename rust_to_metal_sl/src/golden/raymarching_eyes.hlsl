@@ -0,0 +1,321 @@
+static const float MAXFLOAT = 3.4028235e38;
+static const float M_E_F = 2.7182817;
+static const float M_LOG2E_F = 1.442695;
+static const float M_LOG10E_F = 0.4342945;
+static const float M_LN2_F = 0.6931472;
+static const float M_LN10_F = 2.3025851;
+static const float M_PI_F = 3.1415927;
+static const float M_PI_2_F = 1.5707964;
+static const float M_PI_4_F = 0.7853982;
+static const float M_1_PI_F = 0.31830987;
+static const float M_2_PI_F = 0.63661975;
+static const float M_2_SQRTPI_F = 1.1283792;
+static const float M_SQRT2_F = 1.4142135;
+static const float M_SQRT1_2_F = 0.70710677;
+
+float length_squared(float2 x) {
+  return dot(x, x);
+}
+
+float distance_squared(float2 x, float2 y) {
+  return dot(x - y, x - y);
+}
+
+float length_squared(float3 x) {
+  return dot(x, x);
+}
+
+float distance_squared(float3 x, float3 y) {
+  return dot(x - y, x - y);
+}
+
+float length_squared(float4 x) {
+  return dot(x, x);
+}
+
+float distance_squared(float4 x, float4 y) {
+  return dot(x - y, x - y);
+}
+
+float4 pixel_color(float2 coordinates);
+
+float4 sample_color(float2 coordinates);
+
+float2 scene(float3 pos);
+
+float3 infinite_repeat(float3 pos, float period);
+
+float3 repeat(float3 pos, float period, float3 limit);
+
+float sdf(float3 pos);
+
+float3 render(float3 ray_origin, float3 ray_dir, float2 uv);
+
+float2 cast_ray(float3 ray_origin, float3 ray_dir);
+
+float3 calc_normal(float3 pos);
+
+float3 get_camera_ray_dir(float2 uv, float3 cam_pos, float3 cam_target);
+
+float2 sky();
+
+float2 white(float d);
+
+float2 black(float d);
+
+float3 surface_color(float material);
+
+float2 join(float2 a, float2 b);
+
+float3 palette(float t, float3 a, float3 b, float3 c, float3 d);
+
+float2 screen_to_world(float2 screen);
+
+float3 shade(float sd);
+
+float subtract(float d1, float d2);
+
+float sd_sphere(float3 p, float3 center, float radius);
+
+float sd_box(float2 p, float2 pos, float2 size);
+
+float smin_cubic(float a, float b, float k);
+
+float op_u(float d1, float d2);
+
+float op_blend(float d1, float d2);
+
+/// This is the `constant` information passed to the pixel shader from ShaderRoy on each frame.
+struct Input {
+  /// Window size in physical units (at the native resolution of the display device).
+  float2 window_size;
+  /// Window's top left corner position in physical units.
+  float2 window_position;
+  /// The cursor left (x) and top (y) position in physical units.
+  float2 cursor_position;
+  /// Whether the cursor is inside the window and the window is focused.
+  ///
+  /// Defaults to false until the cursor moves inside or enters the window.
+  bool is_cursor_inside_window;
+  /// Time since starting the program, in fractions of seconds.
+  float elapsed_time_secs;
+  /// Time since the rendering of the previous frame.
+  float elapsed_time_since_last_frame_secs;
+  /// Number of frames rendered so far, starting with 1.
+  float frame_count;
+  /// Local calendar year, month, day. tz is UTC timezone offset in secs.
+  float4 year_month_day_tz;
+};
+
+cbuffer InputBuffer : register(b0) {
+  Input INPUT;
+};
+
+float4 pixel_color(float2 coordinates) {
+  int num_samples_per_axis = 3;
+  float4 color = ((float4)(0.0));
+  for (int y = 0; y < num_samples_per_axis; y++) {
+    for (int x = 0; x < num_samples_per_axis; x++) {
+      color += sample_color(coordinates + float2(float(x), float(y)) / (float(num_samples_per_axis)));
+    }
+  }
+  color /= float((num_samples_per_axis * num_samples_per_axis));
+  return color;
+}
+
+float4 sample_color(float2 coordinates) {
+  float3 cam_pos = float3(0.0, 0.0, -1.0);
+  float3 cam_target = float3(0.0, 0.0, 0.0);
+  float2 uv = screen_to_world(coordinates);
+  float3 ray_dir = get_camera_ray_dir(uv, cam_pos, cam_target);
+  float3 col = render(cam_pos, ray_dir, uv);
+  float3 gamma_corrected = pow(col, 0.4545);
+  return float4(gamma_corrected, 1.0);
+}
+
+float2 scene(float3 pos) {
+  float3 copy = repeat(pos + float3(0.11, 0.0, 0.0), 0.3, float3(1.0, 1.0, 0.0));
+  float3 grid = repeat(pos, 0.3, float3(1.0, 1.0, 0.0));
+  float3 eye_dir;
+  if (INPUT.is_cursor_inside_window) {
+    eye_dir = normalize(float3(screen_to_world(INPUT.cursor_position) / 2.0 - pos.xy, -0.05));
+  } else {
+    eye_dir = normalize(float3(sin(INPUT.elapsed_time_secs), cos(INPUT.elapsed_time_secs) + 0.5, -0.5));
+  }
+  return join(white(sd_sphere(grid, float3(0.0, 0.0, 0.0), 0.05)), join(black(sd_sphere(grid, eye_dir / 80.0, 0.04)), join(white(sd_sphere(copy, float3(0.0, 0.0, 0.0), 0.05)), black(sd_sphere(copy, eye_dir / 80.0, 0.04)))));
+  // red_plush(sd_sphere(q, vec3(0.0, 0.0, 0.0), 0.05)).min(sd_sphere(
+  //   grid,
+  //   vec3(0.0, 0.0, 0.0),
+  //   0.01,
+  // ))
+}
+
+float3 infinite_repeat(float3 pos, float period) {
+  return fmod((abs(pos) + 0.5 * period), period) - 0.5 * period;
+}
+
+float3 repeat(float3 pos, float period, float3 limit) {
+  return pos - period * clamp(round((pos / period)), -limit, limit);
+}
+
+float sdf(float3 pos) {
+  return scene(pos).x;
+}
+
+float3 render(float3 ray_origin, float3 ray_dir, float2 uv) {
+  float2 __var__0 = cast_ray(ray_origin, ray_dir);
+  float d = __var__0.x;
+  float material = __var__0.y;
+  if (material <= 0.0) {
+    // Skybox colour
+    return float3(0.30, 0.36, 0.60) - (ray_dir.y * 0.7);
+  } else {
+    float3 pos = ray_origin + ray_dir * d;
+    float3 normal = calc_normal(pos);
+    float3 light_dir;
+    if (INPUT.is_cursor_inside_window) {
+      light_dir = normalize(float3(screen_to_world(INPUT.cursor_position) - uv, -0.5));
+    } else {
+      light_dir = normalize(float3(sin(INPUT.elapsed_time_secs), cos(INPUT.elapsed_time_secs) + 0.5, -0.5));
+    }
+    // L is vector from surface point to light, N is surface normal. N and L must be normalized!
+    float brightness = max(dot(normal, light_dir), 0.0);
+    float3 light_color = float3(1.80, 1.27, 0.99) * brightness;
+    float3 ambient = float3(0.03, 0.04, 0.1);
+    float3 diffuse = surface_color(material) * (light_color + ambient);
+    float shadow = 0.0;
+    float3 shadow_ray_origin = pos + normal * 0.01;
+    float shadow_t = cast_ray(shadow_ray_origin, light_dir).x;
+    if (shadow_t >= -1.0) {
+      shadow = 1.0;
+    }
+    return lerp(diffuse, diffuse * 0.8, shadow);
+    // let N = calc_normal(pos);
+    // N * 0.5.vec3() + 0.5.vec3()
+  }
+}
+
+float2 cast_ray(float3 ray_origin, float3 ray_dir) {
+  float t = 0.0; // Stores current distance along ray
+  float z_clipping_distance = 6.0;
+  for (int __var__0 = 0; __var__0 < 64; __var__0++) {
+    float2 __var__1 = scene(ray_origin + ray_dir * t);
+    float d = __var__1.x;
+    float material = __var__1.y;
+    if (d < (0.0001 * t)) {
+      return float2(t, material);
+    }
+    t += d;
+    if (t > z_clipping_distance) {
+      return sky();
+    }
+  }
+  return sky();
+}
+
+float3 calc_normal(float3 pos) {
+  // Center sample
+  float c = sdf(pos);
+  // Use offset samples to compute gradient / normal
+  float2 eps_zero = float2(0.001, 0.0);
+  return normalize((float3(sdf(pos + eps_zero.xyy), sdf(pos + eps_zero.yxy), sdf(pos + eps_zero.yyx)) - c));
+}
+
+float3 get_camera_ray_dir(float2 uv, float3 cam_pos, float3 cam_target) {
+  // Calculate camera's "orthonormal basis", i.e. its transform matrix components
+  float3 cam_forward = normalize((cam_target - cam_pos));
+  float3 cam_right = normalize((cross(float3(0.0, 1.0, 0.0), cam_forward)));
+  float3 cam_up = normalize((cross(cam_forward, cam_right)));
+  float f_persp = 2.0;
+  return normalize((uv.x * cam_right + uv.y * cam_up + cam_forward * f_persp));
+}
+
+float2 sky() {
+  return float2(-1.0, 0.0);
+}
+
+float2 white(float d) {
+  return float2(d, 1.0);
+}
+
+float2 black(float d) {
+  return float2(d, 2.0);
+}
+
+float3 surface_color(float material) {
+  if (material <= 1.0) {
+    return float3(0.9, 0.9, 0.9);
+  } else {
+    return float3(0.1, 0.1, 0.1);
+  }
+}
+
+float2 join(float2 a, float2 b) {
+  if (a.x < b.x) {
+    return a;
+  } else {
+    return b;
+  }
+}
+
+// // --- Misc functions
+// // https://www.shadertoy.com/view/ll2GD3
+float3 palette(float t, float3 a, float3 b, float3 c, float3 d) {
+  t = clamp(t, 0., 1.);
+  return a + b * cos((6.28318 * (c * t + d)));
+}
+
+float2 screen_to_world(float2 screen) {
+  float2 size = INPUT.window_size;
+  float2 result = 2.0 * (screen / size - 0.5);
+  result.x *= size.x / size.y;
+  result.y *= -1.0;
+  return result;
+}
+
+float3 shade(float sd) {
+  float max_dist = 2.0;
+  float3 pal_col = palette(clamp((0.5 - sd * 0.4), -max_dist, max_dist), float3(0.3, 0.3, 0.0), float3(0.8, 0.8, 0.1), float3(0.9, 0.7, 0.0), float3(0.3, 0.9, 0.8));
+  float3 col = pal_col;
+  // Darken around surface
+  col = lerp(col, col * 1.0 - exp((-10.0 * abs(sd))), 0.4);
+  // repeating lines
+  col *= 0.8 + 0.2 * cos((150.0 * sd));
+  // White outline at surface
+  col = lerp(col, ((float3)(1.0)), (1.0 - smoothstep(0.0, 0.01, abs(sd))));
+  return col;
+}
+
+// --- SDF utility library
+float subtract(float d1, float d2) {
+  return -max(d1, d2);
+}
+
+float sd_sphere(float3 p, float3 center, float radius) {
+  return distance(p, center) - radius;
+}
+
+float sd_box(float2 p, float2 pos, float2 size) {
+  float2 d = abs((p - pos)) - size;
+  return clamp(d.x, d.y, 0.0) + length(max(d, 0.0));
+}
+
+// polynomial smooth min (k = 0.1);
+float smin_cubic(float a, float b, float k) {
+  float h = k - max(abs((a - b)), 0.0);
+  return min(a, b) - h * h * h / (6.0 * k * k);
+}
+
+float op_u(float d1, float d2) {
+  return min(d1, d2);
+}
+
+float op_blend(float d1, float d2) {
+  float k = 0.2;
+  return smin_cubic(d1, d2, k);
+}
+
+// The fragment shader
+float4 fragment_shader(float4 position : SV_Position) : SV_Target {
+  return pixel_color(position.xy);
+}
@@ -0,0 +1,194 @@
+use super::backend::{BuiltinFunction, InputBinding, ShaderBackend, StructField, MATH_CONSTANTS};
+use super::EnhanceConfig;
+
+/// High-Level Shading Language, for Direct3D and Unity
+///
+/// `INPUT` is bound as a `cbuffer`, so the shader should be transpiled
+/// together with the interface.
+pub struct HlslBackend;
+
+impl ShaderBackend for HlslBackend {
+  fn name(&self) -> &'static str {
+    "HLSL"
+  }
+
+  fn scalar_type(&self, rust_type: &str) -> Option<String> {
+    let hlsl_type = match rust_type {
+      "bool" => "bool",
+      "i32" => "int",
+      "u32" => "uint",
+      "i64" => "int64_t",
+      "u64" => "uint64_t",
+      "f16" => "half",
+      "f32" => "float",
+      _ => return None,
+    };
+    Some(hlsl_type.to_owned())
+  }
+
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("{}{}", scalar_type, arity))
+  }
+
+  fn matrix_type(&self, rust_scalar_type: &str, dimension: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("{}{}x{}", scalar_type, dimension, dimension))
+  }
+
+  // HLSL's `fmod` truncates like MSL's, so it keeps its name
+  fn builtin_function(&self, rust_method: &str) -> Option<BuiltinFunction> {
+    let hlsl_function = match rust_method {
+      "clamped" => "clamp",
+      "magnitude" => "length",
+      "magnitude_squared" => "length_squared",
+      "face_forward" => "faceforward",
+      "normalized" => "normalize",
+      "reflected" => "reflect",
+      "refracted" => "refract",
+      "mix" => "lerp",
+      "fract" => "frac",
+      _ => return None,
+    };
+    Some(BuiltinFunction::Function(hlsl_function.to_owned()))
+  }
+
+  fn input_binding(&self) -> InputBinding {
+    InputBinding::Global
+  }
+
+  fn input_declaration(&self, config: &EnhanceConfig, _fields: &[StructField]) -> String {
+    format!(
+      "cbuffer {param_type}Buffer : register(b0) {{\n  {param_type} {constant};\n}};\n",
+      param_type = config.param_type,
+      constant = config.constant_name,
+    )
+  }
+
+  fn entry_point_wrapper(&self, config: &EnhanceConfig, _input_fields: &[StructField]) -> String {
+    format!(
+      "// The fragment shader\n\
+       float4 fragment_shader(float4 position : SV_Position) : SV_Target {{\n  \
+         return {entry_point}(position.xy);\n\
+       }}\n",
+      entry_point = config.entry_point_fn_name,
+    )
+  }
+
+  fn prelude(&self) -> String {
+    let constants = MATH_CONSTANTS
+      .iter()
+      .map(|(name, value)| format!("static const float {} = {:?};\n", name, value));
+    let squared = ["float2", "float3", "float4"].iter().map(|ty| {
+      format!(
+        "float length_squared({ty} x) {{ return dot(x, x); }}\n\
+         float distance_squared({ty} x, {ty} y) {{ return dot(x - y, x - y); }}\n",
+        ty = ty
+      )
+    });
+    constants
+      .chain(std::iter::once("\n".to_owned()))
+      .chain(squared)
+      .chain(std::iter::once("\n".to_owned()))
+      .collect()
+  }
+
+  fn function_signature(&self, name: &str, params: &[String], return_type: Option<&str>) -> String {
+    format!(
+      "{} {} ({})",
+      return_type.unwrap_or("void"),
+      name,
+      params.join(", ")
+    )
+  }
+
+  fn parameter(&self, name: &str, ty: &str, _address_space: Option<&str>) -> String {
+    format!("{} {}", ty, name)
+  }
+
+  fn local_declaration(&self, name: &str, ty: Option<&str>, _is_mutable: bool) -> String {
+    // Types are always known, see `needs_explicit_local_types`
+    format!("{} {}", ty.unwrap_or_default(), name)
+  }
+
+  fn for_range(
+    &self,
+    var: &str,
+    ty: Option<&str>,
+    from: &str,
+    to: &str,
+    is_inclusive: bool,
+  ) -> String {
+    format!(
+      "for ({declaration} = {from}; {var} <{limit} {to}; {var}++)",
+      declaration = self.local_declaration(var, ty, true),
+      var = var,
+      from = from,
+      to = to,
+      limit = if is_inclusive { "=" } else { "" },
+    )
+  }
+
+  fn needs_explicit_local_types(&self) -> bool {
+    true
+  }
+
+  fn has_scalar_vector_constructor(&self) -> bool {
+    false
+  }
+
+  fn matrix_multiplication_function(&self) -> Option<String> {
+    Some("mul".to_owned())
+  }
+}
+
+#[test]
+fn test_raymarching_example_matches_golden_hlsl() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let source = format!(
+    "{}{}{}",
+    include_str!("../../examples/raymarching_eyes/raymarching_eyes.rs"),
+    include_str!("../../examples/raymarching_eyes/sdf_utils.rs"),
+    include_str!("../../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs")
+  );
+  let hlsl =
+    crate::format_msl(&crate::transpile_with_backend(&source, &config, &HlslBackend).unwrap());
+  // Run with `UPDATE_GOLDEN=1` after intended changes to the output
+  let golden_path = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/src/golden/raymarching_eyes.hlsl"
+  );
+  if std::env::var_os("UPDATE_GOLDEN").is_some() {
+    std::fs::write(golden_path, &hlsl).unwrap();
+  }
+  assert_eq!(hlsl, std::fs::read_to_string(golden_path).unwrap());
+}
+
+#[test]
+fn test_matrix_multiplication_uses_mul() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let hlsl = crate::transpile_with_backend(
+    "fn rotate(m: Mat3, n: Mat3, v: Vec3) -> Vec3 {
+      let mut r = m * n;
+      r *= n;
+      (r * v + 1.0.vec3()) * 2.0
+    }",
+    &config,
+    &HlslBackend,
+  )
+  .unwrap();
+  assert!(hlsl.contains("float3 rotate (float3x3 m, float3x3 n, float3 v)"));
+  assert!(hlsl.contains("float3x3 r = mul (m , n);"));
+  assert!(hlsl.contains("r = mul (r , n);"));
+  assert!(hlsl.contains("(mul (r , v) + ((float3) (1.0))) * 2.0"));
+}
@@ -0,0 +1,127 @@
+use super::backend::{
+  BuiltinFunction, FunctionAttribute, InputBinding, ShaderBackend, StructField,
+};
+use super::EnhanceConfig;
+
+/// Metal Shading Language
+pub struct MslBackend;
+
+impl ShaderBackend for MslBackend {
+  fn name(&self) -> &'static str {
+    "MSL"
+  }
+
+  fn scalar_type(&self, rust_type: &str) -> Option<String> {
+    let msl_type = match rust_type {
+      "bool" => "bool",
+      "i8" => "char",
+      "u8" => "uchar",
+      "i16" => "short",
+      "u16" => "ushort",
+      "i32" => "int",
+      "u32" => "uint",
+      "i64" => "long",
+      "u64" => "ulong",
+      "f16" => "half",
+      "f32" => "float",
+      _ => return None,
+    };
+    Some(msl_type.to_owned())
+  }
+
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("{}{}", scalar_type, arity))
+  }
+
+  fn matrix_type(&self, rust_scalar_type: &str, dimension: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("{}{}x{}", scalar_type, dimension, dimension))
+  }
+
+  fn builtin_function(&self, rust_method: &str) -> Option<BuiltinFunction> {
+    let msl_function = match rust_method {
+      "clamped" => "clamp",
+      "magnitude" => "length",
+      "magnitude_squared" => "length_squared",
+      "face_forward" => "faceforward",
+      "normalized" => "normalize",
+      "reflected" => "reflect",
+      "refracted" => "refract",
+      "min" => "fmin",
+      "max" => "fmax",
+      _ => return None,
+    };
+    Some(BuiltinFunction::Function(msl_function.to_owned()))
+  }
+
+  fn input_binding(&self) -> InputBinding {
+    InputBinding::Parameter {
+      address_space: "constant".to_owned(),
+    }
+  }
+
+  fn entry_point_wrapper(&self, config: &EnhanceConfig, _input_fields: &[StructField]) -> String {
+    format!(
+      "// The fragment shader\n\
+       fragment float4 fragment_shader(\n  \
+         float4 in [[position]],\n  \
+         constant {param_type}& input [[ buffer(0) ]]\n\
+       ) {{\n  \
+         return {entry_point}(float2(in.x, in.y), input);\n\
+       }}\n",
+      param_type = config.param_type,
+      entry_point = config.entry_point_fn_name,
+    )
+  }
+
+  fn function_signature(&self, name: &str, params: &[String], return_type: Option<&str>) -> String {
+    format!(
+      "{} {} ({})",
+      return_type.unwrap_or("void"),
+      name,
+      params.join(", ")
+    )
+  }
+
+  fn parameter(&self, name: &str, ty: &str, address_space: Option<&str>) -> String {
+    match address_space {
+      Some(address_space) => format!("{} {}& {}", address_space, ty, name),
+      None => format!("{} {}", ty, name),
+    }
+  }
+
+  fn local_declaration(&self, name: &str, ty: Option<&str>, _is_mutable: bool) -> String {
+    format!("{} {}", ty.unwrap_or("auto"), name)
+  }
+
+  fn for_range(
+    &self,
+    var: &str,
+    ty: Option<&str>,
+    from: &str,
+    to: &str,
+    is_inclusive: bool,
+  ) -> String {
+    format!(
+      "for ({ty} {var} = {from}; {var} <{limit} {to}; {var}++)",
+      ty = ty.unwrap_or("auto"),
+      var = var,
+      from = from,
+      to = to,
+      limit = if is_inclusive { "=" } else { "" },
+    )
+  }
+
+  fn function_attribute(&self, attribute: FunctionAttribute) -> Option<String> {
+    let qualifier = match attribute {
+      FunctionAttribute::Inline => "inline",
+      FunctionAttribute::AlwaysInline => "__attribute__((always_inline)) inline",
+      FunctionAttribute::NeverInline => "__attribute__((noinline))",
+      FunctionAttribute::Cold => "__attribute__((cold))",
+    };
+    Some(qualifier.to_owned())
+  }
+}
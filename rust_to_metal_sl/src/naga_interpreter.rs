@@ -0,0 +1,1125 @@
+//! Runs a `naga::Module` on the CPU, so that the transpiled shader can be
+//! compared with its Rust source without a GPU.
+//!
+//! Only what the transpiled fragment shaders use is supported: scalars,
+//! vectors, matrices, structs and arrays, control flow and function calls,
+//! and most math functions. Textures, atomics, barriers and derivatives are
+//! reported as errors.
+
+use anyhow::{bail, Context, Result};
+use naga::{Handle, TypeInner};
+
+// Guards against transpiled loops which never finish
+const MAX_LOOP_ITERATIONS: usize = 1 << 20;
+
+/// The value of a naga expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+  Bool(bool),
+  I32(i32),
+  U32(u32),
+  F32(f32),
+  /// Vector components, matrix columns, struct members or array elements
+  Composite(Vec<Value>),
+}
+
+impl Value {
+  /// The components of a float vector, or the float scalar.
+  pub fn to_f32s(&self) -> Option<Vec<f32>> {
+    match self {
+      Value::F32(value) => Some(vec![*value]),
+      Value::Composite(components) => components
+        .iter()
+        .map(|component| match component {
+          Value::F32(value) => Some(*value),
+          _ => None,
+        })
+        .collect(),
+      _ => None,
+    }
+  }
+
+  fn floats(&self) -> Result<Vec<f32>> {
+    self
+      .to_f32s()
+      .with_context(|| format!("Expected a float scalar or vector, found {:?}", self))
+  }
+}
+
+pub struct NagaInterpreter<'m> {
+  module: &'m naga::Module,
+  // Indexed by the handles of `module.const_expressions`
+  constants: Vec<Value>,
+  globals: Vec<Value>,
+}
+
+impl<'m> NagaInterpreter<'m> {
+  pub fn new(module: &'m naga::Module) -> Result<Self> {
+    let mut interpreter = Self {
+      module,
+      constants: Vec::new(),
+      globals: Vec::new(),
+    };
+    for (_, expression) in module.const_expressions.iter() {
+      let value = interpreter.evaluate_constant(expression)?;
+      interpreter.constants.push(value);
+    }
+    for (_, global) in module.global_variables.iter() {
+      let value = match global.init {
+        Some(init) => interpreter.constants[init.index()].clone(),
+        None => interpreter.zero_value(global.ty)?,
+      };
+      interpreter.globals.push(value);
+    }
+    Ok(interpreter)
+  }
+
+  /// Sets the struct bound at `group` and `binding` from its fields, matched
+  /// by name. Missing fields are zero, and `bool` fields are converted
+  /// when the struct has a `u32` in their place.
+  pub fn set_uniform(&mut self, group: u32, binding: u32, fields: &[(&str, Value)]) -> Result<()> {
+    let (handle, global) = self
+      .module
+      .global_variables
+      .iter()
+      .find(|(_, global)| global.binding == Some(naga::ResourceBinding { group, binding }))
+      .with_context(|| format!("No global is bound at group {} binding {}", group, binding))?;
+    let members = match &self.module.types[global.ty].inner {
+      TypeInner::Struct { members, .. } => members,
+      _ => bail!(
+        "The global at group {} binding {} is not a struct",
+        group,
+        binding
+      ),
+    };
+    let value = members
+      .iter()
+      .map(|member| {
+        let field = fields
+          .iter()
+          .find(|(name, _)| Some(*name) == member.name.as_deref());
+        match (field, &self.module.types[member.ty].inner) {
+          (
+            Some((_, Value::Bool(value))),
+            TypeInner::Scalar {
+              kind: naga::ScalarKind::Uint,
+              ..
+            },
+          ) => Ok(Value::U32(*value as u32)),
+          (Some((_, value)), _) => Ok(value.clone()),
+          (None, _) => self.zero_value(member.ty),
+        }
+      })
+      .collect::<Result<_>>()?;
+    self.globals[handle.index()] = Value::Composite(value);
+    Ok(())
+  }
+
+  /// Calls the entry point with the given arguments and returns its result.
+  pub fn run_entry_point(&mut self, name: &str, arguments: Vec<Value>) -> Result<Value> {
+    let module = self.module;
+    let entry_point = module
+      .entry_points
+      .iter()
+      .find(|entry_point| entry_point.name == name)
+      .with_context(|| format!("Missing entry point `{}`", name))?;
+    self
+      .call(&entry_point.function, arguments)?
+      .with_context(|| format!("The entry point `{}` doesn't return a value", name))
+  }
+
+  fn call(&mut self, function: &'m naga::Function, arguments: Vec<Value>) -> Result<Option<Value>> {
+    let mut frame = Frame {
+      function,
+      arguments,
+      locals: Vec::new(),
+      expressions: vec![None; function.expressions.len()],
+    };
+    self
+      .run(&mut frame)
+      .with_context(|| format!("In `{}`", function.name.as_deref().unwrap_or("?")))
+  }
+
+  fn run(&mut self, frame: &mut Frame<'m>) -> Result<Option<Value>> {
+    let function = frame.function;
+    for (_, local) in function.local_variables.iter() {
+      let value = match local.init {
+        Some(init) => self.evaluate_value(frame, init)?,
+        None => self.zero_value(local.ty)?,
+      };
+      frame.locals.push(value);
+    }
+    Ok(match self.execute_block(frame, &function.body)? {
+      Flow::Return(value) => value,
+      _ => None,
+    })
+  }
+
+  fn execute_block(&mut self, frame: &mut Frame<'m>, block: &'m naga::Block) -> Result<Flow> {
+    for statement in block.iter() {
+      match self.execute(frame, statement)? {
+        Flow::Next => {}
+        flow => return Ok(flow),
+      }
+    }
+    Ok(Flow::Next)
+  }
+
+  fn execute(&mut self, frame: &mut Frame<'m>, statement: &'m naga::Statement) -> Result<Flow> {
+    use naga::Statement as S;
+    match statement {
+      S::Emit(range) => {
+        // Reevaluated every time, as loads see the latest stores
+        for handle in range.clone() {
+          let evaluated = self.evaluate_uncached(frame, handle)?;
+          frame.expressions[handle.index()] = Some(evaluated);
+        }
+      }
+      S::Block(block) => return self.execute_block(frame, block),
+      S::If {
+        condition,
+        accept,
+        reject,
+      } => {
+        let block = if self.evaluate_bool(frame, *condition)? {
+          accept
+        } else {
+          reject
+        };
+        return self.execute_block(frame, block);
+      }
+      S::Switch { selector, cases } => {
+        let selector = self.evaluate_value(frame, *selector)?;
+        let start = cases
+          .iter()
+          .position(|case| match (case.value, &selector) {
+            (naga::SwitchValue::I32(value), Value::I32(selector)) => value == *selector,
+            (naga::SwitchValue::U32(value), Value::U32(selector)) => value == *selector,
+            _ => false,
+          })
+          .or_else(|| {
+            cases
+              .iter()
+              .position(|case| case.value == naga::SwitchValue::Default)
+          });
+        if let Some(start) = start {
+          for case in &cases[start..] {
+            match self.execute_block(frame, &case.body)? {
+              Flow::Next if case.fall_through => {}
+              Flow::Next | Flow::Break => break,
+              flow => return Ok(flow),
+            }
+          }
+        }
+      }
+      S::Loop {
+        body,
+        continuing,
+        break_if,
+      } => {
+        for _ in 0..MAX_LOOP_ITERATIONS {
+          match self.execute_block(frame, body)? {
+            Flow::Break => return Ok(Flow::Next),
+            Flow::Return(value) => return Ok(Flow::Return(value)),
+            Flow::Next | Flow::Continue => {}
+          }
+          self.execute_block(frame, continuing)?;
+          if let Some(break_if) = break_if {
+            if self.evaluate_bool(frame, *break_if)? {
+              return Ok(Flow::Next);
+            }
+          }
+        }
+        bail!("A loop didn't finish in {} iterations", MAX_LOOP_ITERATIONS)
+      }
+      S::Break => return Ok(Flow::Break),
+      S::Continue => return Ok(Flow::Continue),
+      S::Return { value } => {
+        let value = value
+          .map(|value| self.evaluate_value(frame, value))
+          .transpose()?;
+        return Ok(Flow::Return(value));
+      }
+      S::Store { pointer, value } => {
+        let pointer = self.evaluate_pointer(frame, *pointer)?;
+        let value = self.evaluate_value(frame, *value)?;
+        self.store(frame, &pointer, value)?;
+      }
+      S::Call {
+        function,
+        arguments,
+        result,
+      } => {
+        let arguments = arguments
+          .iter()
+          .map(|argument| self.evaluate_value(frame, *argument))
+          .collect::<Result<Vec<_>>>()?;
+        let module = self.module;
+        let value = self.call(&module.functions[*function], arguments)?;
+        if let Some(result) = result {
+          let value = value.context("The called function doesn't return a value")?;
+          frame.expressions[result.index()] = Some(Evaluated::Value(value));
+        }
+      }
+      _ => bail!("Unsupported statement {:?}", statement),
+    }
+    Ok(Flow::Next)
+  }
+
+  fn evaluate(
+    &mut self,
+    frame: &mut Frame<'m>,
+    handle: Handle<naga::Expression>,
+  ) -> Result<Evaluated> {
+    // Expressions which are not emitted never change, so they are cached
+    if let Some(evaluated) = &frame.expressions[handle.index()] {
+      return Ok(evaluated.clone());
+    }
+    let evaluated = self.evaluate_uncached(frame, handle)?;
+    frame.expressions[handle.index()] = Some(evaluated.clone());
+    Ok(evaluated)
+  }
+
+  fn evaluate_value(
+    &mut self,
+    frame: &mut Frame<'m>,
+    handle: Handle<naga::Expression>,
+  ) -> Result<Value> {
+    match self.evaluate(frame, handle)? {
+      Evaluated::Value(value) => Ok(value),
+      Evaluated::Pointer(_) => bail!("Expected a value, found a pointer"),
+    }
+  }
+
+  fn evaluate_pointer(
+    &mut self,
+    frame: &mut Frame<'m>,
+    handle: Handle<naga::Expression>,
+  ) -> Result<Pointer> {
+    match self.evaluate(frame, handle)? {
+      Evaluated::Pointer(pointer) => Ok(pointer),
+      Evaluated::Value(value) => bail!("Expected a pointer, found {:?}", value),
+    }
+  }
+
+  fn evaluate_bool(
+    &mut self,
+    frame: &mut Frame<'m>,
+    handle: Handle<naga::Expression>,
+  ) -> Result<bool> {
+    match self.evaluate_value(frame, handle)? {
+      Value::Bool(value) => Ok(value),
+      value => bail!("Expected a bool, found {:?}", value),
+    }
+  }
+
+  fn evaluate_uncached(
+    &mut self,
+    frame: &mut Frame<'m>,
+    handle: Handle<naga::Expression>,
+  ) -> Result<Evaluated> {
+    use naga::Expression as E;
+    let function = frame.function;
+    let value = match &function.expressions[handle] {
+      E::Literal(literal) => literal_value(literal)?,
+      E::Constant(constant) => self.constant_value(*constant),
+      E::ZeroValue(ty) => self.zero_value(*ty)?,
+      E::Compose { ty, components } => {
+        let components = components
+          .iter()
+          .map(|component| self.evaluate_value(frame, *component))
+          .collect::<Result<_>>()?;
+        self.compose(*ty, components)
+      }
+      E::Access { base, index } => {
+        let index = match self.evaluate_value(frame, *index)? {
+          Value::I32(index) if index >= 0 => index as usize,
+          Value::U32(index) => index as usize,
+          index => bail!("Invalid index {:?}", index),
+        };
+        return self.access(frame, *base, index);
+      }
+      E::AccessIndex { base, index } => return self.access(frame, *base, *index as usize),
+      E::Splat { size, value } => {
+        Value::Composite(vec![self.evaluate_value(frame, *value)?; *size as usize])
+      }
+      E::Swizzle {
+        size,
+        vector,
+        pattern,
+      } => {
+        let vector = self.evaluate_value(frame, *vector)?;
+        Value::Composite(
+          pattern[..*size as usize]
+            .iter()
+            .map(|component| index(&vector, *component as usize).cloned())
+            .collect::<Result<_>>()?,
+        )
+      }
+      E::FunctionArgument(index) => frame.arguments[*index as usize].clone(),
+      E::GlobalVariable(global) => {
+        return Ok(Evaluated::Pointer(Pointer {
+          root: Root::Global(global.index()),
+          path: Vec::new(),
+        }))
+      }
+      E::LocalVariable(local) => {
+        return Ok(Evaluated::Pointer(Pointer {
+          root: Root::Local(local.index()),
+          path: Vec::new(),
+        }))
+      }
+      E::Load { pointer } => {
+        let pointer = self.evaluate_pointer(frame, *pointer)?;
+        self.load(frame, &pointer)?
+      }
+      E::Unary { op, expr } => {
+        let value = self.evaluate_value(frame, *expr)?;
+        map_scalars(value, &|value| unary(*op, value))?
+      }
+      E::Binary { op, left, right } => {
+        let left = self.evaluate_value(frame, *left)?;
+        let right = self.evaluate_value(frame, *right)?;
+        binary(*op, left, right)?
+      }
+      E::Select {
+        condition,
+        accept,
+        reject,
+      } => {
+        let condition = self.evaluate_value(frame, *condition)?;
+        let accept = self.evaluate_value(frame, *accept)?;
+        let reject = self.evaluate_value(frame, *reject)?;
+        select(condition, accept, reject)?
+      }
+      E::Relational { fun, argument } => {
+        let argument = self.evaluate_value(frame, *argument)?;
+        relational(*fun, argument)?
+      }
+      E::Math {
+        fun,
+        arg,
+        arg1,
+        arg2,
+        arg3,
+      } => {
+        let arguments = std::iter::once(arg)
+          .chain(arg1)
+          .chain(arg2)
+          .chain(arg3)
+          .map(|argument| self.evaluate_value(frame, *argument))
+          .collect::<Result<Vec<_>>>()?;
+        math(*fun, arguments)?
+      }
+      E::As {
+        expr,
+        kind,
+        convert,
+      } => {
+        let value = self.evaluate_value(frame, *expr)?;
+        map_scalars(value, &|value| cast(value, *kind, *convert))?
+      }
+      expression => bail!("Unsupported expression {:?}", expression),
+    };
+    Ok(Evaluated::Value(value))
+  }
+
+  fn access(
+    &mut self,
+    frame: &mut Frame<'m>,
+    base: Handle<naga::Expression>,
+    index_value: usize,
+  ) -> Result<Evaluated> {
+    Ok(match self.evaluate(frame, base)? {
+      Evaluated::Pointer(mut pointer) => {
+        pointer.path.push(index_value);
+        Evaluated::Pointer(pointer)
+      }
+      Evaluated::Value(value) => Evaluated::Value(index(&value, index_value)?.clone()),
+    })
+  }
+
+  fn load(&self, frame: &Frame<'m>, pointer: &Pointer) -> Result<Value> {
+    let mut value = match pointer.root {
+      Root::Local(local) => &frame.locals[local],
+      Root::Global(global) => &self.globals[global],
+    };
+    for &index_value in &pointer.path {
+      value = index(value, index_value)?;
+    }
+    Ok(value.clone())
+  }
+
+  fn store(&mut self, frame: &mut Frame<'m>, pointer: &Pointer, new_value: Value) -> Result<()> {
+    let mut value = match pointer.root {
+      Root::Local(local) => &mut frame.locals[local],
+      Root::Global(global) => &mut self.globals[global],
+    };
+    for &index_value in &pointer.path {
+      value = match value {
+        Value::Composite(components) => components
+          .get_mut(index_value)
+          .with_context(|| format!("Index {} is out of bounds", index_value))?,
+        value => bail!("Can't index into {:?}", value),
+      };
+    }
+    *value = new_value;
+    Ok(())
+  }
+
+  fn evaluate_constant(&self, expression: &naga::Expression) -> Result<Value> {
+    use naga::Expression as E;
+    Ok(match expression {
+      E::Literal(literal) => literal_value(literal)?,
+      E::Constant(constant) => self.constant_value(*constant),
+      E::ZeroValue(ty) => self.zero_value(*ty)?,
+      E::Compose { ty, components } => self.compose(
+        *ty,
+        components
+          .iter()
+          .map(|component| self.constants[component.index()].clone())
+          .collect(),
+      ),
+      E::Splat { size, value } => {
+        Value::Composite(vec![self.constants[value.index()].clone(); *size as usize])
+      }
+      expression => bail!("Unsupported constant expression {:?}", expression),
+    })
+  }
+
+  fn constant_value(&self, constant: Handle<naga::Constant>) -> Value {
+    self.constants[self.module.constants[constant].init.index()].clone()
+  }
+
+  // Vectors are composed from scalars and other vectors, which are flattened
+  fn compose(&self, ty: Handle<naga::Type>, components: Vec<Value>) -> Value {
+    if let TypeInner::Vector { .. } = self.module.types[ty].inner {
+      let mut scalars = Vec::new();
+      for component in components {
+        match component {
+          Value::Composite(inner) => scalars.extend(inner),
+          scalar => scalars.push(scalar),
+        }
+      }
+      Value::Composite(scalars)
+    } else {
+      Value::Composite(components)
+    }
+  }
+
+  fn zero_value(&self, ty: Handle<naga::Type>) -> Result<Value> {
+    Ok(match &self.module.types[ty].inner {
+      TypeInner::Scalar { kind, .. } => zero_scalar(*kind),
+      TypeInner::Vector { size, kind, .. } => {
+        Value::Composite(vec![zero_scalar(*kind); *size as usize])
+      }
+      TypeInner::Matrix { columns, rows, .. } => {
+        Value::Composite(vec![
+          Value::Composite(vec![Value::F32(0.0); *rows as usize]);
+          *columns as usize
+        ])
+      }
+      TypeInner::Array {
+        base,
+        size: naga::ArraySize::Constant(size),
+        ..
+      } => Value::Composite(vec![self.zero_value(*base)?; size.get() as usize]),
+      TypeInner::Struct { members, .. } => Value::Composite(
+        members
+          .iter()
+          .map(|member| self.zero_value(member.ty))
+          .collect::<Result<_>>()?,
+      ),
+      ty => bail!("Unsupported type {:?}", ty),
+    })
+  }
+}
+
+struct Frame<'m> {
+  function: &'m naga::Function,
+  arguments: Vec<Value>,
+  locals: Vec<Value>,
+  // Indexed by the handles of `function.expressions`
+  expressions: Vec<Option<Evaluated>>,
+}
+
+#[derive(Clone)]
+enum Evaluated {
+  Value(Value),
+  Pointer(Pointer),
+}
+
+// A path of component indices into a variable
+#[derive(Clone)]
+struct Pointer {
+  root: Root,
+  path: Vec<usize>,
+}
+
+#[derive(Clone, Copy)]
+enum Root {
+  Local(usize),
+  Global(usize),
+}
+
+enum Flow {
+  Next,
+  Break,
+  Continue,
+  Return(Option<Value>),
+}
+
+fn literal_value(literal: &naga::Literal) -> Result<Value> {
+  Ok(match *literal {
+    naga::Literal::Bool(value) => Value::Bool(value),
+    naga::Literal::I32(value) => Value::I32(value),
+    naga::Literal::U32(value) => Value::U32(value),
+    naga::Literal::F32(value) => Value::F32(value),
+    naga::Literal::F64(_) => bail!("f64 is not supported"),
+  })
+}
+
+fn zero_scalar(kind: naga::ScalarKind) -> Value {
+  match kind {
+    naga::ScalarKind::Bool => Value::Bool(false),
+    naga::ScalarKind::Sint => Value::I32(0),
+    naga::ScalarKind::Uint => Value::U32(0),
+    naga::ScalarKind::Float => Value::F32(0.0),
+  }
+}
+
+fn index(value: &Value, index: usize) -> Result<&Value> {
+  match value {
+    Value::Composite(components) => components
+      .get(index)
+      .with_context(|| format!("Index {} is out of bounds", index)),
+    value => bail!("Can't index into {:?}", value),
+  }
+}
+
+// Applies `f` to every scalar of a vector or matrix
+fn map_scalars(value: Value, f: &impl Fn(Value) -> Result<Value>) -> Result<Value> {
+  match value {
+    Value::Composite(components) => Ok(Value::Composite(
+      components
+        .into_iter()
+        .map(|component| map_scalars(component, f))
+        .collect::<Result<_>>()?,
+    )),
+    scalar => f(scalar),
+  }
+}
+
+// Applies `f` to the matching scalars, a scalar is paired with every
+// component of the other value
+fn zip_scalars(
+  left: Value,
+  right: Value,
+  f: &impl Fn(Value, Value) -> Result<Value>,
+) -> Result<Value> {
+  Ok(match (left, right) {
+    (Value::Composite(left), Value::Composite(right)) => {
+      if left.len() != right.len() {
+        bail!("Mismatched sizes {} and {}", left.len(), right.len());
+      }
+      Value::Composite(
+        left
+          .into_iter()
+          .zip(right)
+          .map(|(left, right)| zip_scalars(left, right, f))
+          .collect::<Result<_>>()?,
+      )
+    }
+    (Value::Composite(left), right) => Value::Composite(
+      left
+        .into_iter()
+        .map(|left| zip_scalars(left, right.clone(), f))
+        .collect::<Result<_>>()?,
+    ),
+    (left, Value::Composite(right)) => Value::Composite(
+      right
+        .into_iter()
+        .map(|right| zip_scalars(left.clone(), right, f))
+        .collect::<Result<_>>()?,
+    ),
+    (left, right) => f(left, right)?,
+  })
+}
+
+fn map_floats(value: Value, f: impl Fn(f32) -> f32) -> Result<Value> {
+  map_scalars(value, &|value| match value {
+    Value::F32(value) => Ok(Value::F32(f(value))),
+    value => bail!("Expected a float, found {:?}", value),
+  })
+}
+
+fn zip_floats(left: Value, right: Value, f: impl Fn(f32, f32) -> f32) -> Result<Value> {
+  zip_scalars(left, right, &|left, right| match (left, right) {
+    (Value::F32(left), Value::F32(right)) => Ok(Value::F32(f(left, right))),
+    (left, right) => bail!("Expected floats, found {:?} and {:?}", left, right),
+  })
+}
+
+fn unary(op: naga::UnaryOperator, value: Value) -> Result<Value> {
+  use naga::UnaryOperator as Op;
+  Ok(match (op, value) {
+    (Op::Negate, Value::F32(value)) => Value::F32(-value),
+    (Op::Negate, Value::I32(value)) => Value::I32(value.wrapping_neg()),
+    (Op::LogicalNot, Value::Bool(value)) => Value::Bool(!value),
+    (Op::BitwiseNot, Value::I32(value)) => Value::I32(!value),
+    (Op::BitwiseNot, Value::U32(value)) => Value::U32(!value),
+    (op, value) => bail!("Unsupported {:?} of {:?}", op, value),
+  })
+}
+
+fn binary(op: naga::BinaryOperator, left: Value, right: Value) -> Result<Value> {
+  if op == naga::BinaryOperator::Multiply {
+    if let Some(product) = matrix_product(&left, &right)? {
+      return Ok(product);
+    }
+  }
+  zip_scalars(left, right, &|left, right| scalar_binary(op, left, right))
+}
+
+fn scalar_binary(op: naga::BinaryOperator, left: Value, right: Value) -> Result<Value> {
+  use naga::BinaryOperator as Op;
+  let result = match (&left, &right) {
+    (&Value::F32(l), &Value::F32(r)) => compare(op, l, r).or(match op {
+      Op::Add => Some(Value::F32(l + r)),
+      Op::Subtract => Some(Value::F32(l - r)),
+      Op::Multiply => Some(Value::F32(l * r)),
+      Op::Divide => Some(Value::F32(l / r)),
+      // Truncated like `fmod`
+      Op::Modulo => Some(Value::F32(l % r)),
+      _ => None,
+    }),
+    (&Value::I32(l), &Value::I32(r)) => compare(op, l, r).or(match op {
+      Op::Add => Some(Value::I32(l.wrapping_add(r))),
+      Op::Subtract => Some(Value::I32(l.wrapping_sub(r))),
+      Op::Multiply => Some(Value::I32(l.wrapping_mul(r))),
+      Op::Divide => l.checked_div(r).map(Value::I32),
+      Op::Modulo => l.checked_rem(r).map(Value::I32),
+      Op::And => Some(Value::I32(l & r)),
+      Op::ExclusiveOr => Some(Value::I32(l ^ r)),
+      Op::InclusiveOr => Some(Value::I32(l | r)),
+      _ => None,
+    }),
+    (&Value::U32(l), &Value::U32(r)) => compare(op, l, r).or(match op {
+      Op::Add => Some(Value::U32(l.wrapping_add(r))),
+      Op::Subtract => Some(Value::U32(l.wrapping_sub(r))),
+      Op::Multiply => Some(Value::U32(l.wrapping_mul(r))),
+      Op::Divide => l.checked_div(r).map(Value::U32),
+      Op::Modulo => l.checked_rem(r).map(Value::U32),
+      Op::And => Some(Value::U32(l & r)),
+      Op::ExclusiveOr => Some(Value::U32(l ^ r)),
+      Op::InclusiveOr => Some(Value::U32(l | r)),
+      Op::ShiftLeft => Some(Value::U32(l.wrapping_shl(r))),
+      Op::ShiftRight => Some(Value::U32(l.wrapping_shr(r))),
+      _ => None,
+    }),
+    // The shift amount is always unsigned
+    (&Value::I32(l), &Value::U32(r)) => match op {
+      Op::ShiftLeft => Some(Value::I32(l.wrapping_shl(r))),
+      Op::ShiftRight => Some(Value::I32(l.wrapping_shr(r))),
+      _ => None,
+    },
+    (&Value::Bool(l), &Value::Bool(r)) => match op {
+      Op::Equal => Some(Value::Bool(l == r)),
+      Op::NotEqual => Some(Value::Bool(l != r)),
+      Op::LogicalAnd | Op::And => Some(Value::Bool(l && r)),
+      Op::LogicalOr | Op::InclusiveOr => Some(Value::Bool(l || r)),
+      _ => None,
+    },
+    _ => None,
+  };
+  result.with_context(|| format!("Can't evaluate {:?} of {:?} and {:?}", op, left, right))
+}
+
+fn compare<T: PartialOrd>(op: naga::BinaryOperator, left: T, right: T) -> Option<Value> {
+  use naga::BinaryOperator as Op;
+  Some(Value::Bool(match op {
+    Op::Equal => left == right,
+    Op::NotEqual => left != right,
+    Op::Less => left < right,
+    Op::LessEqual => left <= right,
+    Op::Greater => left > right,
+    Op::GreaterEqual => left >= right,
+    _ => return None,
+  }))
+}
+
+// Matrices are composites of column vectors
+fn matrix_product(left: &Value, right: &Value) -> Result<Option<Value>> {
+  let is_vector = |value: &Value| matches!(value, Value::Composite(_));
+  Ok(Some(match (matrix_columns(left), matrix_columns(right)) {
+    (Some(left), Some(right)) => Value::Composite(
+      right
+        .iter()
+        .map(|column| matrix_times_vector(left, column))
+        .collect::<Result<_>>()?,
+    ),
+    (Some(left), None) if is_vector(right) => matrix_times_vector(left, right)?,
+    (None, Some(right)) if is_vector(left) => Value::Composite(
+      right
+        .iter()
+        .map(|column| Ok(Value::F32(dot(left, column)?)))
+        .collect::<Result<_>>()?,
+    ),
+    _ => return Ok(None),
+  }))
+}
+
+fn matrix_columns(value: &Value) -> Option<&[Value]> {
+  match value {
+    Value::Composite(columns) if matches!(columns.first(), Some(Value::Composite(_))) => {
+      Some(columns)
+    }
+    _ => None,
+  }
+}
+
+fn matrix_times_vector(columns: &[Value], vector: &Value) -> Result<Value> {
+  let mut sum = None;
+  for (column, component) in columns.iter().zip(vector.floats()?) {
+    let term = binary(
+      naga::BinaryOperator::Multiply,
+      column.clone(),
+      Value::F32(component),
+    )?;
+    sum = Some(match sum {
+      Some(sum) => binary(naga::BinaryOperator::Add, sum, term)?,
+      None => term,
+    });
+  }
+  sum.context("Empty matrix")
+}
+
+fn dot(left: &Value, right: &Value) -> Result<f32> {
+  Ok(
+    left
+      .floats()?
+      .iter()
+      .zip(right.floats()?)
+      .map(|(left, right)| left * right)
+      .sum(),
+  )
+}
+
+fn select(condition: Value, accept: Value, reject: Value) -> Result<Value> {
+  Ok(match (condition, accept, reject) {
+    (Value::Bool(condition), accept, reject) => {
+      if condition {
+        accept
+      } else {
+        reject
+      }
+    }
+    (Value::Composite(condition), Value::Composite(accept), Value::Composite(reject)) => {
+      Value::Composite(
+        condition
+          .into_iter()
+          .zip(accept.into_iter().zip(reject))
+          .map(|(condition, (accept, reject))| select(condition, accept, reject))
+          .collect::<Result<_>>()?,
+      )
+    }
+    (condition, _, _) => bail!("Invalid select condition {:?}", condition),
+  })
+}
+
+fn relational(fun: naga::RelationalFunction, argument: Value) -> Result<Value> {
+  use naga::RelationalFunction as Rf;
+  let bools = || match &argument {
+    Value::Composite(components) => components
+      .iter()
+      .map(|component| match component {
+        Value::Bool(value) => Ok(*value),
+        value => bail!("Expected a bool, found {:?}", value),
+      })
+      .collect::<Result<Vec<_>>>(),
+    value => bail!("Expected a bool vector, found {:?}", value),
+  };
+  Ok(match fun {
+    Rf::All => Value::Bool(bools()?.into_iter().all(|value| value)),
+    Rf::Any => Value::Bool(bools()?.into_iter().any(|value| value)),
+    Rf::IsNan => map_scalars(argument, &|value| match value {
+      Value::F32(value) => Ok(Value::Bool(value.is_nan())),
+      value => bail!("Expected a float, found {:?}", value),
+    })?,
+    Rf::IsInf => map_scalars(argument, &|value| match value {
+      Value::F32(value) => Ok(Value::Bool(value.is_infinite())),
+      value => bail!("Expected a float, found {:?}", value),
+    })?,
+  })
+}
+
+fn cast(value: Value, kind: naga::ScalarKind, convert: Option<naga::Bytes>) -> Result<Value> {
+  use naga::ScalarKind as K;
+  let value = match convert {
+    Some(4) => value,
+    Some(width) => bail!("Unsupported conversion to width {}", width),
+    // Bitcast
+    None => {
+      let bits = match value {
+        Value::F32(value) => value.to_bits(),
+        Value::I32(value) => value as u32,
+        Value::U32(value) => value,
+        value => bail!("Can't bitcast {:?}", value),
+      };
+      return Ok(match kind {
+        K::Float => Value::F32(f32::from_bits(bits)),
+        K::Sint => Value::I32(bits as i32),
+        K::Uint => Value::U32(bits),
+        K::Bool => bail!("Can't bitcast to bool"),
+      });
+    }
+  };
+  Ok(match (value, kind) {
+    (Value::F32(value), K::Float) => Value::F32(value),
+    (Value::F32(value), K::Sint) => Value::I32(value as i32),
+    (Value::F32(value), K::Uint) => Value::U32(value as u32),
+    (Value::F32(value), K::Bool) => Value::Bool(value != 0.0),
+    (Value::I32(value), K::Float) => Value::F32(value as f32),
+    (Value::I32(value), K::Sint) => Value::I32(value),
+    (Value::I32(value), K::Uint) => Value::U32(value as u32),
+    (Value::I32(value), K::Bool) => Value::Bool(value != 0),
+    (Value::U32(value), K::Float) => Value::F32(value as f32),
+    (Value::U32(value), K::Sint) => Value::I32(value as i32),
+    (Value::U32(value), K::Uint) => Value::U32(value),
+    (Value::U32(value), K::Bool) => Value::Bool(value != 0),
+    (Value::Bool(value), K::Float) => Value::F32(if value { 1.0 } else { 0.0 }),
+    (Value::Bool(value), K::Sint) => Value::I32(value as i32),
+    (Value::Bool(value), K::Uint) => Value::U32(value as u32),
+    (Value::Bool(value), K::Bool) => Value::Bool(value),
+    (value, kind) => bail!("Can't convert {:?} to {:?}", value, kind),
+  })
+}
+
+fn math(fun: naga::MathFunction, arguments: Vec<Value>) -> Result<Value> {
+  use naga::BinaryOperator as Op;
+  use naga::MathFunction as Mf;
+  let mut arguments = arguments.into_iter();
+  let mut argument = || arguments.next().context("Missing argument");
+  Ok(match fun {
+    Mf::Abs => map_scalars(argument()?, &|value| {
+      Ok(match value {
+        Value::F32(value) => Value::F32(value.abs()),
+        Value::I32(value) => Value::I32(value.wrapping_abs()),
+        value => value,
+      })
+    })?,
+    Mf::Min => zip_scalars(argument()?, argument()?, &|left, right| {
+      min_max(left, right, false)
+    })?,
+    Mf::Max => zip_scalars(argument()?, argument()?, &|left, right| {
+      min_max(left, right, true)
+    })?,
+    Mf::Clamp => {
+      let (value, low, high) = (argument()?, argument()?, argument()?);
+      let value = math(Mf::Max, vec![value, low])?;
+      math(Mf::Min, vec![value, high])?
+    }
+    Mf::Saturate => map_floats(argument()?, |value| value.clamp(0.0, 1.0))?,
+    Mf::Cos => map_floats(argument()?, f32::cos)?,
+    Mf::Cosh => map_floats(argument()?, f32::cosh)?,
+    Mf::Sin => map_floats(argument()?, f32::sin)?,
+    Mf::Sinh => map_floats(argument()?, f32::sinh)?,
+    Mf::Tan => map_floats(argument()?, f32::tan)?,
+    Mf::Tanh => map_floats(argument()?, f32::tanh)?,
+    Mf::Acos => map_floats(argument()?, f32::acos)?,
+    Mf::Asin => map_floats(argument()?, f32::asin)?,
+    Mf::Atan => map_floats(argument()?, f32::atan)?,
+    Mf::Atan2 => zip_floats(argument()?, argument()?, f32::atan2)?,
+    Mf::Radians => map_floats(argument()?, f32::to_radians)?,
+    Mf::Degrees => map_floats(argument()?, f32::to_degrees)?,
+    Mf::Ceil => map_floats(argument()?, f32::ceil)?,
+    Mf::Floor => map_floats(argument()?, f32::floor)?,
+    Mf::Round => map_floats(argument()?, round_half_to_even)?,
+    Mf::Fract => map_floats(argument()?, |value| value - value.floor())?,
+    Mf::Trunc => map_floats(argument()?, f32::trunc)?,
+    Mf::Exp => map_floats(argument()?, f32::exp)?,
+    Mf::Exp2 => map_floats(argument()?, f32::exp2)?,
+    Mf::Log => map_floats(argument()?, f32::ln)?,
+    Mf::Log2 => map_floats(argument()?, f32::log2)?,
+    Mf::Pow => zip_floats(argument()?, argument()?, f32::powf)?,
+    Mf::Sqrt => map_floats(argument()?, f32::sqrt)?,
+    Mf::InverseSqrt => map_floats(argument()?, |value| 1.0 / value.sqrt())?,
+    Mf::Sign => map_scalars(argument()?, &|value| {
+      Ok(match value {
+        // Unlike `signum`, 0 for zero
+        Value::F32(value) if value == 0.0 => Value::F32(value),
+        Value::F32(value) => Value::F32(value.signum()),
+        Value::I32(value) => Value::I32(value.signum()),
+        value => bail!("Can't take the sign of {:?}", value),
+      })
+    })?,
+    Mf::Fma => {
+      let product = binary(Op::Multiply, argument()?, argument()?)?;
+      binary(Op::Add, product, argument()?)?
+    }
+    Mf::Dot => Value::F32(dot(&argument()?, &argument()?)?),
+    Mf::Cross => {
+      let (a, b) = (argument()?.floats()?, argument()?.floats()?);
+      if a.len() != 3 || b.len() != 3 {
+        bail!("`cross` needs 3 component vectors");
+      }
+      Value::Composite(vec![
+        Value::F32(a[1] * b[2] - a[2] * b[1]),
+        Value::F32(a[2] * b[0] - a[0] * b[2]),
+        Value::F32(a[0] * b[1] - a[1] * b[0]),
+      ])
+    }
+    Mf::Distance => {
+      let difference = binary(Op::Subtract, argument()?, argument()?)?;
+      Value::F32(length(&difference)?)
+    }
+    Mf::Length => Value::F32(length(&argument()?)?),
+    Mf::Normalize => {
+      let vector = argument()?;
+      let length = length(&vector)?;
+      binary(Op::Divide, vector, Value::F32(length))?
+    }
+    Mf::FaceForward => {
+      let (normal, incident, reference) = (argument()?, argument()?, argument()?);
+      if dot(&reference, &incident)? < 0.0 {
+        normal
+      } else {
+        unary_negate(normal)?
+      }
+    }
+    Mf::Reflect => {
+      let (incident, normal) = (argument()?, argument()?);
+      let scale = Value::F32(2.0 * dot(&normal, &incident)?);
+      let offset = binary(Op::Multiply, normal, scale)?;
+      binary(Op::Subtract, incident, offset)?
+    }
+    Mf::Refract => {
+      let (incident, normal, eta) = (argument()?, argument()?, argument()?);
+      let eta = match eta {
+        Value::F32(eta) => eta,
+        eta => bail!("Invalid `refract` ratio {:?}", eta),
+      };
+      let n_dot_i = dot(&normal, &incident)?;
+      let k = 1.0 - eta * eta * (1.0 - n_dot_i * n_dot_i);
+      if k < 0.0 {
+        map_floats(incident, |_| 0.0)?
+      } else {
+        let refracted = binary(Op::Multiply, incident, Value::F32(eta))?;
+        let offset = binary(Op::Multiply, normal, Value::F32(eta * n_dot_i + k.sqrt()))?;
+        binary(Op::Subtract, refracted, offset)?
+      }
+    }
+    Mf::Mix => {
+      let (x, y, a) = (argument()?, argument()?, argument()?);
+      let difference = binary(Op::Subtract, y, x.clone())?;
+      binary(Op::Add, x, binary(Op::Multiply, difference, a)?)?
+    }
+    Mf::Step => zip_floats(argument()?, argument()?, |edge, value| {
+      if value < edge {
+        0.0
+      } else {
+        1.0
+      }
+    })?,
+    Mf::SmoothStep => {
+      let (low, high, value) = (argument()?, argument()?, argument()?);
+      let range = binary(Op::Subtract, high, low.clone())?;
+      let t = binary(Op::Divide, binary(Op::Subtract, value, low)?, range)?;
+      map_floats(t, |t| {
+        let t = t.clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+      })?
+    }
+    Mf::Transpose => {
+      let columns = argument()?;
+      let columns = matrix_columns(&columns).context("`transpose` needs a matrix")?;
+      let rows = columns.first().map_or(Ok(Vec::new()), Value::floats)?.len();
+      Value::Composite(
+        (0..rows)
+          .map(|row| {
+            Ok(Value::Composite(
+              columns
+                .iter()
+                .map(|column| index(column, row).cloned())
+                .collect::<Result<_>>()?,
+            ))
+          })
+          .collect::<Result<_>>()?,
+      )
+    }
+    fun => bail!("Unsupported math function {:?}", fun),
+  })
+}
+
+fn min_max(left: Value, right: Value, is_max: bool) -> Result<Value> {
+  let is_right = match (&left, &right) {
+    (Value::F32(left), Value::F32(right)) => (right > left) == is_max,
+    (Value::I32(left), Value::I32(right)) => (right > left) == is_max,
+    (Value::U32(left), Value::U32(right)) => (right > left) == is_max,
+    _ => bail!("Can't compare {:?} and {:?}", left, right),
+  };
+  Ok(if is_right { right } else { left })
+}
+
+fn unary_negate(value: Value) -> Result<Value> {
+  map_scalars(value, &|value| unary(naga::UnaryOperator::Negate, value))
+}
+
+fn length(value: &Value) -> Result<f32> {
+  Ok(dot(value, value)?.sqrt())
+}
+
+// What `round` does in WGSL and SPIR-V, unlike Rust and Metal, which round
+// halfway cases away from zero
+fn round_half_to_even(value: f32) -> f32 {
+  if (value - value.trunc()).abs() == 0.5 {
+    2.0 * (value / 2.0).round()
+  } else {
+    value.round()
+  }
+}
+
+#[test]
+fn test_runs_transpiled_examples() {
+  let config = super::EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let interface =
+    include_str!("../../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs");
+  let source = format!(
+    "{}{}",
+    include_str!("../../examples/simple/simple.rs"),
+    interface
+  );
+  let wgsl = super::transpile_with_backend(&source, &config, &super::WgslBackend).unwrap();
+  let modules = vec![
+    super::transpile_to_naga_module(&source, &config).unwrap(),
+    naga::front::wgsl::parse_str(&wgsl).unwrap(),
+  ];
+  for module in &modules {
+    let mut interpreter = NagaInterpreter::new(module).unwrap();
+    interpreter
+      .set_uniform(
+        0,
+        0,
+        &[(
+          "window_size",
+          Value::Composite(vec![Value::F32(100.0), Value::F32(100.0)]),
+        )],
+      )
+      .unwrap();
+    let mut pixel_color = |x, y| {
+      let position = [x, y, 0.0, 1.0].iter().copied().map(Value::F32).collect();
+      interpreter
+        .run_entry_point("fragment_shader", vec![Value::Composite(position)])
+        .unwrap()
+        .to_f32s()
+        .unwrap()
+    };
+    // The simple example draws a white circle in the middle
+    assert_eq!(pixel_color(50.5, 50.5), vec![1.0, 1.0, 1.0, 1.0]);
+    assert_eq!(pixel_color(5.5, 5.5), vec![0.0, 0.0, 0.0, 1.0]);
+  }
+}
@@ -0,0 +1,1769 @@
+//! Builds a `naga::Module` directly from the Rust AST, without going through
+//! shader source code. naga can then validate it and write it out as SPIR-V,
+//! MSL, GLSL, HLSL or WGSL.
+//!
+//! Only the subset of Rust used by shaders is supported, anything else
+//! is reported as an error.
+
+use super::backend::MATH_CONSTANTS;
+use super::types::{Type, TypeInferrer};
+use super::{parser, EnhanceConfig};
+use anyhow::{bail, Context, Result};
+use naga::{Handle, Span};
+use std::collections::{HashMap, HashSet};
+
+lazy_static::lazy_static! {
+  static ref ACCESS_METHODS: regex::Regex =
+    regex::Regex::new(r"^(?P<swizzle>[xyzw]{1,4})(?P<mut>_mut)?$").unwrap();
+  static ref ASSIGN_METHODS: regex::Regex =
+    regex::Regex::new(r"^set_(?P<swizzle>[xyzw]{2,4})$").unwrap();
+  static ref CONSTRUCTOR_METHODS: regex::Regex =
+    regex::Regex::new(r"^vec(?P<arity>[234])(?P<scalar>\w*)$").unwrap();
+}
+
+const ENTRY_POINT_NAME: &str = "fragment_shader";
+
+/// Returns a naga module with a `fragment_shader` entry point, which calls
+/// the configured entry point function with the pixel coordinates.
+///
+/// `INPUT` is bound as a uniform at group 0, binding 0, so the shader should
+/// be transpiled together with the interface.
+pub fn transpile_to_naga_module(rust_source: &str, config: &EnhanceConfig) -> Result<naga::Module> {
+  let mut rust_ast = parser::parse_rust_into_ast(rust_source)?;
+  let mut inferrer = TypeInferrer::new(&rust_ast);
+  inferrer.is_splatting_scalars = true;
+  inferrer.is_annotating_locals = true;
+  syn::visit_mut::VisitMut::visit_file_mut(&mut inferrer, &mut rust_ast);
+  ModuleBuilder::new(config).build(&rust_ast)
+}
+
+struct ModuleBuilder<'a> {
+  config: &'a EnhanceConfig,
+  module: naga::Module,
+  structs: HashMap<String, Handle<naga::Type>>,
+  constants: HashMap<String, Handle<naga::Constant>>,
+  functions: HashMap<String, Handle<naga::Function>>,
+  input: Option<Handle<naga::GlobalVariable>>,
+  // The `u32` copy of the input bound instead of it, when it has `bool` fields
+  input_uniform: Option<Handle<naga::GlobalVariable>>,
+}
+
+impl<'a> ModuleBuilder<'a> {
+  fn new(config: &'a EnhanceConfig) -> Self {
+    Self {
+      config,
+      module: naga::Module::default(),
+      structs: HashMap::new(),
+      constants: HashMap::new(),
+      functions: HashMap::new(),
+      input: None,
+      input_uniform: None,
+    }
+  }
+
+  fn build(mut self, rust_ast: &syn::File) -> Result<naga::Module> {
+    // Types and constants first, the interface comes after the shader
+    for item in &rust_ast.items {
+      match item {
+        syn::Item::Struct(item_struct) => self.add_struct(item_struct)?,
+        syn::Item::Const(item_const) if item_const.ident != self.config.constant_name => {
+          self.add_constant(item_const)?
+        }
+        _ => {}
+      }
+    }
+    let functions: Vec<_> = rust_ast
+      .items
+      .iter()
+      .filter_map(|item| match item {
+        syn::Item::Fn(item_fn) => Some(item_fn),
+        _ => None,
+      })
+      .collect();
+    for item_fn in call_order(&functions) {
+      let name = item_fn.sig.ident.to_string();
+      let function = FunctionBuilder::new(&mut self)
+        .build(item_fn)
+        .with_context(|| format!("In function `{}`", name))?;
+      let handle = self.module.functions.append(function, Span::UNDEFINED);
+      self.functions.insert(name, handle);
+    }
+    if self
+      .functions
+      .contains_key(&self.config.entry_point_fn_name)
+    {
+      let function = FunctionBuilder::new(&mut self).build_entry_point()?;
+      self.module.entry_points.push(naga::EntryPoint {
+        name: ENTRY_POINT_NAME.to_owned(),
+        stage: naga::ShaderStage::Fragment,
+        early_depth_test: None,
+        workgroup_size: [0; 3],
+        function,
+      });
+    }
+    Ok(self.module)
+  }
+
+  fn add_struct(&mut self, item_struct: &syn::ItemStruct) -> Result<()> {
+    let name = item_struct.ident.to_string();
+    let members = item_struct
+      .fields
+      .iter()
+      .map(|field| {
+        let field_name = field
+          .ident
+          .as_ref()
+          .context("Tuple structs are not supported")?;
+        Ok((field_name.to_string(), self.rust_type(&field.ty)?))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let ty = self.insert_struct(&name, &members)?;
+    self.structs.insert(name.clone(), ty);
+    if name == self.config.param_type {
+      self.add_input(ty, &members)?;
+    }
+    Ok(())
+  }
+
+  // `bool` is not allowed in uniform buffers, so if the input contains one
+  // we bind a copy of it with `u32` fields and convert it in the entry point,
+  // like `WgslBackend` does.
+  fn add_input(
+    &mut self,
+    ty: Handle<naga::Type>,
+    members: &[(String, Handle<naga::Type>)],
+  ) -> Result<()> {
+    let binding = Some(naga::ResourceBinding {
+      group: 0,
+      binding: 0,
+    });
+    let bool_type = self.scalar_type(naga::ScalarKind::Bool, naga::BOOL_WIDTH);
+    if !members.iter().any(|(_, member_ty)| *member_ty == bool_type) {
+      self.input = Some(self.module.global_variables.append(
+        naga::GlobalVariable {
+          name: Some(self.config.constant_name.clone()),
+          space: naga::AddressSpace::Uniform,
+          binding,
+          ty,
+          init: None,
+        },
+        Span::UNDEFINED,
+      ));
+      return Ok(());
+    }
+    let u32_type = self.scalar_type(naga::ScalarKind::Uint, 4);
+    let uniform_members: Vec<_> = members
+      .iter()
+      .map(|(name, member_ty)| {
+        let member_ty = if *member_ty == bool_type {
+          u32_type
+        } else {
+          *member_ty
+        };
+        (name.clone(), member_ty)
+      })
+      .collect();
+    let uniform_type = self.insert_struct(
+      &format!("{}Uniform", self.config.param_type),
+      &uniform_members,
+    )?;
+    self.input_uniform = Some(self.module.global_variables.append(
+      naga::GlobalVariable {
+        name: Some(format!("{}_UNIFORM", self.config.constant_name)),
+        space: naga::AddressSpace::Uniform,
+        binding,
+        ty: uniform_type,
+        init: None,
+      },
+      Span::UNDEFINED,
+    ));
+    self.input = Some(self.module.global_variables.append(
+      naga::GlobalVariable {
+        name: Some(self.config.constant_name.clone()),
+        space: naga::AddressSpace::Private,
+        binding: None,
+        ty,
+        init: None,
+      },
+      Span::UNDEFINED,
+    ));
+    Ok(())
+  }
+
+  fn add_constant(&mut self, item_const: &syn::ItemConst) -> Result<()> {
+    let name = item_const.ident.to_string();
+    let literal = match &*item_const.expr {
+      syn::Expr::Lit(syn::ExprLit { lit, .. }) => literal(lit)?,
+      syn::Expr::Unary(syn::ExprUnary {
+        op: syn::UnOp::Neg(_),
+        expr,
+        ..
+      }) => match &**expr {
+        syn::Expr::Lit(syn::ExprLit { lit, .. }) => negated(literal(lit)?)?,
+        _ => bail!("Constant `{}` must be a literal", name),
+      },
+      _ => bail!("Constant `{}` must be a literal", name),
+    };
+    let ty = self.rust_type(&item_const.ty)?;
+    self.insert_constant(name, ty, literal);
+    Ok(())
+  }
+
+  fn insert_constant(&mut self, name: String, ty: Handle<naga::Type>, literal: naga::Literal) {
+    let init = self
+      .module
+      .const_expressions
+      .append(naga::Expression::Literal(literal), Span::UNDEFINED);
+    let constant = self.module.constants.append(
+      naga::Constant {
+        name: Some(name.clone()),
+        r#override: naga::Override::None,
+        ty,
+        init,
+      },
+      Span::UNDEFINED,
+    );
+    self.constants.insert(name, constant);
+  }
+
+  // Math constants are only added to the module when they are used
+  fn constant(&mut self, name: &str) -> Option<Handle<naga::Constant>> {
+    if !self.constants.contains_key(name) {
+      let (_, value) = MATH_CONSTANTS
+        .iter()
+        .find(|(constant_name, _)| *constant_name == name)?;
+      let ty = self.scalar_type(naga::ScalarKind::Float, 4);
+      self.insert_constant(name.to_owned(), ty, naga::Literal::F32(*value));
+    }
+    self.constants.get(name).copied()
+  }
+
+  fn insert_type(&mut self, inner: naga::TypeInner) -> Handle<naga::Type> {
+    self
+      .module
+      .types
+      .insert(naga::Type { name: None, inner }, Span::UNDEFINED)
+  }
+
+  fn insert_struct(
+    &mut self,
+    name: &str,
+    members: &[(String, Handle<naga::Type>)],
+  ) -> Result<Handle<naga::Type>> {
+    let mut layouter = naga::proc::Layouter::default();
+    layouter.update(self.module.to_ctx())?;
+    let mut offset = 0;
+    let mut alignment = naga::proc::Alignment::ONE;
+    let members = members
+      .iter()
+      .map(|(member_name, ty)| {
+        let layout = layouter[*ty];
+        offset = layout.alignment.round_up(offset);
+        let member = naga::StructMember {
+          name: Some(member_name.clone()),
+          ty: *ty,
+          binding: None,
+          offset,
+        };
+        offset += layout.size;
+        alignment = alignment.max(layout.alignment);
+        member
+      })
+      .collect();
+    Ok(self.module.types.insert(
+      naga::Type {
+        name: Some(name.to_owned()),
+        inner: naga::TypeInner::Struct {
+          members,
+          span: alignment.round_up(offset),
+        },
+      },
+      Span::UNDEFINED,
+    ))
+  }
+
+  fn scalar_type(&mut self, kind: naga::ScalarKind, width: naga::Bytes) -> Handle<naga::Type> {
+    self.insert_type(naga::TypeInner::Scalar { kind, width })
+  }
+
+  fn rust_type(&mut self, ty: &syn::Type) -> Result<Handle<naga::Type>> {
+    let rust_type =
+      Type::from_rust(ty).with_context(|| format!("Unsupported type `{}`", quote::quote!(#ty)))?;
+    Ok(match rust_type {
+      Type::Scalar(scalar) => {
+        let (kind, width) = scalar_kind(&scalar)?;
+        self.scalar_type(kind, width)
+      }
+      Type::Vector(scalar, arity) => {
+        let (kind, width) = scalar_kind(&scalar)?;
+        self.insert_type(naga::TypeInner::Vector {
+          size: vector_size(arity)?,
+          kind,
+          width,
+        })
+      }
+      Type::Matrix(scalar, dimension) => {
+        if scalar_kind(&scalar)?.0 != naga::ScalarKind::Float {
+          bail!("Only matrices of floats are supported");
+        }
+        let size = vector_size(dimension)?;
+        self.insert_type(naga::TypeInner::Matrix {
+          columns: size,
+          rows: size,
+          width: 4,
+        })
+      }
+      Type::Struct(name) => *self
+        .structs
+        .get(&name)
+        .with_context(|| format!("Unknown type `{}`", name))?,
+    })
+  }
+
+  fn literal_type(&mut self, literal: naga::Literal) -> Handle<naga::Type> {
+    let (kind, width) = match literal {
+      naga::Literal::F64(_) => (naga::ScalarKind::Float, 8),
+      naga::Literal::F32(_) => (naga::ScalarKind::Float, 4),
+      naga::Literal::U32(_) => (naga::ScalarKind::Uint, 4),
+      naga::Literal::I32(_) => (naga::ScalarKind::Sint, 4),
+      naga::Literal::Bool(_) => (naga::ScalarKind::Bool, naga::BOOL_WIDTH),
+    };
+    self.scalar_type(kind, width)
+  }
+
+  // The type of the same shape as the given type, with a different scalar kind
+  fn with_kind(
+    &mut self,
+    ty: Handle<naga::Type>,
+    kind: naga::ScalarKind,
+    width: naga::Bytes,
+  ) -> Handle<naga::Type> {
+    match self.module.types[ty].inner {
+      naga::TypeInner::Vector { size, .. } => {
+        self.insert_type(naga::TypeInner::Vector { size, kind, width })
+      }
+      _ => self.scalar_type(kind, width),
+    }
+  }
+
+  fn scalar_of(&mut self, ty: Handle<naga::Type>) -> Handle<naga::Type> {
+    match self.module.types[ty].inner {
+      naga::TypeInner::Vector { kind, width, .. } | naga::TypeInner::Scalar { kind, width } => {
+        self.scalar_type(kind, width)
+      }
+      naga::TypeInner::Matrix { width, .. } => self.scalar_type(naga::ScalarKind::Float, width),
+      _ => ty,
+    }
+  }
+
+  fn vector_of(&mut self, ty: Handle<naga::Type>, size: naga::VectorSize) -> Handle<naga::Type> {
+    match self.module.types[ty].inner {
+      naga::TypeInner::Vector { kind, width, .. } | naga::TypeInner::Scalar { kind, width } => {
+        self.insert_type(naga::TypeInner::Vector { size, kind, width })
+      }
+      _ => ty,
+    }
+  }
+
+  // The index and type of the named vector component or struct field
+  fn member(&mut self, ty: Handle<naga::Type>, name: &str) -> Result<(u32, Handle<naga::Type>)> {
+    match &self.module.types[ty].inner {
+      naga::TypeInner::Vector { size, .. } => {
+        let index = "xyzw"
+          .find(name)
+          .filter(|index| name.len() == 1 && *index < *size as usize)
+          .with_context(|| format!("Unknown vector component `{}`", name))?;
+        Ok((index as u32, self.scalar_of(ty)))
+      }
+      naga::TypeInner::Struct { members, .. } => members
+        .iter()
+        .enumerate()
+        .find(|(_, member)| member.name.as_deref() == Some(name))
+        .map(|(index, member)| (index as u32, member.ty))
+        .with_context(|| format!("Unknown field `{}`", name)),
+      _ => bail!(
+        "Cannot access `{}` of a value which is not a vector or a struct",
+        name
+      ),
+    }
+  }
+
+  fn product_type(
+    &mut self,
+    left: Handle<naga::Type>,
+    right: Handle<naga::Type>,
+  ) -> Handle<naga::Type> {
+    use naga::TypeInner::{Matrix, Scalar, Vector};
+    match (
+      &self.module.types[left].inner,
+      &self.module.types[right].inner,
+    ) {
+      (Matrix { rows, .. }, Vector { .. }) => {
+        let rows = *rows;
+        self.vector_of(right, rows)
+      }
+      (Vector { .. }, Matrix { columns, .. }) => {
+        let columns = *columns;
+        self.vector_of(left, columns)
+      }
+      (Matrix { rows, width, .. }, Matrix { columns, .. }) => {
+        let inner = Matrix {
+          columns: *columns,
+          rows: *rows,
+          width: *width,
+        };
+        self.insert_type(inner)
+      }
+      (Scalar { .. }, _) => right,
+      _ => left,
+    }
+  }
+}
+
+// naga requires functions to be defined before they are called
+fn call_order<'f>(functions: &[&'f syn::ItemFn]) -> Vec<&'f syn::ItemFn> {
+  struct CalleeCollector(Vec<String>);
+
+  impl<'ast> syn::visit::Visit<'ast> for CalleeCollector {
+    fn visit_expr_call(&mut self, call: &'ast syn::ExprCall) {
+      if let syn::Expr::Path(syn::ExprPath { path, .. }) = &*call.func {
+        if let Some(ident) = path.get_ident() {
+          self.0.push(ident.to_string());
+        }
+      }
+      syn::visit::visit_expr_call(self, call);
+    }
+  }
+
+  fn visit<'f>(
+    item_fn: &'f syn::ItemFn,
+    by_name: &HashMap<String, &'f syn::ItemFn>,
+    visited: &mut HashSet<String>,
+    ordered: &mut Vec<&'f syn::ItemFn>,
+  ) {
+    if !visited.insert(item_fn.sig.ident.to_string()) {
+      return;
+    }
+    let mut callees = CalleeCollector(vec![]);
+    syn::visit::Visit::visit_block(&mut callees, &item_fn.block);
+    for callee in callees.0 {
+      if let Some(callee_fn) = by_name.get(&callee) {
+        visit(callee_fn, by_name, visited, ordered);
+      }
+    }
+    ordered.push(item_fn);
+  }
+
+  let by_name = functions
+    .iter()
+    .map(|item_fn| (item_fn.sig.ident.to_string(), *item_fn))
+    .collect();
+  let mut visited = HashSet::new();
+  let mut ordered = vec![];
+  for item_fn in functions {
+    visit(item_fn, &by_name, &mut visited, &mut ordered);
+  }
+  ordered
+}
+
+#[derive(Clone, Copy)]
+struct Value {
+  expression: Handle<naga::Expression>,
+  ty: Handle<naga::Type>,
+}
+
+#[derive(Clone, Copy)]
+enum Variable {
+  // Immutable bindings refer directly to the expression
+  Value(Value),
+  Local(Handle<naga::LocalVariable>, Handle<naga::Type>),
+}
+
+// What to do with the value of the last expression in a block
+#[derive(Clone, Copy)]
+enum Tail {
+  Return,
+  Store(Handle<naga::LocalVariable>),
+  Discard,
+}
+
+struct FunctionBuilder<'m, 'a> {
+  module: &'m mut ModuleBuilder<'a>,
+  function: naga::Function,
+  scopes: Vec<HashMap<String, Variable>>,
+}
+
+impl<'m, 'a> FunctionBuilder<'m, 'a> {
+  fn new(module: &'m mut ModuleBuilder<'a>) -> Self {
+    Self {
+      module,
+      function: naga::Function::default(),
+      scopes: vec![HashMap::new()],
+    }
+  }
+
+  fn build(mut self, item_fn: &syn::ItemFn) -> Result<naga::Function> {
+    self.function.name = Some(item_fn.sig.ident.to_string());
+    let mut body = naga::Block::new();
+    for (index, input) in item_fn.sig.inputs.iter().enumerate() {
+      let (pat, ty) = match input {
+        syn::FnArg::Typed(syn::PatType { pat, ty, .. }) => (pat, ty),
+        syn::FnArg::Receiver(_) => bail!("Methods are not supported"),
+      };
+      let (name, is_mutable) = match &**pat {
+        syn::Pat::Ident(syn::PatIdent {
+          ident, mutability, ..
+        }) => (ident.to_string(), mutability.is_some()),
+        _ => bail!("Unsupported parameter pattern `{}`", quote::quote!(#pat)),
+      };
+      let ty = self.module.rust_type(ty)?;
+      self.function.arguments.push(naga::FunctionArgument {
+        name: Some(name.clone()),
+        ty,
+        binding: None,
+      });
+      let argument = self.add(
+        naga::Expression::FunctionArgument(index as u32),
+        ty,
+        &mut body,
+      );
+      if is_mutable {
+        // Arguments can't be assigned to, so mutable ones are copied
+        self.declare(&name, ty, Some(argument), &mut body);
+      } else {
+        self.bind(name, Variable::Value(argument));
+      }
+    }
+    let tail = match &item_fn.sig.output {
+      syn::ReturnType::Type(_, ty) => {
+        let ty = self.module.rust_type(ty)?;
+        self.function.result = Some(naga::FunctionResult { ty, binding: None });
+        Tail::Return
+      }
+      syn::ReturnType::Default => Tail::Discard,
+    };
+    self.block(&item_fn.block, tail, &mut body)?;
+    self.function.body = body;
+    Ok(self.function)
+  }
+
+  fn build_entry_point(mut self) -> Result<naga::Function> {
+    let vec4 = self.module.insert_type(naga::TypeInner::Vector {
+      size: naga::VectorSize::Quad,
+      kind: naga::ScalarKind::Float,
+      width: 4,
+    });
+    self.function.name = Some(ENTRY_POINT_NAME.to_owned());
+    self.function.arguments.push(naga::FunctionArgument {
+      name: Some("position".to_owned()),
+      ty: vec4,
+      binding: Some(naga::Binding::BuiltIn(naga::BuiltIn::Position {
+        invariant: false,
+      })),
+    });
+    self.function.result = Some(naga::FunctionResult {
+      ty: vec4,
+      binding: Some(naga::Binding::Location {
+        location: 0,
+        second_blend_source: false,
+        interpolation: None,
+        sampling: None,
+      }),
+    });
+    let mut body = naga::Block::new();
+    if let (Some(input_uniform), Some(input)) = (self.module.input_uniform, self.module.input) {
+      self.convert_input(input_uniform, input, &mut body)?;
+    }
+    let position = self.add(naga::Expression::FunctionArgument(0), vec4, &mut body);
+    let coordinates = self.swizzle(position, "xy", &mut body)?;
+    let entry_point = self.module.functions[&self.module.config.entry_point_fn_name];
+    let color = self
+      .call_function(entry_point, vec![coordinates.expression], &mut body)
+      .context("The entry point function must return a color")?;
+    body.push(
+      naga::Statement::Return {
+        value: Some(color.expression),
+      },
+      Span::UNDEFINED,
+    );
+    self.function.body = body;
+    Ok(self.function)
+  }
+
+  fn convert_input(
+    &mut self,
+    input_uniform: Handle<naga::GlobalVariable>,
+    input: Handle<naga::GlobalVariable>,
+    out: &mut naga::Block,
+  ) -> Result<()> {
+    let uniform_type = self.module.module.global_variables[input_uniform].ty;
+    let input_type = self.module.module.global_variables[input].ty;
+    let uniform_pointer = self.append(naga::Expression::GlobalVariable(input_uniform), out);
+    let uniform = self.add(
+      naga::Expression::Load {
+        pointer: uniform_pointer,
+      },
+      uniform_type,
+      out,
+    );
+    let member_types: Vec<_> = match &self.module.module.types[input_type].inner {
+      naga::TypeInner::Struct { members, .. } => members.iter().map(|member| member.ty).collect(),
+      _ => bail!("The input must be a struct"),
+    };
+    let bool_type = self
+      .module
+      .scalar_type(naga::ScalarKind::Bool, naga::BOOL_WIDTH);
+    let u32_type = self.module.scalar_type(naga::ScalarKind::Uint, 4);
+    let mut components = vec![];
+    for (index, member_type) in member_types.into_iter().enumerate() {
+      let is_bool = member_type == bool_type;
+      let field = self.add(
+        naga::Expression::AccessIndex {
+          base: uniform.expression,
+          index: index as u32,
+        },
+        if is_bool { u32_type } else { member_type },
+        out,
+      );
+      components.push(if is_bool {
+        let zero = self.add(
+          naga::Expression::Literal(naga::Literal::U32(0)),
+          u32_type,
+          out,
+        );
+        self.binary(naga::BinaryOperator::NotEqual, field, zero, out)?
+      } else {
+        field
+      });
+    }
+    let value = self.add(
+      naga::Expression::Compose {
+        ty: input_type,
+        components: components
+          .iter()
+          .map(|component| component.expression)
+          .collect(),
+      },
+      input_type,
+      out,
+    );
+    let pointer = self.append(naga::Expression::GlobalVariable(input), out);
+    out.push(
+      naga::Statement::Store {
+        pointer,
+        value: value.expression,
+      },
+      Span::UNDEFINED,
+    );
+    Ok(())
+  }
+
+  // Expressions which aren't available up front need to be emitted
+  // before they can be used
+  fn append(
+    &mut self,
+    expression: naga::Expression,
+    out: &mut naga::Block,
+  ) -> Handle<naga::Expression> {
+    let needs_emit = !expression.needs_pre_emit();
+    let handle = self
+      .function
+      .expressions
+      .append(expression, Span::UNDEFINED);
+    if needs_emit {
+      out.push(
+        naga::Statement::Emit(naga::Range::new_from_bounds(handle, handle)),
+        Span::UNDEFINED,
+      );
+    }
+    handle
+  }
+
+  fn add(
+    &mut self,
+    expression: naga::Expression,
+    ty: Handle<naga::Type>,
+    out: &mut naga::Block,
+  ) -> Value {
+    Value {
+      expression: self.append(expression, out),
+      ty,
+    }
+  }
+
+  fn bind(&mut self, name: String, variable: Variable) {
+    self.scopes.last_mut().unwrap().insert(name, variable);
+  }
+
+  fn lookup(&self, name: &str) -> Option<Variable> {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .copied()
+  }
+
+  fn declare(
+    &mut self,
+    name: &str,
+    ty: Handle<naga::Type>,
+    init: Option<Value>,
+    out: &mut naga::Block,
+  ) -> Handle<naga::LocalVariable> {
+    let local = self.function.local_variables.append(
+      naga::LocalVariable {
+        name: Some(name.to_owned()),
+        ty,
+        init: None,
+      },
+      Span::UNDEFINED,
+    );
+    if let Some(init) = init {
+      self.store_local(local, init, out);
+    }
+    self.bind(name.to_owned(), Variable::Local(local, ty));
+    local
+  }
+
+  fn store_local(
+    &mut self,
+    local: Handle<naga::LocalVariable>,
+    value: Value,
+    out: &mut naga::Block,
+  ) {
+    // Temporaries get their type from the first value stored in them
+    self.function.local_variables[local].ty = value.ty;
+    let pointer = self.append(naga::Expression::LocalVariable(local), out);
+    out.push(
+      naga::Statement::Store {
+        pointer,
+        value: value.expression,
+      },
+      Span::UNDEFINED,
+    );
+  }
+
+  fn block(&mut self, block: &syn::Block, tail: Tail, out: &mut naga::Block) -> Result<()> {
+    self.scopes.push(HashMap::new());
+    let last = block.stmts.len().saturating_sub(1);
+    for (index, stmt) in block.stmts.iter().enumerate() {
+      match stmt {
+        syn::Stmt::Expr(expression) if index == last => self.tail(expression, tail, out)?,
+        syn::Stmt::Expr(expression) | syn::Stmt::Semi(expression, _) => {
+          self.statement(expression, out)?
+        }
+        syn::Stmt::Local(local) => {
+          let init = match &local.init {
+            Some((_, init)) => Some(self.expression(init, out)?),
+            None => None,
+          };
+          self.bind_pattern(&local.pat, None, init, out)?;
+        }
+        syn::Stmt::Item(_) => bail!("Nested items are not supported"),
+      }
+    }
+    self.scopes.pop();
+    Ok(())
+  }
+
+  fn tail(&mut self, expression: &syn::Expr, tail: Tail, out: &mut naga::Block) -> Result<()> {
+    match expression {
+      syn::Expr::If(expr_if) => self.if_statement(expr_if, tail, out),
+      syn::Expr::Block(syn::ExprBlock { block, .. }) => self.block(block, tail, out),
+      syn::Expr::Paren(syn::ExprParen { expr, .. }) => self.tail(expr, tail, out),
+      _ if is_statement(expression) => self.statement(expression, out),
+      _ => match tail {
+        Tail::Return => {
+          let value = self.expression(expression, out)?;
+          out.push(
+            naga::Statement::Return {
+              value: Some(value.expression),
+            },
+            Span::UNDEFINED,
+          );
+          Ok(())
+        }
+        Tail::Store(local) => {
+          let value = self.expression(expression, out)?;
+          self.store_local(local, value, out);
+          Ok(())
+        }
+        Tail::Discard => self.statement(expression, out),
+      },
+    }
+  }
+
+  fn statement(&mut self, expression: &syn::Expr, out: &mut naga::Block) -> Result<()> {
+    match expression {
+      syn::Expr::Assign(syn::ExprAssign { left, right, .. }) => {
+        let value = self.expression(right, out)?;
+        self.assign(left, value, out)
+      }
+      syn::Expr::AssignOp(syn::ExprAssignOp {
+        left, op, right, ..
+      }) => {
+        let current = self.expression(left, out)?;
+        let operand = self.expression(right, out)?;
+        let value = self.binary(binary_operator(op), current, operand, out)?;
+        self.assign(left, value, out)
+      }
+      syn::Expr::If(expr_if) => self.if_statement(expr_if, Tail::Discard, out),
+      syn::Expr::Block(syn::ExprBlock { block, .. }) => self.block(block, Tail::Discard, out),
+      syn::Expr::Paren(syn::ExprParen { expr, .. }) => self.statement(expr, out),
+      syn::Expr::ForLoop(expr_for_loop) => self.for_loop(expr_for_loop, out),
+      syn::Expr::While(syn::ExprWhile { cond, body, .. }) => {
+        let mut loop_body = naga::Block::new();
+        let condition = self.expression(cond, &mut loop_body)?;
+        loop_body.push(
+          naga::Statement::If {
+            condition: condition.expression,
+            accept: naga::Block::new(),
+            reject: break_block(),
+          },
+          Span::UNDEFINED,
+        );
+        self.block(body, Tail::Discard, &mut loop_body)?;
+        out.push(
+          naga::Statement::Loop {
+            body: loop_body,
+            continuing: naga::Block::new(),
+            break_if: None,
+          },
+          Span::UNDEFINED,
+        );
+        Ok(())
+      }
+      syn::Expr::Loop(syn::ExprLoop { body, .. }) => {
+        let mut loop_body = naga::Block::new();
+        self.block(body, Tail::Discard, &mut loop_body)?;
+        out.push(
+          naga::Statement::Loop {
+            body: loop_body,
+            continuing: naga::Block::new(),
+            break_if: None,
+          },
+          Span::UNDEFINED,
+        );
+        Ok(())
+      }
+      syn::Expr::Return(syn::ExprReturn { expr, .. }) => {
+        let value = match expr {
+          Some(expr) => Some(self.expression(expr, out)?.expression),
+          None => None,
+        };
+        out.push(naga::Statement::Return { value }, Span::UNDEFINED);
+        Ok(())
+      }
+      syn::Expr::Break(_) => {
+        out.push(naga::Statement::Break, Span::UNDEFINED);
+        Ok(())
+      }
+      syn::Expr::Continue(_) => {
+        out.push(naga::Statement::Continue, Span::UNDEFINED);
+        Ok(())
+      }
+      syn::Expr::Call(call) => self.call(call, out).map(|_| ()),
+      syn::Expr::MethodCall(call) => {
+        let method = call.method.to_string();
+        match ASSIGN_METHODS.captures(&method) {
+          Some(captures) => {
+            let value = self.single_argument(call, out)?;
+            self.assign_swizzle(&call.receiver, &captures["swizzle"], value, out)
+          }
+          None => self.method_call(call, out).map(|_| ()),
+        }
+      }
+      _ => self.expression(expression, out).map(|_| ()),
+    }
+  }
+
+  fn if_statement(
+    &mut self,
+    expr_if: &syn::ExprIf,
+    tail: Tail,
+    out: &mut naga::Block,
+  ) -> Result<()> {
+    let condition = self.expression(&expr_if.cond, out)?;
+    let mut accept = naga::Block::new();
+    self.block(&expr_if.then_branch, tail, &mut accept)?;
+    let mut reject = naga::Block::new();
+    if let Some((_, else_branch)) = &expr_if.else_branch {
+      self.tail(else_branch, tail, &mut reject)?;
+    }
+    out.push(
+      naga::Statement::If {
+        condition: condition.expression,
+        accept,
+        reject,
+      },
+      Span::UNDEFINED,
+    );
+    Ok(())
+  }
+
+  fn for_loop(&mut self, expr_for_loop: &syn::ExprForLoop, out: &mut naga::Block) -> Result<()> {
+    let (from, to, limits) = match &*expr_for_loop.expr {
+      syn::Expr::Range(syn::ExprRange {
+        from: Some(from),
+        to: Some(to),
+        limits,
+        ..
+      }) => (from, to, limits),
+      _ => bail!("Only `for` loops over ranges are supported"),
+    };
+    let from = self.expression(from, out)?;
+    // The end of the range is evaluated once, like in Rust
+    let to = self.expression(to, out)?;
+    self.scopes.push(HashMap::new());
+    let name = match &expr_for_loop.pat {
+      syn::Pat::Type(syn::PatType { pat, .. }) => pattern_name(pat)?,
+      pat => pattern_name(pat)?,
+    };
+    let counter = self.declare(name.as_deref().unwrap_or("_"), from.ty, Some(from), out);
+
+    let mut body = naga::Block::new();
+    let pointer = self.append(naga::Expression::LocalVariable(counter), &mut body);
+    let current = self.add(naga::Expression::Load { pointer }, from.ty, &mut body);
+    let op = match limits {
+      syn::RangeLimits::HalfOpen(_) => naga::BinaryOperator::GreaterEqual,
+      syn::RangeLimits::Closed(_) => naga::BinaryOperator::Greater,
+    };
+    let is_done = self.binary(op, current, to, &mut body)?;
+    body.push(
+      naga::Statement::If {
+        condition: is_done.expression,
+        accept: break_block(),
+        reject: naga::Block::new(),
+      },
+      Span::UNDEFINED,
+    );
+    self.block(&expr_for_loop.body, Tail::Discard, &mut body)?;
+
+    let mut continuing = naga::Block::new();
+    let pointer = self.append(naga::Expression::LocalVariable(counter), &mut continuing);
+    let current = self.add(naga::Expression::Load { pointer }, from.ty, &mut continuing);
+    let one = match self.module.module.types[from.ty].inner {
+      naga::TypeInner::Scalar {
+        kind: naga::ScalarKind::Uint,
+        ..
+      } => naga::Literal::U32(1),
+      _ => naga::Literal::I32(1),
+    };
+    let one = self.add(naga::Expression::Literal(one), from.ty, &mut continuing);
+    let next = self.binary(naga::BinaryOperator::Add, current, one, &mut continuing)?;
+    continuing.push(
+      naga::Statement::Store {
+        pointer,
+        value: next.expression,
+      },
+      Span::UNDEFINED,
+    );
+    out.push(
+      naga::Statement::Loop {
+        body,
+        continuing,
+        break_if: None,
+      },
+      Span::UNDEFINED,
+    );
+    self.scopes.pop();
+    Ok(())
+  }
+
+  fn bind_pattern(
+    &mut self,
+    pattern: &syn::Pat,
+    ty: Option<Handle<naga::Type>>,
+    value: Option<Value>,
+    out: &mut naga::Block,
+  ) -> Result<()> {
+    match pattern {
+      syn::Pat::Type(syn::PatType { pat, ty, .. }) => {
+        let ty = self.module.rust_type(ty)?;
+        self.bind_pattern(pat, Some(ty), value, out)
+      }
+      syn::Pat::Ident(syn::PatIdent {
+        ident,
+        mutability: None,
+        ..
+      }) if value.is_some() => {
+        let value = value.unwrap();
+        let name = ident.to_string();
+        if !self.function.expressions[value.expression].needs_pre_emit() {
+          self
+            .function
+            .named_expressions
+            .entry(value.expression)
+            .or_insert_with(|| name.clone());
+        }
+        self.bind(name, Variable::Value(value));
+        Ok(())
+      }
+      syn::Pat::Ident(syn::PatIdent { ident, .. }) => {
+        let name = ident.to_string();
+        let ty = value.map(|value| value.ty).or(ty).with_context(|| {
+          format!(
+            "Could not infer the type of `{}`, add a type annotation",
+            name
+          )
+        })?;
+        self.declare(&name, ty, value, out);
+        Ok(())
+      }
+      syn::Pat::Struct(syn::PatStruct { fields, .. }) => {
+        let value = value.context("Struct patterns need a value")?;
+        for field in fields {
+          let name = match &field.member {
+            syn::Member::Named(name) => name.to_string(),
+            syn::Member::Unnamed(_) => bail!("Tuple structs are not supported"),
+          };
+          let (index, field_type) = self.module.member(value.ty, &name)?;
+          let field_value = self.add(
+            naga::Expression::AccessIndex {
+              base: value.expression,
+              index,
+            },
+            field_type,
+            out,
+          );
+          self.bind_pattern(&field.pat, None, Some(field_value), out)?;
+        }
+        Ok(())
+      }
+      syn::Pat::Wild(_) => Ok(()),
+      _ => bail!("Unsupported pattern `{}`", quote::quote!(#pattern)),
+    }
+  }
+
+  fn assign(&mut self, place: &syn::Expr, value: Value, out: &mut naga::Block) -> Result<()> {
+    if let syn::Expr::Unary(syn::ExprUnary {
+      op: syn::UnOp::Deref(_),
+      expr,
+      ..
+    }) = place
+    {
+      if let syn::Expr::MethodCall(call) = &**expr {
+        let method = call.method.to_string();
+        if let Some(captures) = ACCESS_METHODS.captures(&method) {
+          if captures.name("mut").is_some() {
+            return self.assign_swizzle(&call.receiver, &captures["swizzle"], value, out);
+          }
+        }
+      }
+      return self.assign(expr, value, out);
+    }
+    let (pointer, _) = self.pointer(place, out)?;
+    out.push(
+      naga::Statement::Store {
+        pointer,
+        value: value.expression,
+      },
+      Span::UNDEFINED,
+    );
+    Ok(())
+  }
+
+  // Stores each component of the value separately, as naga can't store
+  // to a swizzle
+  fn assign_swizzle(
+    &mut self,
+    vector: &syn::Expr,
+    swizzle: &str,
+    value: Value,
+    out: &mut naga::Block,
+  ) -> Result<()> {
+    let (pointer, ty) = self.pointer(vector, out)?;
+    for (value_index, component) in swizzle.chars().enumerate() {
+      let (index, _) = self.module.member(ty, &component.to_string())?;
+      let component_pointer = self.append(
+        naga::Expression::AccessIndex {
+          base: pointer,
+          index,
+        },
+        out,
+      );
+      let component_value = self.append(
+        naga::Expression::AccessIndex {
+          base: value.expression,
+          index: value_index as u32,
+        },
+        out,
+      );
+      out.push(
+        naga::Statement::Store {
+          pointer: component_pointer,
+          value: component_value,
+        },
+        Span::UNDEFINED,
+      );
+    }
+    Ok(())
+  }
+
+  // The pointer to the assigned place and the type it points to
+  fn pointer(
+    &mut self,
+    place: &syn::Expr,
+    out: &mut naga::Block,
+  ) -> Result<(Handle<naga::Expression>, Handle<naga::Type>)> {
+    match place {
+      syn::Expr::Path(syn::ExprPath { path, .. }) => {
+        let name = path_name(path)?;
+        match self.lookup(&name) {
+          Some(Variable::Local(local, ty)) => {
+            Ok((self.append(naga::Expression::LocalVariable(local), out), ty))
+          }
+          _ => bail!("Cannot assign to `{}`, it's not a mutable variable", name),
+        }
+      }
+      syn::Expr::Field(syn::ExprField {
+        base,
+        member: syn::Member::Named(name),
+        ..
+      }) => {
+        let (base_pointer, base_type) = self.pointer(base, out)?;
+        let (index, ty) = self.module.member(base_type, &name.to_string())?;
+        Ok((
+          self.append(
+            naga::Expression::AccessIndex {
+              base: base_pointer,
+              index,
+            },
+            out,
+          ),
+          ty,
+        ))
+      }
+      syn::Expr::Paren(syn::ExprParen { expr, .. }) => self.pointer(expr, out),
+      _ => bail!("Cannot assign to `{}`", quote::quote!(#place)),
+    }
+  }
+
+  fn expression(&mut self, expression: &syn::Expr, out: &mut naga::Block) -> Result<Value> {
+    Ok(match expression {
+      syn::Expr::Lit(syn::ExprLit { lit, .. }) => {
+        let literal = literal(lit)?;
+        let ty = self.module.literal_type(literal);
+        self.add(naga::Expression::Literal(literal), ty, out)
+      }
+      syn::Expr::Path(syn::ExprPath { path, .. }) => self.variable(&path_name(path)?, out)?,
+      syn::Expr::Paren(syn::ExprParen { expr, .. })
+      | syn::Expr::Group(syn::ExprGroup { expr, .. }) => self.expression(expr, out)?,
+      syn::Expr::Unary(syn::ExprUnary { op, expr, .. }) => {
+        let value = self.expression(expr, out)?;
+        let op = match op {
+          syn::UnOp::Deref(_) => return Ok(value),
+          syn::UnOp::Neg(_) => naga::UnaryOperator::Negate,
+          syn::UnOp::Not(_) => match self.module.module.types[value.ty].inner {
+            naga::TypeInner::Scalar {
+              kind: naga::ScalarKind::Bool,
+              ..
+            } => naga::UnaryOperator::LogicalNot,
+            _ => naga::UnaryOperator::BitwiseNot,
+          },
+        };
+        self.add(
+          naga::Expression::Unary {
+            op,
+            expr: value.expression,
+          },
+          value.ty,
+          out,
+        )
+      }
+      syn::Expr::Binary(syn::ExprBinary {
+        left, op, right, ..
+      }) => {
+        let left = self.expression(left, out)?;
+        let right = self.expression(right, out)?;
+        self.binary(binary_operator(op), left, right, out)?
+      }
+      syn::Expr::Cast(syn::ExprCast { expr, ty, .. }) => {
+        let value = self.expression(expr, out)?;
+        let target = self.module.rust_type(ty)?;
+        match self.module.module.types[target].inner {
+          naga::TypeInner::Scalar { kind, width } => self.convert(value, kind, width, out),
+          _ => bail!("Only casts to scalar types are supported"),
+        }
+      }
+      syn::Expr::Field(syn::ExprField { base, member, .. }) => {
+        let base = self.expression(base, out)?;
+        let name = match member {
+          syn::Member::Named(name) => name.to_string(),
+          syn::Member::Unnamed(_) => bail!("Tuples are not supported"),
+        };
+        let (index, ty) = self.module.member(base.ty, &name)?;
+        self.add(
+          naga::Expression::AccessIndex {
+            base: base.expression,
+            index,
+          },
+          ty,
+          out,
+        )
+      }
+      syn::Expr::Call(call) => self
+        .call(call, out)?
+        .with_context(|| format!("`{}` doesn't return a value", quote::quote!(#call)))?,
+      syn::Expr::MethodCall(call) => self.method_call(call, out)?,
+      syn::Expr::If(_) | syn::Expr::Block(_) => {
+        // The type is set when the first branch stores its value
+        let placeholder = self.module.scalar_type(naga::ScalarKind::Float, 4);
+        let local = self.function.local_variables.append(
+          naga::LocalVariable {
+            name: None,
+            ty: placeholder,
+            init: None,
+          },
+          Span::UNDEFINED,
+        );
+        self.tail(expression, Tail::Store(local), out)?;
+        let ty = self.function.local_variables[local].ty;
+        let pointer = self.append(naga::Expression::LocalVariable(local), out);
+        self.add(naga::Expression::Load { pointer }, ty, out)
+      }
+      _ => bail!("Unsupported expression `{}`", quote::quote!(#expression)),
+    })
+  }
+
+  fn variable(&mut self, name: &str, out: &mut naga::Block) -> Result<Value> {
+    if let Some(variable) = self.lookup(name) {
+      return Ok(match variable {
+        Variable::Value(value) => value,
+        Variable::Local(local, ty) => {
+          let pointer = self.append(naga::Expression::LocalVariable(local), out);
+          self.add(naga::Expression::Load { pointer }, ty, out)
+        }
+      });
+    }
+    if name == self.module.config.constant_name {
+      let input = self.module.input.with_context(|| {
+        format!(
+          "`{}` is used but the `{}` struct is missing",
+          name, self.module.config.param_type
+        )
+      })?;
+      let ty = self.module.module.global_variables[input].ty;
+      let pointer = self.append(naga::Expression::GlobalVariable(input), out);
+      return Ok(self.add(naga::Expression::Load { pointer }, ty, out));
+    }
+    if let Some(constant) = self.module.constant(name) {
+      let ty = self.module.module.constants[constant].ty;
+      return Ok(self.add(naga::Expression::Constant(constant), ty, out));
+    }
+    bail!("Unknown variable `{}`", name)
+  }
+
+  fn binary(
+    &mut self,
+    op: naga::BinaryOperator,
+    left: Value,
+    right: Value,
+    out: &mut naga::Block,
+  ) -> Result<Value> {
+    use naga::BinaryOperator as Op;
+    // Only multiplication mixes scalars and vectors in naga
+    let (left, right) = if op == Op::Multiply {
+      (left, right)
+    } else {
+      (
+        self.splat(left, right.ty, out),
+        self.splat(right, left.ty, out),
+      )
+    };
+    let ty = match op {
+      Op::Equal | Op::NotEqual | Op::Less | Op::LessEqual | Op::Greater | Op::GreaterEqual => self
+        .module
+        .with_kind(left.ty, naga::ScalarKind::Bool, naga::BOOL_WIDTH),
+      Op::Multiply => self.module.product_type(left.ty, right.ty),
+      _ => left.ty,
+    };
+    Ok(self.add(
+      naga::Expression::Binary {
+        op,
+        left: left.expression,
+        right: right.expression,
+      },
+      ty,
+      out,
+    ))
+  }
+
+  // Splats a scalar value to the size of the other operand, if it's a vector
+  fn splat(&mut self, value: Value, other: Handle<naga::Type>, out: &mut naga::Block) -> Value {
+    let types = &self.module.module.types;
+    match (&types[value.ty].inner, &types[other].inner) {
+      (naga::TypeInner::Scalar { .. }, naga::TypeInner::Vector { size, .. }) => {
+        let size = *size;
+        let ty = self.module.vector_of(value.ty, size);
+        self.add(
+          naga::Expression::Splat {
+            size,
+            value: value.expression,
+          },
+          ty,
+          out,
+        )
+      }
+      _ => value,
+    }
+  }
+
+  fn convert(
+    &mut self,
+    value: Value,
+    kind: naga::ScalarKind,
+    width: naga::Bytes,
+    out: &mut naga::Block,
+  ) -> Value {
+    let ty = self.module.with_kind(value.ty, kind, width);
+    if ty == value.ty {
+      return value;
+    }
+    self.add(
+      naga::Expression::As {
+        expr: value.expression,
+        kind,
+        convert: Some(width),
+      },
+      ty,
+      out,
+    )
+  }
+
+  fn call(&mut self, call: &syn::ExprCall, out: &mut naga::Block) -> Result<Option<Value>> {
+    let name = match &*call.func {
+      syn::Expr::Path(syn::ExprPath { path, .. }) => path_name(path)?,
+      func => bail!("Unsupported function `{}`", quote::quote!(#func)),
+    };
+    let function = *self
+      .module
+      .functions
+      .get(&name)
+      .with_context(|| format!("Unknown function `{}`", name))?;
+    let arguments = call
+      .args
+      .iter()
+      .map(|arg| Ok(self.expression(arg, out)?.expression))
+      .collect::<Result<Vec<_>>>()?;
+    Ok(self.call_function(function, arguments, out))
+  }
+
+  fn call_function(
+    &mut self,
+    function: Handle<naga::Function>,
+    arguments: Vec<Handle<naga::Expression>>,
+    out: &mut naga::Block,
+  ) -> Option<Value> {
+    let result = self.module.module.functions[function]
+      .result
+      .as_ref()
+      .map(|result| result.ty)
+      .map(|ty| Value {
+        expression: self
+          .function
+          .expressions
+          .append(naga::Expression::CallResult(function), Span::UNDEFINED),
+        ty,
+      });
+    out.push(
+      naga::Statement::Call {
+        function,
+        arguments,
+        result: result.map(|result| result.expression),
+      },
+      Span::UNDEFINED,
+    );
+    result
+  }
+
+  fn method_call(&mut self, call: &syn::ExprMethodCall, out: &mut naga::Block) -> Result<Value> {
+    let method = call.method.to_string();
+    if let Some(captures) = CONSTRUCTOR_METHODS.captures(&method) {
+      let arity = captures["arity"].parse().unwrap();
+      let scalar = match &captures["scalar"] {
+        "" => "f32",
+        scalar => scalar,
+      };
+      return self.construct(&call.receiver, arity, scalar, out);
+    }
+    if let Some(captures) = ACCESS_METHODS.captures(&method) {
+      let vector = self.expression(&call.receiver, out)?;
+      return self.swizzle(vector, &captures["swizzle"], out);
+    }
+    let receiver = self.expression(&call.receiver, out)?;
+    match method.as_str() {
+      "fmod" => {
+        let divisor = self.single_argument(call, out)?;
+        return self.binary(naga::BinaryOperator::Modulo, receiver, divisor, out);
+      }
+      "magnitude_squared" => return Ok(self.dot(receiver, receiver, out)),
+      "distance_squared" => {
+        let other = self.single_argument(call, out)?;
+        let difference = self.binary(naga::BinaryOperator::Subtract, receiver, other, out)?;
+        return Ok(self.dot(difference, difference, out));
+      }
+      _ => {}
+    }
+    let (fun, is_receiver_last) =
+      math_function(&method).with_context(|| format!("Unsupported method `{}`", method))?;
+    let mut arguments = call
+      .args
+      .iter()
+      .map(|arg| self.expression(arg, out))
+      .collect::<Result<Vec<_>>>()?;
+    if is_receiver_last {
+      arguments.push(receiver);
+    } else {
+      arguments.insert(0, receiver);
+    }
+    let ty = match fun {
+      naga::MathFunction::Dot | naga::MathFunction::Length | naga::MathFunction::Distance => {
+        self.module.scalar_of(arguments[0].ty)
+      }
+      _ => arguments[0].ty,
+    };
+    let argument = |index: usize| arguments.get(index).map(|value| value.expression);
+    let expression = naga::Expression::Math {
+      fun,
+      arg: arguments[0].expression,
+      arg1: argument(1),
+      arg2: argument(2),
+      arg3: argument(3),
+    };
+    Ok(self.add(expression, ty, out))
+  }
+
+  fn single_argument(
+    &mut self,
+    call: &syn::ExprMethodCall,
+    out: &mut naga::Block,
+  ) -> Result<Value> {
+    match call.args.iter().collect::<Vec<_>>().as_slice() {
+      [arg] => self.expression(arg, out),
+      _ => bail!("`{}` takes a single argument", call.method),
+    }
+  }
+
+  fn dot(&mut self, left: Value, right: Value, out: &mut naga::Block) -> Value {
+    let ty = self.module.scalar_of(left.ty);
+    self.add(
+      naga::Expression::Math {
+        fun: naga::MathFunction::Dot,
+        arg: left.expression,
+        arg1: Some(right.expression),
+        arg2: None,
+        arg3: None,
+      },
+      ty,
+      out,
+    )
+  }
+
+  fn construct(
+    &mut self,
+    receiver: &syn::Expr,
+    arity: usize,
+    scalar: &str,
+    out: &mut naga::Block,
+  ) -> Result<Value> {
+    let (kind, width) = scalar_kind(scalar)?;
+    let size = vector_size(arity)?;
+    let ty = self
+      .module
+      .insert_type(naga::TypeInner::Vector { size, kind, width });
+    let components = match receiver {
+      syn::Expr::Tuple(syn::ExprTuple { elems, .. }) => elems
+        .iter()
+        .map(|elem| self.expression(elem, out))
+        .collect::<Result<Vec<_>>>()?,
+      _ => vec![self.expression(receiver, out)?],
+    };
+    if let [component] = components.as_slice() {
+      let component = self.convert(*component, kind, width, out);
+      return Ok(match self.module.module.types[component.ty].inner {
+        naga::TypeInner::Scalar { .. } => self.add(
+          naga::Expression::Splat {
+            size,
+            value: component.expression,
+          },
+          ty,
+          out,
+        ),
+        _ => component,
+      });
+    }
+    Ok(
+      self.add(
+        naga::Expression::Compose {
+          ty,
+          components: components
+            .iter()
+            .map(|component| component.expression)
+            .collect(),
+        },
+        ty,
+        out,
+      ),
+    )
+  }
+
+  fn swizzle(&mut self, vector: Value, swizzle: &str, out: &mut naga::Block) -> Result<Value> {
+    if swizzle.len() == 1 {
+      let (index, ty) = self.module.member(vector.ty, swizzle)?;
+      return Ok(self.add(
+        naga::Expression::AccessIndex {
+          base: vector.expression,
+          index,
+        },
+        ty,
+        out,
+      ));
+    }
+    let size = vector_size(swizzle.len())?;
+    let mut pattern = [naga::SwizzleComponent::X; 4];
+    for (index, component) in swizzle.chars().enumerate() {
+      // Checks that the vector has the component
+      self.module.member(vector.ty, &component.to_string())?;
+      pattern[index] = match component {
+        'x' => naga::SwizzleComponent::X,
+        'y' => naga::SwizzleComponent::Y,
+        'z' => naga::SwizzleComponent::Z,
+        _ => naga::SwizzleComponent::W,
+      };
+    }
+    let ty = self.module.vector_of(vector.ty, size);
+    Ok(self.add(
+      naga::Expression::Swizzle {
+        size,
+        vector: vector.expression,
+        pattern,
+      },
+      ty,
+      out,
+    ))
+  }
+}
+
+// Expressions which don't have a value
+fn is_statement(expression: &syn::Expr) -> bool {
+  matches!(
+    expression,
+    syn::Expr::Assign(_)
+      | syn::Expr::AssignOp(_)
+      | syn::Expr::ForLoop(_)
+      | syn::Expr::While(_)
+      | syn::Expr::Loop(_)
+      | syn::Expr::Return(_)
+      | syn::Expr::Break(_)
+      | syn::Expr::Continue(_)
+  )
+}
+
+fn break_block() -> naga::Block {
+  let mut block = naga::Block::new();
+  block.push(naga::Statement::Break, Span::UNDEFINED);
+  block
+}
+
+fn path_name(path: &syn::Path) -> Result<String> {
+  Ok(
+    path
+      .get_ident()
+      .with_context(|| format!("Unsupported path `{}`", quote::quote!(#path)))?
+      .to_string(),
+  )
+}
+
+fn pattern_name(pattern: &syn::Pat) -> Result<Option<String>> {
+  Ok(match pattern {
+    syn::Pat::Ident(syn::PatIdent { ident, .. }) => Some(ident.to_string()),
+    syn::Pat::Wild(_) => None,
+    _ => bail!("Unsupported pattern `{}`", quote::quote!(#pattern)),
+  })
+}
+
+fn scalar_kind(scalar: &str) -> Result<(naga::ScalarKind, naga::Bytes)> {
+  Ok(match scalar {
+    "f32" => (naga::ScalarKind::Float, 4),
+    "i32" => (naga::ScalarKind::Sint, 4),
+    "u32" => (naga::ScalarKind::Uint, 4),
+    "bool" => (naga::ScalarKind::Bool, naga::BOOL_WIDTH),
+    _ => bail!("Unsupported scalar type `{}`", scalar),
+  })
+}
+
+fn vector_size(arity: usize) -> Result<naga::VectorSize> {
+  Ok(match arity {
+    2 => naga::VectorSize::Bi,
+    3 => naga::VectorSize::Tri,
+    4 => naga::VectorSize::Quad,
+    _ => bail!("Unsupported vector size {}", arity),
+  })
+}
+
+fn literal(lit: &syn::Lit) -> Result<naga::Literal> {
+  Ok(match lit {
+    syn::Lit::Float(float) => match float.suffix() {
+      "" | "f32" => naga::Literal::F32(float.base10_parse()?),
+      suffix => bail!("Unsupported literal suffix `{}`", suffix),
+    },
+    syn::Lit::Int(int) => match int.suffix() {
+      "" | "i32" => naga::Literal::I32(int.base10_parse()?),
+      "u32" => naga::Literal::U32(int.base10_parse()?),
+      "f32" => naga::Literal::F32(int.base10_parse()?),
+      suffix => bail!("Unsupported literal suffix `{}`", suffix),
+    },
+    syn::Lit::Bool(bool) => naga::Literal::Bool(bool.value),
+    _ => bail!("Unsupported literal `{}`", quote::quote!(#lit)),
+  })
+}
+
+fn negated(literal: naga::Literal) -> Result<naga::Literal> {
+  Ok(match literal {
+    naga::Literal::F64(value) => naga::Literal::F64(-value),
+    naga::Literal::F32(value) => naga::Literal::F32(-value),
+    naga::Literal::I32(value) => naga::Literal::I32(-value),
+    _ => bail!("Cannot negate {:?}", literal),
+  })
+}
+
+fn binary_operator(op: &syn::BinOp) -> naga::BinaryOperator {
+  use naga::BinaryOperator as Op;
+  use syn::BinOp;
+  match op {
+    BinOp::Add(_) | BinOp::AddEq(_) => Op::Add,
+    BinOp::Sub(_) | BinOp::SubEq(_) => Op::Subtract,
+    BinOp::Mul(_) | BinOp::MulEq(_) => Op::Multiply,
+    BinOp::Div(_) | BinOp::DivEq(_) => Op::Divide,
+    // Rust's `%` truncates, like naga's
+    BinOp::Rem(_) | BinOp::RemEq(_) => Op::Modulo,
+    BinOp::And(_) => Op::LogicalAnd,
+    BinOp::Or(_) => Op::LogicalOr,
+    BinOp::BitXor(_) | BinOp::BitXorEq(_) => Op::ExclusiveOr,
+    BinOp::BitAnd(_) | BinOp::BitAndEq(_) => Op::And,
+    BinOp::BitOr(_) | BinOp::BitOrEq(_) => Op::InclusiveOr,
+    BinOp::Shl(_) | BinOp::ShlEq(_) => Op::ShiftLeft,
+    BinOp::Shr(_) | BinOp::ShrEq(_) => Op::ShiftRight,
+    BinOp::Eq(_) => Op::Equal,
+    BinOp::Lt(_) => Op::Less,
+    BinOp::Le(_) => Op::LessEqual,
+    BinOp::Ne(_) => Op::NotEqual,
+    BinOp::Ge(_) => Op::GreaterEqual,
+    BinOp::Gt(_) => Op::Greater,
+  }
+}
+
+// The naga function for the vek method, and whether the receiver
+// is its last argument
+fn math_function(method: &str) -> Option<(naga::MathFunction, bool)> {
+  use naga::MathFunction as Mf;
+  Some(match method {
+    "abs" => (Mf::Abs, false),
+    "min" => (Mf::Min, false),
+    "max" => (Mf::Max, false),
+    "clamped" => (Mf::Clamp, false),
+    "saturate" => (Mf::Saturate, false),
+    "cos" => (Mf::Cos, false),
+    "cosh" => (Mf::Cosh, false),
+    "sin" => (Mf::Sin, false),
+    "sinh" => (Mf::Sinh, false),
+    "tan" => (Mf::Tan, false),
+    "tanh" => (Mf::Tanh, false),
+    "acos" => (Mf::Acos, false),
+    "asin" => (Mf::Asin, false),
+    "atan" => (Mf::Atan, false),
+    "atan2" => (Mf::Atan2, false),
+    "ceil" => (Mf::Ceil, false),
+    "floor" => (Mf::Floor, false),
+    "round" => (Mf::Round, false),
+    "fract" => (Mf::Fract, false),
+    "trunc" => (Mf::Trunc, false),
+    "exp" => (Mf::Exp, false),
+    "exp2" => (Mf::Exp2, false),
+    "log" => (Mf::Log, false),
+    "log2" => (Mf::Log2, false),
+    "pow" => (Mf::Pow, false),
+    "dot" => (Mf::Dot, false),
+    "cross" => (Mf::Cross, false),
+    "distance" => (Mf::Distance, false),
+    "magnitude" => (Mf::Length, false),
+    "normalized" => (Mf::Normalize, false),
+    "face_forward" => (Mf::FaceForward, false),
+    "reflected" => (Mf::Reflect, false),
+    "refracted" => (Mf::Refract, false),
+    "sign" => (Mf::Sign, false),
+    "sqrt" => (Mf::Sqrt, false),
+    "rsqrt" => (Mf::InverseSqrt, false),
+    "mix" => (Mf::Mix, true),
+    "step" => (Mf::Step, true),
+    "smoothstep" => (Mf::SmoothStep, true),
+    _ => return None,
+  })
+}
+
+#[test]
+fn test_examples_build_valid_naga_modules() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let interface =
+    include_str!("../../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs");
+  // Examples go first, because of their inner attributes
+  for example in &[
+    include_str!("../../examples/simple/simple.rs").to_owned(),
+    include_str!("../../examples/mandelbrot/mandelbrot.rs").to_owned(),
+    include_str!("../../examples/two_dimensional_sdf/two_dimensional_sdf.rs").to_owned(),
+    format!(
+      "{}{}",
+      include_str!("../../examples/raymarching_eyes/raymarching_eyes.rs"),
+      include_str!("../../examples/raymarching_eyes/sdf_utils.rs")
+    ),
+    "pub fn pixel_color(coordinates: Vec2) -> Vec4 {
+      let mut p = (coordinates, 1.0).vec3();
+      p.set_xz(INPUT.window_size);
+      *p.zy_mut() *= 2.0;
+      (p, 1.0).vec4()
+    }"
+    .to_owned(),
+  ] {
+    let module = transpile_to_naga_module(&format!("{}{}", example, interface), &config).unwrap();
+    let info = naga::valid::Validator::new(
+      naga::valid::ValidationFlags::all(),
+      naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .unwrap_or_else(|error| panic!("{:?}\n{:#?}", error, module));
+
+    naga::back::wgsl::write_string(&module, &info, naga::back::wgsl::WriterFlags::empty()).unwrap();
+    naga::back::msl::write_string(
+      &module,
+      &info,
+      &naga::back::msl::Options::default(),
+      &naga::back::msl::PipelineOptions::default(),
+    )
+    .unwrap();
+    naga::back::hlsl::Writer::new(&mut String::new(), &naga::back::hlsl::Options::default())
+      .write(&module, &info)
+      .unwrap();
+    let mut glsl = String::new();
+    naga::back::glsl::Writer::new(
+      &mut glsl,
+      &module,
+      &info,
+      &naga::back::glsl::Options::default(),
+      &naga::back::glsl::PipelineOptions {
+        shader_stage: naga::ShaderStage::Fragment,
+        entry_point: ENTRY_POINT_NAME.to_owned(),
+        multiview: None,
+      },
+      naga::proc::BoundsCheckPolicies::default(),
+    )
+    .unwrap()
+    .write()
+    .unwrap();
+  }
+}
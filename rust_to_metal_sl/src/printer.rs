@@ -1,10 +1,34 @@
+use super::backend::{FunctionAttribute, InputBinding, ShaderBackend, StructField};
+use super::comments;
+use super::EnhanceConfig;
 use anyhow::Result;
 
-pub fn print_ast_into_msl(file: syn::File) -> Result<String> {
+pub fn print_ast(
+  file: syn::File,
+  config: &EnhanceConfig,
+  backend: &dyn ShaderBackend,
+) -> Result<String> {
+  let has_entry_point = file.items.iter().any(|item| {
+    matches!(item, syn::Item::Fn(syn::ItemFn { sig, .. }) if sig.ident == config.entry_point_fn_name)
+  });
+  let input_fields = file
+    .items
+    .iter()
+    .find_map(|item| match item {
+      syn::Item::Struct(strct) if strct.ident == config.param_type => Some(struct_fields(strct)),
+      _ => None,
+    })
+    .unwrap_or_default();
   Ok(format!(
-    "{}{}",
-    AstPrinter::print(&file, PrinterMode::Declarations)?,
-    AstPrinter::print(&file, PrinterMode::Definitions)?
+    "{}{}{}{}",
+    backend.prelude(),
+    AstPrinter::print(&file, PrinterMode::Declarations, config, backend)?,
+    AstPrinter::print(&file, PrinterMode::Definitions, config, backend)?,
+    if has_entry_point {
+      backend.entry_point_wrapper(config, &input_fields)
+    } else {
+      String::new()
+    }
   ))
 }
 
@@ -13,24 +37,36 @@ enum PrinterMode {
   Definitions,
 }
 
-struct AstPrinter {
+struct AstPrinter<'a> {
+  config: &'a EnhanceConfig,
+  backend: &'a dyn ShaderBackend,
   error: Option<anyhow::Error>,
   output: String,
   mode: PrinterMode,
   context: Context,
   indent: String,
   unique_id: u64,
+  // Statements printed at the start of the function body
+  fn_prologue: Vec<String>,
 }
 
-impl AstPrinter {
-  fn print(file: &syn::File, mode: PrinterMode) -> Result<String> {
+impl<'a> AstPrinter<'a> {
+  fn print(
+    file: &syn::File,
+    mode: PrinterMode,
+    config: &'a EnhanceConfig,
+    backend: &'a dyn ShaderBackend,
+  ) -> Result<String> {
     let mut printer = AstPrinter {
+      config,
+      backend,
       error: None,
       output: String::new(),
       mode,
       context: Context::TopLevel,
       indent: String::new(),
       unique_id: 0,
+      fn_prologue: vec![],
     };
     use syn::visit::Visit;
     printer.visit_file(file);
@@ -125,7 +161,7 @@ impl AstPrinter {
   }
 
   fn current_var(&self) -> String {
-    format!("__var__{}", self.unique_id)
+    self.backend.temporary_variable(self.unique_id)
   }
 
   fn done_with_var(&mut self) {
@@ -142,55 +178,101 @@ enum Context {
   ReturnStmt,
 }
 
-impl syn::visit::Visit<'_> for AstPrinter {
+impl syn::visit::Visit<'_> for AstPrinter<'_> {
   fn visit_item_struct(&mut self, strct: &syn::ItemStruct) {
     if !matches!(self.mode, PrinterMode::Declarations) {
       return;
     }
+    for comment_line in comments::attrs_to_comment_lines(&strct.attrs) {
+      self.addln(comment_line);
+    }
     self.addln(format!("struct {} {{", strct.ident.to_string()));
     self.indent(|_self| {
       if let syn::Fields::Named(syn::FieldsNamed { named, .. }) = &strct.fields {
         for field in named.iter() {
-          _self.visit_type(&field.ty);
-          _self.append(format!(" {}", field.ident.as_ref().unwrap().to_string()));
-          _self.appendln(";");
+          for comment_line in comments::attrs_to_comment_lines(&field.attrs) {
+            _self.addln(comment_line);
+          }
+          let mut line = _self
+            .backend
+            .struct_field(&field.ident.as_ref().unwrap().to_string(), &cp(&field.ty));
+          for comment_line in comments::attrs_to_trailing_comments(&field.attrs) {
+            line = format!("{} {}", line, comment_line);
+          }
+          _self.addln(line);
         }
       }
     });
-    self.addln("};\n");
+    self.addln(format!("{}\n", self.backend.struct_end()));
+    if matches!(self.backend.input_binding(), InputBinding::Global)
+      && strct.ident == self.config.param_type
+    {
+      self.addln(
+        self
+          .backend
+          .input_declaration(self.config, &struct_fields(strct)),
+      );
+    }
   }
 
   fn visit_item_const(&mut self, _: &syn::ItemConst) {}
 
   fn visit_item_fn(&mut self, fun: &syn::ItemFn) {
+    if matches!(self.mode, PrinterMode::Declarations) && !self.backend.needs_forward_declarations()
+    {
+      return;
+    }
     self.process_with_context(Context::ItemFn, |_self| {
       let ret_type = match &fun.sig.output {
-        syn::ReturnType::Default => "void".to_string(),
-        syn::ReturnType::Type(_, x) => cp(x),
+        syn::ReturnType::Default => None,
+        syn::ReturnType::Type(_, x) => Some(cp(x)),
       };
       let name = cp(&fun.sig.ident);
-      _self.add(format!("{} {} (", ret_type, name));
+      if let PrinterMode::Definitions = _self.mode {
+        for comment_line in comments::attrs_to_comment_lines(&fun.attrs) {
+          _self.addln(comment_line);
+        }
+      }
 
-      let inputs = &fun.sig.inputs;
-      let num_inputs = inputs.len();
-      let is_last = |i: usize| i == num_inputs - 1;
-      for (i, param) in inputs.iter().enumerate() {
+      let mut params = vec![];
+      for param in fun.sig.inputs.iter() {
         match param {
           syn::FnArg::Typed(syn::PatType { ty, pat, attrs, .. }) => match &**pat {
-            syn::Pat::Ident(syn::PatIdent { ident, .. }) => {
-              attrs.iter().for_each(|attr| {
+            syn::Pat::Ident(syn::PatIdent {
+              ident, mutability, ..
+            }) => {
+              let address_space = attrs.iter().find_map(|attr| {
                 if cp(&attr.path) == "address_space" {
                   if let Ok(syn::Meta::List(syn::MetaList { nested, .. })) = attr.parse_meta() {
                     if let Some(syn::NestedMeta::Meta(syn::Meta::Path(name))) = nested.first() {
-                      _self.add(format!("{} ", cp(name)));
+                      return Some(cp(name));
                     }
                   }
                 }
+                None
               });
-              _self.visit_type(ty);
-              _self.add(format!(" {}", ident));
-              if !is_last(i) {
-                _self.add(", ");
+              let ty = match &**ty {
+                syn::Type::Reference(syn::TypeReference { elem, .. }) => cp(elem),
+                ty => cp(ty),
+              };
+              let name = cp(ident);
+              let param_name = format!("{}_param", name);
+              let mutable_copy = mutability.and_then(|_| {
+                _self
+                  .backend
+                  .mutable_parameter_copy(&name, &param_name, &ty)
+              });
+              params.push(_self.backend.parameter(
+                if mutable_copy.is_some() {
+                  &param_name
+                } else {
+                  &name
+                },
+                &ty,
+                address_space.as_deref(),
+              ));
+              if let PrinterMode::Definitions = _self.mode {
+                _self.fn_prologue.extend(mutable_copy);
               }
             }
             _ => anyhow::bail!("Unsupported argument type"),
@@ -198,7 +280,18 @@ impl syn::visit::Visit<'_> for AstPrinter {
           _ => anyhow::bail!("Unsupported argument type"),
         }
       }
-      _self.add(")");
+      let qualifiers: String = fn_attributes(&fun.attrs)
+        .into_iter()
+        .filter_map(|attribute| _self.backend.function_attribute(attribute))
+        .map(|qualifier| format!("{} ", qualifier))
+        .collect();
+      _self.add(format!(
+        "{}{}",
+        qualifiers,
+        _self
+          .backend
+          .function_signature(&name, &params, ret_type.as_deref())
+      ));
       match _self.mode {
         PrinterMode::Declarations => {
           _self.append(";\n");
@@ -210,22 +303,19 @@ impl syn::visit::Visit<'_> for AstPrinter {
     });
   }
 
-  fn visit_type(&mut self, ty: &syn::Type) {
-    match ty {
-      syn::Type::Reference(syn::TypeReference { elem, .. }) => {
-        self.visit_type(elem);
-        self.add("&");
-      }
-      _ => self.add(cp(ty)),
-    }
-  }
-
   fn visit_block(&mut self, block: &syn::Block) {
     self.addln("{");
     self.indent(|_self| {
+      for statement in std::mem::take(&mut _self.fn_prologue) {
+        _self.addln(statement);
+      }
       if matches!(_self.context, Context::ItemFn | Context::ReturnStmt) {
-        let num_stmts = block.stmts.len();
-        let is_last = |i: usize| i == num_stmts - 1;
+        // Trailing comments shouldn't take the place of the returned expression
+        let last_index = block
+          .stmts
+          .iter()
+          .rposition(|statement| !comments::is_comment_stmt(statement));
+        let is_last = |i: usize| Some(i) == last_index;
         block.stmts.iter().enumerate().for_each(|(i, statement)| {
           _self.with_context(
             if is_last(i) {
@@ -244,28 +334,45 @@ impl syn::visit::Visit<'_> for AstPrinter {
   }
 
   fn visit_stmt(&mut self, statement: &syn::Stmt) {
+    match comments::stmt_to_comment(statement) {
+      Some(comments::StmtComment::Leading(comment_line)) => {
+        self.addln(comment_line);
+        return;
+      }
+      Some(comments::StmtComment::Trailing(comment_line)) => {
+        let has_newline = self.output.ends_with('\n');
+        if has_newline {
+          self.output.pop();
+        }
+        self.append(format!(" {}", comment_line));
+        if has_newline {
+          self.output.push('\n');
+        }
+        return;
+      }
+      None => {}
+    }
     syn::visit::visit_stmt(self, statement);
-    if matches!(statement, syn::Stmt::Semi(_, _)) {
+    if matches!(statement, syn::Stmt::Semi(expression, _) if !matches!(expression, syn::Expr::Block(_)))
+    {
       self.append(";\n");
     }
   }
 
   fn visit_local(&mut self, local: &syn::Local) {
     self.process(|_self| {
-      let ty = match &local.pat {
-        syn::Pat::Type(syn::PatType { ty, .. }) => cp(ty),
-        syn::Pat::Struct(syn::PatStruct { path, .. }) => cp(path),
-        _ => "auto".to_string(),
-      };
-      let var_name = match match &local.pat {
-        syn::Pat::Type(syn::PatType { pat, .. }) => pat,
-        pat => pat,
-      } {
-        syn::Pat::Ident(syn::PatIdent { ident, .. }) => cp(ident),
-        syn::Pat::Struct(_) => _self.current_var(),
-        _ => anyhow::bail!("Unsupported assignment pattern"),
+      let (var_name, ty, is_mutable) = match &local.pat {
+        syn::Pat::Struct(syn::PatStruct { path, .. }) => {
+          (_self.current_var(), Some(cp(path)), false)
+        }
+        pat => match binding_name_and_type(pat) {
+          Some(binding) => binding,
+          None => anyhow::bail!("Unsupported assignment pattern"),
+        },
       };
-      _self.add(format!("{} {}", ty, var_name));
+      // Variables without initializer are assigned to later
+      let is_mutable = is_mutable || local.init.is_none();
+      _self.add(_self.local_declaration(&var_name, ty.as_deref(), is_mutable)?);
       match &local.init {
         Some((_, expression)) => _self.with_context(Context::LetBinding(var_name), |_self| {
           _self.visit_expr(expression);
@@ -276,8 +383,13 @@ impl syn::visit::Visit<'_> for AstPrinter {
         let var_name = _self.current_var();
         for field in fields {
           if let syn::Member::Named(name) = &field.member {
-            if let syn::Pat::Ident(syn::PatIdent { ident, .. }) = &*field.pat {
-              _self.add(format!("; auto {} = {}.{}", ident, var_name, name));
+            if let Some((field_var_name, ty, is_mutable)) = binding_name_and_type(&field.pat) {
+              _self.add(format!(
+                "; {} = {}.{}",
+                _self.local_declaration(&field_var_name, ty.as_deref(), is_mutable)?,
+                var_name,
+                name
+              ));
             } else {
               anyhow::bail!("Unsupported struct member in pattern");
             }
@@ -304,16 +416,31 @@ impl syn::visit::Visit<'_> for AstPrinter {
             limits,
             ..
           }) => {
-            _self.addln(format!(
-              "for (auto {pat} = {from}; {pat} <{limit} {to}; {pat}++)",
-              pat = cp(pat),
-              from = cp(&*from),
-              to = cp(&*to),
-              limit = if matches!(limits, syn::RangeLimits::Closed(_)) {
-                "="
-              } else {
-                ""
+            // `_` is not a valid variable name in all languages
+            let (var_name, ty) = match pat {
+              syn::Pat::Wild(_) => {
+                let var_name = _self.current_var();
+                _self.done_with_var();
+                (var_name, None)
+              }
+              syn::Pat::Type(syn::PatType { pat, ty, .. })
+                if matches!(**pat, syn::Pat::Wild(_)) =>
+              {
+                let var_name = _self.current_var();
+                _self.done_with_var();
+                (var_name, Some(cp(&**ty)))
+              }
+              pat => match binding_name_and_type(pat) {
+                Some((var_name, ty, _)) => (var_name, ty),
+                None => anyhow::bail!("Unsupported for loop pattern"),
               },
+            };
+            _self.addln(_self.backend.for_range(
+              &var_name,
+              ty.as_deref(),
+              &cp(from),
+              &cp(to),
+              matches!(limits, syn::RangeLimits::Closed(_)),
             ));
             _self.visit_block(body);
           }
@@ -337,21 +464,7 @@ impl syn::visit::Visit<'_> for AstPrinter {
           } = if_expression;
           let is_in_binding = binding_context.is_some();
           if is_in_binding && is_if_simple_ternary(if_expression) {
-            _self.append(format!(" = ({}) ? ", cp(cond)));
-            _self.with_context(Context::NormalStmt, |_self| {
-              _self.visit_stmt(then_branch.stmts.first().unwrap());
-              _self.append(" : ");
-              let else_clause = &*else_branch.as_ref().unwrap().1;
-              match else_clause {
-                syn::Expr::If(_) => _self.visit_expr(else_clause),
-                syn::Expr::Block(syn::ExprBlock { block, .. }) => {
-                  _self.visit_stmt(block.stmts.first().unwrap())
-                }
-                // Never happens
-                _ => {}
-              }
-              _self.appendln(";");
-            })
+            _self.appendln(format!(" = {};", _self.print_ternary(if_expression)));
           } else {
             if is_in_binding {
               // close the variable declaration
@@ -388,6 +501,98 @@ impl syn::visit::Visit<'_> for AstPrinter {
   }
 }
 
+impl AstPrinter<'_> {
+  fn local_declaration(&self, name: &str, ty: Option<&str>, is_mutable: bool) -> Result<String> {
+    if ty.is_none() && self.backend.needs_explicit_local_types() {
+      anyhow::bail!(
+        "Could not infer the type of `{}`, add a type annotation",
+        name
+      );
+    }
+    Ok(self.backend.local_declaration(name, ty, is_mutable))
+  }
+
+  // Only called for `is_if_simple_ternary` expressions
+  fn print_ternary(&self, if_expression: &syn::ExprIf) -> String {
+    let syn::ExprIf {
+      cond,
+      then_branch,
+      else_branch,
+      ..
+    } = if_expression;
+    let else_clause = &*else_branch.as_ref().unwrap().1;
+    let otherwise = match else_clause {
+      syn::Expr::If(nested_if_expression) => self.print_ternary(nested_if_expression),
+      syn::Expr::Block(syn::ExprBlock { block, .. }) => self.print_ternary_clause(block),
+      // Never happens
+      _ => String::new(),
+    };
+    self.backend.ternary(
+      &cp(cond),
+      &self.print_ternary_clause(then_branch),
+      &otherwise,
+    )
+  }
+
+  fn print_ternary_clause(&self, block: &syn::Block) -> String {
+    match block.stmts.first().unwrap() {
+      syn::Stmt::Expr(syn::Expr::If(nested_if_expression)) => {
+        self.print_ternary(nested_if_expression)
+      }
+      statement => cp(statement),
+    }
+  }
+}
+
+// Returns the variable name, type and mutability of `x` or `mut x: T`
+fn binding_name_and_type(pattern: &syn::Pat) -> Option<(String, Option<String>, bool)> {
+  match pattern {
+    syn::Pat::Ident(syn::PatIdent {
+      ident, mutability, ..
+    }) => Some((cp(ident), None, mutability.is_some())),
+    syn::Pat::Type(syn::PatType { pat, ty, .. }) => {
+      let (name, _, is_mutable) = binding_name_and_type(pat)?;
+      Some((name, Some(cp(ty)), is_mutable))
+    }
+    _ => None,
+  }
+}
+
+fn struct_fields(strct: &syn::ItemStruct) -> Vec<StructField> {
+  strct
+    .fields
+    .iter()
+    .filter_map(|field| {
+      Some(StructField {
+        name: field.ident.as_ref()?.to_string(),
+        ty: cp(&field.ty),
+      })
+    })
+    .collect()
+}
+
+fn fn_attributes(attrs: &[syn::Attribute]) -> Vec<FunctionAttribute> {
+  attrs
+    .iter()
+    .filter_map(|attr| match attr.parse_meta() {
+      Ok(syn::Meta::Path(path)) if path.is_ident("inline") => Some(FunctionAttribute::Inline),
+      Ok(syn::Meta::Path(path)) if path.is_ident("cold") => Some(FunctionAttribute::Cold),
+      Ok(syn::Meta::List(syn::MetaList { path, nested, .. })) if path.is_ident("inline") => {
+        match nested.first() {
+          Some(syn::NestedMeta::Meta(syn::Meta::Path(arg))) if arg.is_ident("always") => {
+            Some(FunctionAttribute::AlwaysInline)
+          }
+          Some(syn::NestedMeta::Meta(syn::Meta::Path(arg))) if arg.is_ident("never") => {
+            Some(FunctionAttribute::NeverInline)
+          }
+          _ => None,
+        }
+      }
+      _ => None,
+    })
+    .collect()
+}
+
 fn cp<T>(x: &T) -> String
 where
   T: quote::ToTokens,
@@ -445,3 +650,21 @@ fn is_if_simple_ternary(expression: &syn::ExprIf) -> bool {
   } = expression;
   is_block_simple_ternary_clause(then_branch) && is_else_clause_simple_ternary_clause(else_branch)
 }
+
+#[test]
+fn test_print_ternary_chain() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let msl = crate::transpile(
+    "fn f(a: f32) -> f32 {
+      let b = if a > 0.0 { 1.0 } else if a < -1.0 { 2.0 } else { 3.0 };
+      b
+    }",
+    &config,
+  )
+  .unwrap();
+  assert!(msl.contains("auto b = (a > 0.0) ? 1.0 : (a < - 1.0) ? 2.0 : 3.0;"));
+}
@@ -1,16 +1,97 @@
 mod adapter;
+mod backend;
+mod comments;
 mod enhancer;
+mod formatter;
+mod glsl;
+mod hlsl;
+mod msl;
+#[cfg(feature = "naga")]
+mod naga_interpreter;
+#[cfg(feature = "naga")]
+mod naga_module;
 mod parser;
 mod printer;
+mod types;
+mod wgsl;
 
 use anyhow::Result;
 
+pub use backend::{BuiltinFunction, FunctionAttribute, InputBinding, ShaderBackend, StructField};
 pub use enhancer::EnhanceConfig;
+pub use formatter::{format_msl, format_wgsl};
+pub use glsl::GlslBackend;
+pub use hlsl::HlslBackend;
+pub use msl::MslBackend;
+#[cfg(feature = "naga")]
+pub use naga_interpreter::{NagaInterpreter, Value};
+#[cfg(feature = "naga")]
+pub use naga_module::transpile_to_naga_module;
+pub use wgsl::WgslBackend;
 
 /// Returns Metal Shader Language source code.
 pub fn transpile(rust_source: &str, config: &EnhanceConfig) -> Result<String> {
+  transpile_with_backend(rust_source, config, &MslBackend)
+}
+
+/// Returns source code in the shading language of the given backend.
+pub fn transpile_with_backend(
+  rust_source: &str,
+  config: &EnhanceConfig,
+  backend: &dyn ShaderBackend,
+) -> Result<String> {
+  transpile_with_phases(rust_source, config, backend, &mut |_, _| Ok(()))
+}
+
+/// The steps of transpiling, in order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Phase {
+  Parse,
+  Enhance,
+  Adapt,
+  Print,
+}
+
+impl Phase {
+  pub fn name(self) -> &'static str {
+    match self {
+      Phase::Parse => "parse",
+      Phase::Enhance => "enhance",
+      Phase::Adapt => "adapt",
+      Phase::Print => "print",
+    }
+  }
+}
+
+/// Like `transpile_with_backend`, calling `after_phase` with how long each
+/// phase took. Stops with the error of `after_phase` when it fails, so that
+/// callers can cancel between phases.
+pub fn transpile_with_phases(
+  rust_source: &str,
+  config: &EnhanceConfig,
+  backend: &dyn ShaderBackend,
+  after_phase: &mut dyn FnMut(Phase, std::time::Duration) -> Result<()>,
+) -> Result<String> {
+  let mut phase_start_time = std::time::Instant::now();
+  let mut finish_phase = |phase| {
+    let result = after_phase(phase, phase_start_time.elapsed());
+    phase_start_time = std::time::Instant::now();
+    result
+  };
   let rust_ast = parser::parse_rust_into_ast(rust_source)?;
-  let rust_ast_enhanced = enhancer::convert_constant_to_param(rust_ast, config);
-  let msl_compatible_ast = adapter::make_rust_ast_msl_compatible(rust_ast_enhanced);
-  printer::print_ast_into_msl(msl_compatible_ast)
+  let rust_ast_with_comments = comments::attach_comments(rust_ast, rust_source);
+  finish_phase(Phase::Parse)?;
+  let rust_ast_enhanced = match backend.input_binding() {
+    InputBinding::Parameter { address_space } => {
+      enhancer::convert_constant_to_param(rust_ast_with_comments, config, &address_space)
+    }
+    InputBinding::Global => rust_ast_with_comments,
+  };
+  let rust_ast_typed = types::make_types_explicit(rust_ast_enhanced, backend);
+  finish_phase(Phase::Enhance)?;
+  let compatible_ast = adapter::make_rust_ast_compatible(rust_ast_typed, backend)?;
+  finish_phase(Phase::Adapt)?;
+  let shader = printer::print_ast(compatible_ast, config, backend)?;
+  finish_phase(Phase::Print)?;
+  Ok(shader)
 }
@@ -0,0 +1,561 @@
+//! Infers the types of Rust expressions, for backends which can't rely
+//! on the target compiler to convert between scalars and vectors,
+//! to infer the types of local variables, to multiply matrices with `*`
+//! or to pick the overload of a function.
+//!
+//! Only the subset of Rust used by shaders is understood, expressions
+//! of unknown types are left untouched.
+
+use super::backend::ShaderBackend;
+use std::collections::HashMap;
+
+pub fn make_types_explicit(mut rust_ast: syn::File, backend: &dyn ShaderBackend) -> syn::File {
+  let mut inferrer = TypeInferrer::new(&rust_ast);
+  inferrer.is_splatting_scalars = !backend.has_implicit_vector_splat();
+  inferrer.is_annotating_locals = backend.needs_explicit_local_types();
+  inferrer.matrix_multiplication_function = backend.matrix_multiplication_function();
+  inferrer.is_suffixing_widths = !backend.has_function_overloading();
+  if inferrer.is_splatting_scalars
+    || inferrer.is_annotating_locals
+    || inferrer.matrix_multiplication_function.is_some()
+    || inferrer.is_suffixing_widths
+  {
+    use syn::visit_mut::VisitMut;
+    inferrer.visit_file_mut(&mut rust_ast);
+  }
+  rust_ast
+}
+
+lazy_static::lazy_static! {
+  static ref ACCESS_METHODS: regex::Regex = regex::Regex::new(r"^[xywz]{1,4}$").unwrap();
+  static ref CONSTRUCTOR_METHODS: regex::Regex =
+    regex::Regex::new(r"^vec(?P<arity>[234])(?P<scalar>\w*)$").unwrap();
+}
+
+const DEFAULT_SCALAR: &str = "f32";
+
+// Return a scalar of the receiver's component type
+const SCALAR_RESULT_METHODS: &[&str] = &[
+  "magnitude",
+  "magnitude_squared",
+  "dot",
+  "distance",
+  "distance_squared",
+];
+
+// Implemented by the prelude of backends, once per vector width
+const WIDTH_SUFFIXED_METHODS: &[&str] = &["magnitude_squared", "distance_squared"];
+
+// Component-wise functions whose arguments need to have the same type
+const COMPONENT_WISE_METHODS: &[&str] = &[
+  "min",
+  "max",
+  "clamped",
+  "pow",
+  "fmod",
+  "step",
+  "smoothstep",
+  "atan2",
+];
+
+#[derive(Clone, PartialEq)]
+pub enum Type {
+  Scalar(String),
+  Vector(String, usize),
+  Matrix(String, usize),
+  Struct(String),
+}
+
+impl Type {
+  pub(crate) fn from_rust(ty: &syn::Type) -> Option<Type> {
+    let path = match ty {
+      syn::Type::Path(syn::TypePath { path, .. }) => path,
+      syn::Type::Reference(syn::TypeReference { elem, .. }) => return Self::from_rust(elem),
+      _ => return None,
+    };
+    let segment = path.segments.last()?;
+    let name = segment.ident.to_string();
+    let scalar = || match &segment.arguments {
+      syn::PathArguments::AngleBracketed(args) => {
+        let args = &args.args;
+        quote::quote!(#args).to_string()
+      }
+      _ => DEFAULT_SCALAR.to_owned(),
+    };
+    if let Some(Ok(arity)) = name.strip_prefix("Vec").map(str::parse) {
+      return Some(Type::Vector(scalar(), arity));
+    }
+    if let Some(Ok(dimension)) = name.strip_prefix("Mat").map(str::parse) {
+      return Some(Type::Matrix(scalar(), dimension));
+    }
+    Some(if is_scalar(&name) {
+      Type::Scalar(name)
+    } else {
+      Type::Struct(name)
+    })
+  }
+
+  fn to_rust(&self) -> syn::Type {
+    syn::parse_str(&match self {
+      Type::Scalar(scalar) => scalar.clone(),
+      Type::Vector(scalar, arity) if scalar == DEFAULT_SCALAR => format!("Vec{}", arity),
+      Type::Vector(scalar, arity) => format!("Vec{}<{}>", arity, scalar),
+      Type::Matrix(scalar, dimension) if scalar == DEFAULT_SCALAR => format!("Mat{}", dimension),
+      Type::Matrix(scalar, dimension) => format!("Mat{}<{}>", dimension, scalar),
+      Type::Struct(name) => name.clone(),
+    })
+    .unwrap()
+  }
+
+  fn scalar(&self) -> Option<&str> {
+    match self {
+      Type::Scalar(scalar) | Type::Vector(scalar, _) | Type::Matrix(scalar, _) => Some(scalar),
+      Type::Struct(_) => None,
+    }
+  }
+}
+
+fn is_scalar(name: &str) -> bool {
+  matches!(
+    name,
+    "bool" | "i8" | "u8" | "i16" | "u16" | "i32" | "u32" | "i64" | "u64" | "f16" | "f32"
+  )
+}
+
+// Vectors win over matrices and scalars, as in `v * 2.0` or `m * v`,
+// and matrices over scalars
+fn widest(types: impl IntoIterator<Item = Option<Type>>) -> Option<Type> {
+  types
+    .into_iter()
+    .flatten()
+    .fold(None, |widest, ty| match (&widest, &ty) {
+      (Some(Type::Vector(_, _)), _) | (Some(Type::Matrix(_, _)), Type::Scalar(_)) => widest,
+      _ => Some(ty),
+    })
+}
+
+pub struct TypeInferrer {
+  /// `v.max(0.0)` becomes `v.max((0.0).vec3())`
+  pub is_splatting_scalars: bool,
+  /// `let x = 1.0` becomes `let x: f32 = 1.0`
+  pub is_annotating_locals: bool,
+  /// `m * v` becomes `mul(m, v)`
+  pub matrix_multiplication_function: Option<String>,
+  /// `v.magnitude_squared()` becomes `v.magnitude_squared3()`
+  pub is_suffixing_widths: bool,
+  fn_return_types: HashMap<String, Type>,
+  struct_fields: HashMap<String, HashMap<String, Type>>,
+  constants: HashMap<String, Type>,
+  scopes: Vec<HashMap<String, Type>>,
+}
+
+impl TypeInferrer {
+  pub fn new(rust_ast: &syn::File) -> Self {
+    let mut inferrer = Self {
+      is_splatting_scalars: false,
+      is_annotating_locals: false,
+      matrix_multiplication_function: None,
+      is_suffixing_widths: false,
+      fn_return_types: HashMap::new(),
+      struct_fields: HashMap::new(),
+      constants: HashMap::new(),
+      scopes: vec![],
+    };
+    for item in &rust_ast.items {
+      match item {
+        syn::Item::Fn(syn::ItemFn { sig, .. }) => {
+          if let syn::ReturnType::Type(_, ty) = &sig.output {
+            if let Some(ty) = Type::from_rust(ty) {
+              inferrer.fn_return_types.insert(sig.ident.to_string(), ty);
+            }
+          }
+        }
+        syn::Item::Struct(strct) => {
+          let fields = strct
+            .fields
+            .iter()
+            .filter_map(|field| {
+              Some((
+                field.ident.as_ref()?.to_string(),
+                Type::from_rust(&field.ty)?,
+              ))
+            })
+            .collect();
+          inferrer
+            .struct_fields
+            .insert(strct.ident.to_string(), fields);
+        }
+        syn::Item::Const(syn::ItemConst { ident, ty, .. }) => {
+          if let Some(ty) = Type::from_rust(ty) {
+            inferrer.constants.insert(ident.to_string(), ty);
+          }
+        }
+        _ => {}
+      }
+    }
+    inferrer
+  }
+
+  pub fn with_scope<F>(&mut self, processor: F)
+  where
+    F: FnOnce(&mut Self),
+  {
+    self.scopes.push(HashMap::new());
+    processor(self);
+    self.scopes.pop();
+  }
+
+  pub fn bind(&mut self, name: String, ty: Type) {
+    if let Some(scope) = self.scopes.last_mut() {
+      scope.insert(name, ty);
+    }
+  }
+
+  // Binds the variables declared by a `let` or a parameter
+  pub fn bind_pattern(&mut self, pattern: &syn::Pat, ty: Option<Type>) {
+    match pattern {
+      syn::Pat::Type(syn::PatType { pat, ty, .. }) => self.bind_pattern(pat, Type::from_rust(ty)),
+      syn::Pat::Ident(syn::PatIdent { ident, .. }) => {
+        if let Some(ty) = ty {
+          self.bind(ident.to_string(), ty);
+        }
+      }
+      syn::Pat::Struct(syn::PatStruct { path, fields, .. }) => {
+        let struct_type = Type::from_rust(&syn::parse_quote!(#path));
+        for field in fields {
+          if let syn::Member::Named(name) = &field.member {
+            let ty = self.field_type(struct_type.as_ref(), &name.to_string());
+            self.bind_pattern(&field.pat, ty);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  // Adds type annotations to variables declared by a `let`
+  fn annotate_pattern(&self, pattern: &mut syn::Pat, ty: Option<Type>) {
+    match pattern {
+      syn::Pat::Ident(_) | syn::Pat::Wild(_) => {
+        if let Some(ty) = ty {
+          let ty = ty.to_rust();
+          *pattern = syn::Pat::Type(syn::PatType {
+            attrs: vec![],
+            pat: Box::new(pattern.clone()),
+            colon_token: Default::default(),
+            ty: Box::new(ty),
+          });
+        }
+      }
+      syn::Pat::Struct(syn::PatStruct { path, fields, .. }) => {
+        let struct_type = Type::from_rust(&syn::parse_quote!(#path));
+        for field in fields {
+          if let syn::Member::Named(name) = &field.member {
+            let ty = self.field_type(struct_type.as_ref(), &name.to_string());
+            self.annotate_pattern(&mut field.pat, ty);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+
+  fn lookup(&self, name: &str) -> Option<Type> {
+    self
+      .scopes
+      .iter()
+      .rev()
+      .find_map(|scope| scope.get(name))
+      .or_else(|| self.constants.get(name))
+      .cloned()
+  }
+
+  fn field_type(&self, base: Option<&Type>, field: &str) -> Option<Type> {
+    match base? {
+      Type::Struct(name) => self.struct_fields.get(name)?.get(field).cloned(),
+      vector @ Type::Vector(_, _) => swizzle_type(vector, field),
+      Type::Scalar(_) | Type::Matrix(_, _) => None,
+    }
+  }
+
+  pub fn type_of(&self, expression: &syn::Expr) -> Option<Type> {
+    match expression {
+      syn::Expr::Lit(syn::ExprLit { lit, .. }) => match lit {
+        syn::Lit::Float(float) => Some(Type::Scalar(
+          Some(float.suffix())
+            .filter(|suffix| !suffix.is_empty())
+            .unwrap_or(DEFAULT_SCALAR)
+            .to_owned(),
+        )),
+        syn::Lit::Int(int) => Some(Type::Scalar(
+          Some(int.suffix())
+            .filter(|suffix| !suffix.is_empty())
+            .unwrap_or("i32")
+            .to_owned(),
+        )),
+        syn::Lit::Bool(_) => Some(Type::Scalar("bool".to_owned())),
+        _ => None,
+      },
+      syn::Expr::Path(syn::ExprPath { path, .. }) => self.lookup(&path.get_ident()?.to_string()),
+      syn::Expr::Paren(syn::ExprParen { expr, .. })
+      | syn::Expr::Group(syn::ExprGroup { expr, .. })
+      | syn::Expr::Unary(syn::ExprUnary { expr, .. }) => self.type_of(expr),
+      syn::Expr::Cast(syn::ExprCast { ty, .. }) => Type::from_rust(ty),
+      syn::Expr::Binary(syn::ExprBinary {
+        left, op, right, ..
+      }) => match op {
+        syn::BinOp::Add(_)
+        | syn::BinOp::Sub(_)
+        | syn::BinOp::Mul(_)
+        | syn::BinOp::Div(_)
+        | syn::BinOp::Rem(_) => widest(vec![self.type_of(left), self.type_of(right)]),
+        syn::BinOp::Eq(_)
+        | syn::BinOp::Lt(_)
+        | syn::BinOp::Le(_)
+        | syn::BinOp::Ne(_)
+        | syn::BinOp::Ge(_)
+        | syn::BinOp::Gt(_)
+        | syn::BinOp::And(_)
+        | syn::BinOp::Or(_) => Some(Type::Scalar("bool".to_owned())),
+        _ => None,
+      },
+      syn::Expr::Field(syn::ExprField {
+        base,
+        member: syn::Member::Named(field),
+        ..
+      }) => self.field_type(self.type_of(base).as_ref(), &field.to_string()),
+      syn::Expr::Call(syn::ExprCall { func, args, .. }) => match &**func {
+        syn::Expr::Path(syn::ExprPath { path, .. }) => {
+          let name = path.get_ident()?.to_string();
+          if self.matrix_multiplication_function.as_ref() == Some(&name) {
+            return widest(args.iter().map(|arg| self.type_of(arg)));
+          }
+          self.fn_return_types.get(&name).cloned()
+        }
+        _ => None,
+      },
+      syn::Expr::If(syn::ExprIf { then_branch, .. }) => self.type_of_block(then_branch),
+      syn::Expr::Block(syn::ExprBlock { block, .. }) => self.type_of_block(block),
+      syn::Expr::MethodCall(syn::ExprMethodCall {
+        receiver,
+        method,
+        args,
+        ..
+      }) => {
+        let method = without_width_suffix(&method.to_string()).to_owned();
+        if let Some(captures) = CONSTRUCTOR_METHODS.captures(&method) {
+          let scalar = match &captures["scalar"] {
+            "" => DEFAULT_SCALAR,
+            scalar => scalar,
+          };
+          return Some(Type::Vector(
+            scalar.to_owned(),
+            captures["arity"].parse().unwrap(),
+          ));
+        }
+        let receiver_type = self.type_of(receiver);
+        if ACCESS_METHODS.is_match(&method) {
+          return swizzle_type(&receiver_type?, &method);
+        }
+        if let Some(swizzle) = method.strip_suffix("_mut") {
+          return swizzle_type(&receiver_type?, swizzle);
+        }
+        match method.as_str() {
+          "cross" => receiver_type,
+          "mix" => widest(args.iter().map(|arg| self.type_of(arg))),
+          _ if SCALAR_RESULT_METHODS.contains(&method.as_str()) => {
+            Some(Type::Scalar(receiver_type?.scalar()?.to_owned()))
+          }
+          _ if COMPONENT_WISE_METHODS.contains(&method.as_str()) => {
+            widest(std::iter::once(receiver_type).chain(args.iter().map(|arg| self.type_of(arg))))
+          }
+          _ => receiver_type,
+        }
+      }
+      _ => None,
+    }
+  }
+
+  fn type_of_block(&self, block: &syn::Block) -> Option<Type> {
+    match block.stmts.last()? {
+      syn::Stmt::Expr(expression) => self.type_of(expression),
+      _ => None,
+    }
+  }
+
+  // Wraps scalar arguments of component-wise functions into vectors,
+  // `v.max(0.0)` becomes `v.max((0.0).vec3())`
+  fn splat_scalar_args(&self, call: &mut syn::ExprMethodCall) {
+    let method = call.method.to_string();
+    let is_component_wise = COMPONENT_WISE_METHODS.contains(&method.as_str());
+    // `mix` accepts a scalar factor, which is the receiver in Rust
+    if !is_component_wise && method != "mix" {
+      return;
+    }
+    let (scalar, arity) = match self.type_of(&syn::Expr::MethodCall(call.clone())) {
+      Some(Type::Vector(scalar, arity)) => (scalar, arity),
+      _ => return,
+    };
+    let constructor = if scalar == DEFAULT_SCALAR {
+      quote::format_ident!("vec{}", arity)
+    } else {
+      quote::format_ident!("vec{}{}", arity, scalar)
+    };
+    let splat = |inferrer: &Self, operand: &mut syn::Expr| {
+      if let Some(Type::Scalar(_)) = inferrer.type_of(operand) {
+        *operand = syn::parse_quote!((#operand).#constructor());
+      }
+    };
+    if is_component_wise {
+      splat(self, &mut call.receiver);
+    }
+    for arg in call.args.iter_mut() {
+      splat(self, arg);
+    }
+  }
+
+  // `v.magnitude_squared()` becomes `v.magnitude_squared3()`
+  fn suffix_width(&self, call: &mut syn::ExprMethodCall) {
+    if !WIDTH_SUFFIXED_METHODS.contains(&call.method.to_string().as_str()) {
+      return;
+    }
+    if let Some(Type::Vector(_, arity)) = self.type_of(&call.receiver) {
+      call.method = quote::format_ident!("{}{}", call.method, arity);
+    }
+  }
+
+  // `m * v` becomes `mul(m, v)` and `m *= n` becomes `m = mul(m, n)`
+  fn multiply_matrices(&self, expression: &mut syn::Expr) {
+    let function = match &self.matrix_multiplication_function {
+      Some(function) => quote::format_ident!("{}", function),
+      None => return,
+    };
+    let is_matrix = |operand: &syn::Expr| matches!(self.type_of(operand), Some(Type::Matrix(_, _)));
+    match expression {
+      syn::Expr::Binary(syn::ExprBinary {
+        left,
+        op: syn::BinOp::Mul(_),
+        right,
+        ..
+      }) if is_matrix(left) || is_matrix(right) => {
+        *expression = syn::parse_quote!(#function(#left, #right));
+      }
+      syn::Expr::AssignOp(syn::ExprAssignOp {
+        left,
+        op: syn::BinOp::MulEq(_),
+        right,
+        ..
+      }) if is_matrix(left) || is_matrix(right) => {
+        *expression = syn::parse_quote!(#left = #function(#left, #right));
+      }
+      _ => {}
+    }
+  }
+}
+
+// The name of a method before `suffix_width`
+fn without_width_suffix(method: &str) -> &str {
+  let base = method.trim_end_matches(|c: char| c.is_ascii_digit());
+  if base.len() < method.len() && WIDTH_SUFFIXED_METHODS.contains(&base) {
+    base
+  } else {
+    method
+  }
+}
+
+fn swizzle_type(vector: &Type, swizzle: &str) -> Option<Type> {
+  if !ACCESS_METHODS.is_match(swizzle) {
+    return None;
+  }
+  let scalar = vector.scalar()?.to_owned();
+  Some(match swizzle.len() {
+    1 => Type::Scalar(scalar),
+    arity => Type::Vector(scalar, arity),
+  })
+}
+
+impl syn::visit_mut::VisitMut for TypeInferrer {
+  fn visit_item_fn_mut(&mut self, item_fn: &mut syn::ItemFn) {
+    self.with_scope(|_self| {
+      for param in &item_fn.sig.inputs {
+        if let syn::FnArg::Typed(syn::PatType { pat, ty, .. }) = param {
+          _self.bind_pattern(pat, Type::from_rust(ty));
+        }
+      }
+      _self.visit_block_mut(&mut item_fn.block);
+    });
+  }
+
+  fn visit_block_mut(&mut self, block: &mut syn::Block) {
+    self.with_scope(|_self| syn::visit_mut::visit_block_mut(_self, block));
+  }
+
+  fn visit_local_mut(&mut self, local: &mut syn::Local) {
+    if let Some((_, init)) = &mut local.init {
+      self.visit_expr_mut(init);
+    }
+    let ty = local.init.as_ref().and_then(|(_, init)| self.type_of(init));
+    self.bind_pattern(&local.pat, ty.clone());
+    if self.is_annotating_locals {
+      self.annotate_pattern(&mut local.pat, ty);
+    }
+  }
+
+  fn visit_expr_mut(&mut self, expression: &mut syn::Expr) {
+    if let syn::Expr::ForLoop(syn::ExprForLoop {
+      pat, expr, body, ..
+    }) = expression
+    {
+      self.visit_expr_mut(expr);
+      let ty = match &**expr {
+        syn::Expr::Range(syn::ExprRange {
+          from: Some(from), ..
+        }) => self.type_of(from),
+        _ => None,
+      };
+      self.with_scope(|_self| {
+        _self.bind_pattern(pat, ty.clone());
+        if _self.is_annotating_locals {
+          _self.annotate_pattern(pat, ty);
+        }
+        _self.visit_block_mut(body);
+      });
+      return;
+    }
+    syn::visit_mut::visit_expr_mut(self, expression);
+    if let syn::Expr::MethodCall(call) = expression {
+      if self.is_splatting_scalars {
+        self.splat_scalar_args(call);
+      }
+      if self.is_suffixing_widths {
+        self.suffix_width(call);
+      }
+    }
+    self.multiply_matrices(expression);
+  }
+}
+
+#[test]
+fn test_splat_scalar_args() {
+  let rust_ast: syn::File = syn::parse_quote! {
+    fn f(v: Vec3, t: f32) -> Vec3 {
+      let d = (v - 1.0).abs();
+      t.mix(d.max(0.0), 1.0.vec3())
+    }
+  };
+  let mut converted = rust_ast.clone();
+  let mut inferrer = TypeInferrer::new(&rust_ast);
+  inferrer.is_splatting_scalars = true;
+  use syn::visit_mut::VisitMut;
+  inferrer.visit_file_mut(&mut converted);
+  let expected: syn::File = syn::parse_quote! {
+    fn f(v: Vec3, t: f32) -> Vec3 {
+      let d = (v - 1.0).abs();
+      t.mix(d.max((0.0).vec3()), 1.0.vec3())
+    }
+  };
+  assert_eq!(
+    quote::quote!(#converted).to_string(),
+    quote::quote!(#expected).to_string()
+  );
+}
@@ -0,0 +1,289 @@
+use super::backend::{BuiltinFunction, InputBinding, ShaderBackend, StructField, MATH_CONSTANTS};
+use super::EnhanceConfig;
+
+/// WebGPU Shading Language
+///
+/// The input struct needs to be part of the transpiled source, so the shader
+/// should be transpiled together with the interface.
+pub struct WgslBackend;
+
+impl ShaderBackend for WgslBackend {
+  fn name(&self) -> &'static str {
+    "WGSL"
+  }
+
+  fn scalar_type(&self, rust_type: &str) -> Option<String> {
+    match rust_type {
+      "bool" | "i32" | "u32" | "f16" | "f32" => Some(rust_type.to_owned()),
+      _ => None,
+    }
+  }
+
+  fn vector_type(&self, rust_scalar_type: &str, arity: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("vec{}<{}>", arity, scalar_type))
+  }
+
+  fn matrix_type(&self, rust_scalar_type: &str, dimension: usize) -> Option<String> {
+    self
+      .scalar_type(rust_scalar_type)
+      .map(|scalar_type| format!("mat{}x{}<{}>", dimension, dimension, scalar_type))
+  }
+
+  fn builtin_function(&self, rust_method: &str) -> Option<BuiltinFunction> {
+    // Suffixed with the vector width, see `has_function_overloading`
+    if let Some(width) = rust_method.strip_prefix("magnitude_squared") {
+      return Some(BuiltinFunction::Function(format!(
+        "length_squared{}",
+        width
+      )));
+    }
+    let wgsl_function = match rust_method {
+      "clamped" => "clamp",
+      "magnitude" => "length",
+      "face_forward" => "faceForward",
+      "normalized" => "normalize",
+      "reflected" => "reflect",
+      "refracted" => "refract",
+      "rsqrt" => "inverseSqrt",
+      // WGSL `%` truncates like `fmod`
+      "fmod" => return Some(BuiltinFunction::BinaryOperator("%".to_owned())),
+      _ => return None,
+    };
+    Some(BuiltinFunction::Function(wgsl_function.to_owned()))
+  }
+
+  fn input_binding(&self) -> InputBinding {
+    InputBinding::Global
+  }
+
+  // `bool` is not allowed in uniform buffers, so if the input contains one
+  // we bind a copy of it with `u32` fields and convert it in the entry point.
+  fn input_declaration(&self, config: &EnhanceConfig, fields: &[StructField]) -> String {
+    let binding = "@group(0) @binding(0) var<uniform>";
+    if !has_bool_field(fields) {
+      return format!(
+        "{} {}: {};\n",
+        binding, config.constant_name, config.param_type
+      );
+    }
+    let uniform_fields: String = fields
+      .iter()
+      .map(|field| {
+        format!(
+          "  {}: {},\n",
+          field.name,
+          if field.ty == "bool" { "u32" } else { &field.ty }
+        )
+      })
+      .collect();
+    format!(
+      "struct {uniform_type} {{\n{fields}}}\n\n\
+       {binding} {uniform}: {uniform_type};\n\
+       var<private> {constant}: {param_type};\n",
+      uniform_type = uniform_type_name(config),
+      fields = uniform_fields,
+      binding = binding,
+      uniform = uniform_name(config),
+      constant = config.constant_name,
+      param_type = config.param_type,
+    )
+  }
+
+  fn entry_point_wrapper(&self, config: &EnhanceConfig, input_fields: &[StructField]) -> String {
+    let input_conversion = if has_bool_field(input_fields) {
+      let converted_fields = input_fields
+        .iter()
+        .map(|field| {
+          let access = format!("{}.{}", uniform_name(config), field.name);
+          if field.ty == "bool" {
+            format!("{} != 0u", access)
+          } else {
+            access
+          }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+      format!(
+        "  {} = {}({});\n",
+        config.constant_name, config.param_type, converted_fields
+      )
+    } else {
+      String::new()
+    };
+    format!(
+      "// The fragment shader\n\
+       @fragment\n\
+       fn fragment_shader(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {{\n\
+       {input_conversion}  \
+         return {entry_point}(position.xy);\n\
+       }}\n",
+      input_conversion = input_conversion,
+      entry_point = config.entry_point_fn_name,
+    )
+  }
+
+  fn prelude(&self) -> String {
+    let constants = MATH_CONSTANTS
+      .iter()
+      .map(|(name, value)| format!("const {}: f32 = {:?};\n", name, value));
+    let squared = (2..=4).map(|width| {
+      format!(
+        "fn length_squared{width}(x: vec{width}<f32>) -> f32 {{ return dot(x, x); }}\n\
+         fn distance_squared{width}(x: vec{width}<f32>, y: vec{width}<f32>) -> f32 \
+           {{ return dot(x - y, x - y); }}\n",
+        width = width
+      )
+    });
+    constants
+      .chain(std::iter::once("\n".to_owned()))
+      .chain(squared)
+      .chain(std::iter::once("\n".to_owned()))
+      .collect()
+  }
+
+  fn function_signature(&self, name: &str, params: &[String], return_type: Option<&str>) -> String {
+    format!(
+      "fn {}({}){}",
+      name,
+      params.join(", "),
+      return_type
+        .map(|return_type| format!(" -> {}", return_type))
+        .unwrap_or_default()
+    )
+  }
+
+  fn parameter(&self, name: &str, ty: &str, _address_space: Option<&str>) -> String {
+    format!("{}: {}", name, ty)
+  }
+
+  fn mutable_parameter_copy(&self, name: &str, param_name: &str, ty: &str) -> Option<String> {
+    Some(format!("var {}: {} = {};", name, ty, param_name))
+  }
+
+  fn local_declaration(&self, name: &str, ty: Option<&str>, is_mutable: bool) -> String {
+    format!(
+      "{} {}{}",
+      if is_mutable { "var" } else { "let" },
+      name,
+      ty.map(|ty| format!(": {}", ty)).unwrap_or_default()
+    )
+  }
+
+  fn for_range(
+    &self,
+    var: &str,
+    ty: Option<&str>,
+    from: &str,
+    to: &str,
+    is_inclusive: bool,
+  ) -> String {
+    format!(
+      "for ({declaration} = {from}; {var} <{limit} {to}; {var}++)",
+      declaration = self.local_declaration(var, ty, true),
+      var = var,
+      from = from,
+      to = to,
+      limit = if is_inclusive { "=" } else { "" },
+    )
+  }
+
+  // Identifiers starting with `__` are reserved in WGSL
+  fn temporary_variable(&self, id: u64) -> String {
+    format!("_var_{}", id)
+  }
+
+  fn supports_swizzle_assignment(&self) -> bool {
+    false
+  }
+
+  fn has_implicit_vector_splat(&self) -> bool {
+    false
+  }
+
+  fn has_function_overloading(&self) -> bool {
+    false
+  }
+
+  fn needs_forward_declarations(&self) -> bool {
+    false
+  }
+
+  fn struct_field(&self, name: &str, ty: &str) -> String {
+    format!("{}: {},", name, ty)
+  }
+
+  fn struct_end(&self) -> String {
+    "}".to_owned()
+  }
+
+  fn ternary(&self, condition: &str, then: &str, otherwise: &str) -> String {
+    format!("select({}, {}, {})", otherwise, then, condition)
+  }
+}
+
+fn has_bool_field(fields: &[StructField]) -> bool {
+  fields.iter().any(|field| field.ty == "bool")
+}
+
+fn uniform_type_name(config: &EnhanceConfig) -> String {
+  format!("{}Uniform", config.param_type)
+}
+
+fn uniform_name(config: &EnhanceConfig) -> String {
+  format!("{}_UNIFORM", config.constant_name)
+}
+
+#[test]
+fn test_examples_validate_as_wgsl() {
+  let config = EnhanceConfig {
+    entry_point_fn_name: "pixel_color".to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  };
+  let interface =
+    include_str!("../../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs");
+  // Examples go first, because of their inner attributes
+  for example in &[
+    include_str!("../../examples/simple/simple.rs").to_owned(),
+    include_str!("../../examples/mandelbrot/mandelbrot.rs").to_owned(),
+    include_str!("../../examples/two_dimensional_sdf/two_dimensional_sdf.rs").to_owned(),
+    format!(
+      "{}{}",
+      include_str!("../../examples/raymarching_eyes/raymarching_eyes.rs"),
+      include_str!("../../examples/raymarching_eyes/sdf_utils.rs")
+    ),
+    "pub fn pixel_color(coordinates: Vec2) -> Vec4 {
+      let mut p = (coordinates, 1.0).vec3();
+      p.set_xz(INPUT.window_size);
+      *p.zy_mut() *= 2.0;
+      (p, 1.0).vec4()
+    }"
+    .to_owned(),
+    "pub fn pixel_color(coordinates: Vec2) -> Vec4 {
+      let p = (coordinates, 1.0).vec3();
+      let q = (p, 1.0).vec4();
+      let d = coordinates.magnitude_squared() + coordinates.distance_squared(INPUT.window_size);
+      let e = p.magnitude_squared() * p.distance_squared((1.0).vec3());
+      (d, e, q.magnitude_squared(), q.distance_squared(q.wzyx())).vec4()
+    }"
+    .to_owned(),
+  ] {
+    let wgsl =
+      crate::transpile_with_backend(&format!("{}{}", example, interface), &config, &WgslBackend)
+        .unwrap();
+    let formatted = crate::format_wgsl(&wgsl);
+    assert_eq!(crate::format_wgsl(&formatted), formatted);
+    for wgsl in [&wgsl, &formatted] {
+      let module = naga::front::wgsl::parse_str(wgsl)
+        .unwrap_or_else(|error| panic!("{}\n{}", error.emit_to_string(wgsl), wgsl));
+      naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+      )
+      .validate(&module)
+      .unwrap_or_else(|error| panic!("{}\n{}", error.emit_to_string(wgsl), wgsl));
+    }
+  }
+}
@@ -0,0 +1,427 @@
+//! The commands typed into the terminal while the window is open, or piped
+//! to stdin from a file, one per line. `control_server` receives the same
+//! commands as JSON.
+
+use super::shader_file_path_arg;
+use anyhow::{anyhow, bail, Result};
+
+/// In JSON `{"command": "seek", "args": 12.5}`, `{"command": "pause"}`.
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(tag = "command", content = "args", rename_all = "snake_case")]
+pub enum CLICommand {
+  Pause,
+  Restart,
+  Run(String),
+  Seek(f64),
+  Speed(f64),
+  Step(i64),
+  Loop(Option<(f64, f64)>),
+  Screenshot(String),
+  Status,
+  Msl,
+  Quit,
+}
+
+impl CLICommand {
+  /// Checks the arguments the types don't, for commands from both
+  /// the terminal and `control_server`.
+  pub fn check(&self) -> Result<()> {
+    if let CLICommand::Loop(Some((start, end))) = self {
+      if start >= end {
+        bail!("The loop needs to start before it ends");
+      }
+    }
+    Ok(())
+  }
+}
+
+pub struct Request {
+  pub command: CLICommand,
+  pub responder: Responder,
+}
+
+/// Where the result of a command goes.
+pub enum Responder {
+  Terminal,
+  Client(std::sync::mpsc::Sender<Result<Reply, String>>),
+}
+
+impl Responder {
+  pub fn send(self, result: Result<Reply, String>) {
+    match self {
+      Responder::Terminal => {
+        match result {
+          Ok(Reply::Done) => return,
+          Ok(Reply::Time(time_secs)) => println!("{:.3}s", time_secs),
+          Ok(Reply::Screenshot(path)) => println!("Saved {:?}", path),
+          Ok(Reply::Status(status)) => println!("{}", status),
+          Ok(Reply::Msl(msl)) => println!("{}", msl),
+          Err(error) => println!("{}", error),
+        }
+        CLICommandsWatcher::print_prompt();
+      }
+      // The client might have disconnected in the meantime
+      Responder::Client(sender) => {
+        let _ = sender.send(result);
+      }
+    }
+  }
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Reply {
+  Done,
+  /// The shader's time after the command.
+  Time(f64),
+  Screenshot(std::path::PathBuf),
+  Status(CompileStatus),
+  Msl(String),
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CompileStatus {
+  Compiling {
+    path: std::path::PathBuf,
+  },
+  Compiled {
+    path: std::path::PathBuf,
+  },
+  Failed {
+    path: std::path::PathBuf,
+    error: String,
+  },
+}
+
+impl std::fmt::Display for CompileStatus {
+  fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      CompileStatus::Compiling { path } => write!(formatter, "Compiling {:?}", path),
+      CompileStatus::Compiled { path } => write!(formatter, "Compiled {:?}", path),
+      CompileStatus::Failed { path, error } => {
+        write!(formatter, "Failed to compile {:?}\n{}", path, error)
+      }
+    }
+  }
+}
+
+// Handled by the thread reading the commands, not sent to the window
+#[derive(Debug, PartialEq)]
+enum Line {
+  Command(CLICommand),
+  Help,
+  Wait(f64),
+  Empty,
+}
+
+// The usage and description of each command, for `help`
+const HELP: &[(&str, &str)] = &[
+  ("p, pause", "Pauses or resumes"),
+  ("r, restart", "Restarts from 0"),
+  (
+    "r, run <path>",
+    "Runs another shader, a file, a crate directory or an example name",
+  ),
+  ("seek <secs>", "Jumps to the time"),
+  (
+    "speed <factor>",
+    "Changes the playback speed, negative plays backwards",
+  ),
+  (
+    "step [<frames>]",
+    "Pauses and moves one or more frames, backwards when negative",
+  ),
+  (
+    "loop [<start secs> <end secs>]",
+    "Plays the range repeatedly, or the whole timeline without a range",
+  ),
+  ("screenshot <path>", "Saves the next frame as a PNG"),
+  ("status", "Prints whether the shader compiled"),
+  ("msl", "Prints the last generated Metal Shading Language"),
+  (
+    "wait <secs>",
+    "Waits before reading the next command, for scripts piped to stdin",
+  ),
+  ("help", "Prints this list"),
+  ("q, quit", "Closes the window"),
+];
+
+// Completed at the start of the line
+const COMMAND_NAMES: &[&str] = &[
+  "pause",
+  "restart",
+  "run",
+  "seek",
+  "speed",
+  "step",
+  "loop",
+  "screenshot",
+  "status",
+  "msl",
+  "wait",
+  "help",
+  "quit",
+];
+
+fn print_help() {
+  let width = HELP.iter().map(|(usage, _)| usage.len()).max().unwrap_or(0);
+  for (usage, description) in HELP {
+    println!("  {:width$}  {}", usage, description, width = width);
+  }
+  println!("Quote paths with spaces, lines starting with # are ignored");
+}
+
+/// Splits the line at whitespace, except inside `'...'` or `"..."`.
+/// A backslash escapes the next character, except inside single quotes.
+fn tokenize(line: &str) -> Result<Vec<String>> {
+  let mut tokens = Vec::new();
+  let mut token: Option<String> = None;
+  let mut quote = None;
+  let mut chars = line.chars();
+  while let Some(char) = chars.next() {
+    match (quote, char) {
+      (Some(quote_char), _) if char == quote_char => quote = None,
+      (None | Some('"'), '\\') => {
+        let escaped = chars
+          .next()
+          .ok_or_else(|| anyhow!("Nothing to escape at the end of the line"))?;
+        token.get_or_insert_with(String::new).push(escaped);
+      }
+      (None, '\'' | '"') => {
+        quote = Some(char);
+        // Quotes can delimit an empty token
+        token.get_or_insert_with(String::new);
+      }
+      (None, _) if char.is_whitespace() => tokens.extend(token.take()),
+      _ => token.get_or_insert_with(String::new).push(char),
+    }
+  }
+  if let Some(quote_char) = quote {
+    bail!("Missing closing {}", quote_char);
+  }
+  tokens.extend(token);
+  Ok(tokens)
+}
+
+fn parse_line(line: &str) -> Result<Line> {
+  if line.trim_start().starts_with('#') {
+    return Ok(Line::Empty);
+  }
+  let tokens = tokenize(line)?;
+  let arguments: Vec<&str> = tokens.iter().map(String::as_str).collect();
+  let (name, arguments) = match arguments.split_first() {
+    Some((name, arguments)) => (*name, arguments),
+    None => return Ok(Line::Empty),
+  };
+  let number = |index: usize| -> Result<f64> {
+    let argument = arguments[index];
+    argument
+      .parse()
+      .map_err(|_| anyhow!("Invalid number `{}` for `{}`", argument, name))
+  };
+  let command = match (name, arguments.len()) {
+    ("p" | "pause", 0) => CLICommand::Pause,
+    ("r" | "restart", 0) => CLICommand::Restart,
+    ("r" | "run", 1) => CLICommand::Run(arguments[0].to_owned()),
+    ("seek", 1) => CLICommand::Seek(number(0)?),
+    ("speed", 1) => CLICommand::Speed(number(0)?),
+    ("step", 0) => CLICommand::Step(1),
+    ("step", 1) => CLICommand::Step(
+      arguments[0]
+        .parse()
+        .map_err(|_| anyhow!("Invalid frame count `{}` for `step`", arguments[0]))?,
+    ),
+    ("loop", 0) => CLICommand::Loop(None),
+    ("loop", 2) => {
+      let command = CLICommand::Loop(Some((number(0)?, number(1)?)));
+      command.check()?;
+      command
+    }
+    ("screenshot", 1) => CLICommand::Screenshot(arguments[0].to_owned()),
+    ("status", 0) => CLICommand::Status,
+    ("msl", 0) => CLICommand::Msl,
+    ("q" | "quit", 0) => CLICommand::Quit,
+    ("wait", 1) => return Ok(Line::Wait(number(0)?)),
+    ("help", _) => return Ok(Line::Help),
+    // The last usage, so that `r` with arguments is explained as `run`
+    _ => match HELP
+      .iter()
+      .rev()
+      .find(|(usage, _)| usage.split(&[',', ' '][..]).any(|word| word == name))
+    {
+      Some((usage, _)) if name == "r" || name == "run" => {
+        bail!("Usage: {}, quote paths with spaces", usage)
+      }
+      Some((usage, _)) => bail!("Usage: {}", usage),
+      None => bail!("Unknown command `{}`, type `help` for the list", name),
+    },
+  };
+  Ok(Line::Command(command))
+}
+
+pub struct CLICommandsWatcher {
+  #[allow(dead_code)] // Required to avoid dropping child
+  watcher_thread: std::thread::JoinHandle<()>,
+}
+
+impl CLICommandsWatcher {
+  pub fn new(tx: std::sync::mpsc::Sender<Request>) -> Self {
+    let watcher_thread = std::thread::spawn(move || {
+      let mut editor = rustyline::Editor::<CompletionHelper>::new();
+      editor.set_helper(Some(CompletionHelper {
+        filename_completer: rustyline::completion::FilenameCompleter::new(),
+      }));
+      let history_path = std::env::var_os("HOME")
+        .map(|home| std::path::Path::new(&home).join(".shader_roy_history"));
+      if let Some(history_path) = &history_path {
+        // There is no history on the first run
+        let _ = editor.load_history(history_path);
+      }
+      loop {
+        let line = match editor.readline("> ") {
+          Ok(line) => line,
+          // Ctrl-C in the terminal
+          Err(rustyline::error::ReadlineError::Interrupted) => {
+            let _ = tx.send(Request {
+              command: CLICommand::Quit,
+              responder: Responder::Terminal,
+            });
+            break;
+          }
+          // stdin was closed, or can't be read
+          Err(_) => break,
+        };
+        if !line.trim().is_empty() {
+          editor.add_history_entry(line.as_str());
+          if let Some(history_path) = &history_path {
+            let _ = editor.save_history(history_path);
+          }
+        }
+        match parse_line(&line) {
+          Ok(Line::Command(command)) => {
+            let request = Request {
+              command,
+              responder: Responder::Terminal,
+            };
+            if tx.send(request).is_err() {
+              // The window was closed
+              break;
+            }
+          }
+          Ok(Line::Help) => print_help(),
+          Ok(Line::Wait(secs)) => {
+            std::thread::sleep(std::time::Duration::from_secs_f64(secs.max(0.0)))
+          }
+          Ok(Line::Empty) => {}
+          Err(error) => println!("{}", error),
+        }
+      }
+    });
+
+    CLICommandsWatcher { watcher_thread }
+  }
+
+  pub fn print_instructions() {
+    println!("[p] to pause, [r] to restart, [r <path>] to run another shader, [help] for more");
+    Self::print_prompt();
+  }
+
+  pub fn print_prompt() {
+    print!("> ");
+    use std::io::Write;
+    let _ = std::io::stdout().flush();
+  }
+}
+
+// Completes command names, and shader paths and example names for `run`
+struct CompletionHelper {
+  filename_completer: rustyline::completion::FilenameCompleter,
+}
+
+impl rustyline::completion::Completer for CompletionHelper {
+  type Candidate = rustyline::completion::Pair;
+
+  fn complete(
+    &self,
+    line: &str,
+    pos: usize,
+    context: &rustyline::Context<'_>,
+  ) -> rustyline::Result<(usize, Vec<Self::Candidate>)> {
+    let line_before_cursor = &line[..pos];
+    let pair = |name: &str| rustyline::completion::Pair {
+      display: name.to_owned(),
+      replacement: name.to_owned(),
+    };
+    match line_before_cursor.split_once(char::is_whitespace) {
+      None => Ok((
+        0,
+        COMMAND_NAMES
+          .iter()
+          .filter(|name| name.starts_with(line_before_cursor))
+          .map(|name| pair(name))
+          .collect(),
+      )),
+      Some(("r" | "run", _)) => {
+        let (start, mut candidates) = self.filename_completer.complete(line, pos, context)?;
+        let prefix = &line[start..pos];
+        let mut example_names = shader_file_path_arg::example_names();
+        example_names.retain(|name| name.starts_with(prefix));
+        candidates.extend(example_names.iter().map(|name| pair(name)));
+        Ok((start, candidates))
+      }
+      Some(_) => Ok((pos, Vec::new())),
+    }
+  }
+}
+
+impl rustyline::hint::Hinter for CompletionHelper {
+  type Hint = String;
+}
+
+impl rustyline::highlight::Highlighter for CompletionHelper {}
+
+impl rustyline::validate::Validator for CompletionHelper {}
+
+impl rustyline::Helper for CompletionHelper {}
+
+#[test]
+fn test_parse_line() {
+  assert_eq!(
+    tokenize(r#"r "my dir/a.rs" 'it''s' b\ c"#).unwrap(),
+    vec!["r", "my dir/a.rs", "its", "b c"]
+  );
+  assert_eq!(tokenize(r#"r """#).unwrap(), vec!["r", ""]);
+  assert!(tokenize("r \"a").is_err());
+
+  assert_eq!(parse_line("p").unwrap(), Line::Command(CLICommand::Pause));
+  assert_eq!(
+    parse_line("  r 'my dir/a.rs'\n").unwrap(),
+    Line::Command(CLICommand::Run("my dir/a.rs".to_owned()))
+  );
+  assert_eq!(
+    parse_line("speed -0.25").unwrap(),
+    Line::Command(CLICommand::Speed(-0.25))
+  );
+  assert_eq!(
+    parse_line("step -1").unwrap(),
+    Line::Command(CLICommand::Step(-1))
+  );
+  assert_eq!(
+    parse_line("loop 1 2.5").unwrap(),
+    Line::Command(CLICommand::Loop(Some((1.0, 2.5))))
+  );
+  assert_eq!(parse_line("wait 0.5").unwrap(), Line::Wait(0.5));
+  assert_eq!(parse_line("").unwrap(), Line::Empty);
+  assert_eq!(parse_line("# seek 1").unwrap(), Line::Empty);
+
+  let error = |line| parse_line(line).unwrap_err().to_string();
+  assert_eq!(error("seek"), "Usage: seek <secs>");
+  assert_eq!(error("seek x"), "Invalid number `x` for `seek`");
+  assert_eq!(error("loop 2 1"), "The loop needs to start before it ends");
+  assert_eq!(
+    error("r my dir"),
+    "Usage: r, run <path>, quote paths with spaces"
+  );
+  assert_eq!(error("x"), "Unknown command `x`, type `help` for the list");
+}
@@ -0,0 +1,159 @@
+//! The source of time for the window's `INPUT`. The wall clock follows real
+//! time, the others make the frames reproducible.
+
+use anyhow::{anyhow, bail, Result};
+
+pub trait Clock {
+  /// Seconds since the clock was started or restarted.
+  fn now_secs(&self) -> f64;
+  fn restart(&mut self);
+  /// Called after each rendered frame.
+  fn finish_frame(&mut self) {}
+  /// Year, month, day and the local time zone's offset from UTC in seconds.
+  fn date(&self) -> [f32; 4];
+}
+
+pub struct WallClock {
+  start_time: std::time::Instant,
+}
+
+impl WallClock {
+  pub fn new() -> Self {
+    Self {
+      start_time: std::time::Instant::now(),
+    }
+  }
+}
+
+impl Clock for WallClock {
+  fn now_secs(&self) -> f64 {
+    self.start_time.elapsed().as_secs_f64()
+  }
+
+  fn restart(&mut self) {
+    self.start_time = std::time::Instant::now();
+  }
+
+  fn date(&self) -> [f32; 4] {
+    let today = chrono::prelude::Local::now();
+    use chrono::Datelike;
+    [
+      today.year() as f32,
+      today.month() as f32,
+      today.day() as f32,
+      today.offset().local_minus_utc() as f32,
+    ]
+  }
+}
+
+/// Advances by `1 / frames_per_sec` with each frame, however long it took.
+pub struct FixedStepClock {
+  frames_per_sec: f64,
+  frame_index: u64,
+}
+
+impl FixedStepClock {
+  pub fn new(frames_per_sec: f64) -> Self {
+    Self {
+      frames_per_sec,
+      frame_index: 0,
+    }
+  }
+}
+
+impl Clock for FixedStepClock {
+  fn now_secs(&self) -> f64 {
+    // Computed from the index, so that the error doesn't accumulate
+    self.frame_index as f64 / self.frames_per_sec
+  }
+
+  fn restart(&mut self) {
+    self.frame_index = 0;
+  }
+
+  fn finish_frame(&mut self) {
+    self.frame_index += 1;
+  }
+
+  // Like the offscreen renderers, which don't have a date either
+  fn date(&self) -> [f32; 4] {
+    [0.0; 4]
+  }
+}
+
+/// Stays at the given time, also when restarted.
+pub struct ManualClock {
+  time_secs: f64,
+}
+
+impl ManualClock {
+  pub fn new(time_secs: f64) -> Self {
+    Self { time_secs }
+  }
+}
+
+impl Clock for ManualClock {
+  fn now_secs(&self) -> f64 {
+    self.time_secs
+  }
+
+  fn restart(&mut self) {}
+
+  fn date(&self) -> [f32; 4] {
+    [0.0; 4]
+  }
+}
+
+pub const USAGE: &str = "wall, fixed[:<frames per sec>] or manual[:<secs>]";
+
+/// Parses `wall`, `fixed`, `fixed:30`, `manual` or `manual:2.5`.
+pub fn parse(spec: &str) -> Result<Box<dyn Clock>> {
+  let (name, value) = match spec.split_once(':') {
+    Some((name, value)) => (name, Some(value)),
+    None => (spec, None),
+  };
+  let number = |default: f64| -> Result<f64> {
+    value.map_or(Ok(default), |value| {
+      value
+        .parse()
+        .map_err(|_| anyhow!("Invalid number `{}` in clock `{}`", value, spec))
+    })
+  };
+  Ok(match name {
+    "wall" if value.is_none() => Box::new(WallClock::new()),
+    "fixed" => {
+      let frames_per_sec = number(60.0)?;
+      if frames_per_sec <= 0.0 {
+        bail!("The frame rate of clock `{}` must be positive", spec);
+      }
+      Box::new(FixedStepClock::new(frames_per_sec))
+    }
+    "manual" => Box::new(ManualClock::new(number(0.0)?)),
+    _ => bail!("Unknown clock `{}`, expected {}", spec, USAGE),
+  })
+}
+
+#[test]
+fn test_clocks_are_reproducible() {
+  let mut clock = parse("fixed:4").unwrap();
+  let mut times = Vec::new();
+  for _ in 0..3 {
+    times.push(clock.now_secs());
+    // Reading the time doesn't advance it
+    assert_eq!(clock.now_secs(), times[times.len() - 1]);
+    clock.finish_frame();
+  }
+  assert_eq!(times, vec![0.0, 0.25, 0.5]);
+  assert_eq!(clock.date(), [0.0; 4]);
+  clock.restart();
+  assert_eq!(clock.now_secs(), 0.0);
+
+  let mut clock = ManualClock::new(2.5);
+  clock.finish_frame();
+  assert_eq!(clock.now_secs(), 2.5);
+  clock.restart();
+  assert_eq!(clock.now_secs(), 2.5);
+
+  assert!(parse("fixed:0").is_err());
+  assert!(parse("wall:1").is_err());
+}
@@ -0,0 +1,126 @@
+//! Compiles shaders on a background thread, so that the window keeps
+//! rendering the previous shader meanwhile. Each request supersedes the
+//! earlier ones, cancelling the compile in progress at its next phase.
+
+use super::metal_renderer::prepare_pipeline_state;
+use super::phase_timings::PhaseTimings;
+use super::shader_compiler;
+use anyhow::{anyhow, bail};
+use metal::*;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+
+pub struct CompileResult {
+  // Also for shaders which Metal failed to compile
+  pub msl: Option<String>,
+  pub pipeline_state: Result<RenderPipelineState, String>,
+  pub timings: PhaseTimings,
+  // The files the shader was compiled from, empty when the compiler crashed
+  pub source_files: Vec<std::path::PathBuf>,
+}
+
+pub struct CompileWorker {
+  jobs: mpsc::Sender<(u64, std::path::PathBuf)>,
+  results: mpsc::Receiver<(u64, CompileResult)>,
+  // The number of the newest request
+  latest_job: Arc<AtomicU64>,
+}
+
+impl CompileWorker {
+  /// Compiles pipelines which render in `pixel_format`.
+  pub fn new(device: &Device, pixel_format: MTLPixelFormat) -> Self {
+    let (jobs, job_receiver) = mpsc::channel::<(u64, std::path::PathBuf)>();
+    let (result_sender, results) = mpsc::channel();
+    let latest_job = Arc::new(AtomicU64::new(0));
+    let device = device.clone();
+    let worker_latest_job = latest_job.clone();
+    std::thread::spawn(move || {
+      while let Ok(job) = job_receiver.recv() {
+        // Skips requests which were already superseded
+        let (job_number, path) = job_receiver.try_iter().last().unwrap_or(job);
+        let is_cancelled = || worker_latest_job.load(Ordering::SeqCst) != job_number;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+          compile(&device, pixel_format, &path, &is_cancelled)
+        }))
+        .unwrap_or_else(|_| CompileResult {
+          msl: None,
+          pipeline_state: Err("The shader compiler crashed".to_owned()),
+          timings: PhaseTimings::default(),
+          source_files: Vec::new(),
+        });
+        if is_cancelled() {
+          continue;
+        }
+        if result_sender.send((job_number, result)).is_err() {
+          // The window was closed
+          return;
+        }
+      }
+    });
+    Self {
+      jobs,
+      results,
+      latest_job,
+    }
+  }
+
+  /// Compiles the shader at `path`, cancelling the compile in progress.
+  pub fn compile(&self, path: &std::path::Path) {
+    let job_number = self.latest_job.fetch_add(1, Ordering::SeqCst) + 1;
+    let _ = self.jobs.send((job_number, path.to_owned()));
+  }
+
+  /// The result of the newest request, once it's done.
+  pub fn try_recv(&self) -> Option<CompileResult> {
+    let latest_job = self.latest_job.load(Ordering::SeqCst);
+    self
+      .results
+      .try_iter()
+      .filter(|(job_number, _)| *job_number == latest_job)
+      .last()
+      .map(|(_, result)| result)
+  }
+}
+
+fn compile(
+  device: &Device,
+  pixel_format: MTLPixelFormat,
+  path: &std::path::Path,
+  is_cancelled: &dyn Fn() -> bool,
+) -> CompileResult {
+  let mut msl = None;
+  let mut timings = PhaseTimings::default();
+  let mut source_files = Vec::new();
+  let mut after_phase = |phase: &'static str, duration: std::time::Duration| {
+    timings.add(phase, duration);
+    if is_cancelled() {
+      bail!("Cancelled by a newer compile");
+    }
+    Ok(())
+  };
+  let pipeline_state =
+    shader_compiler::generate_msl_with_phases(path, &mut after_phase, &mut source_files)
+      .and_then(|shader_msl| {
+        msl = Some(rust_to_metal_sl::format_msl(&shader_msl.fragment_shader));
+        let backend_start_time = std::time::Instant::now();
+        let library = shader_compiler::compile_msl(device, &shader_msl.combined)?;
+        let pipeline_state = prepare_pipeline_state(
+          device,
+          &library,
+          "vertex_shader",
+          "fragment_shader",
+          pixel_format,
+        )
+        .map_err(|error| anyhow!("{}", error))?;
+        // Fails when a newer compile was requested meanwhile
+        after_phase("backend compile", backend_start_time.elapsed())?;
+        Ok(pipeline_state)
+      })
+      .map_err(|error| format!("{:?}", error));
+  CompileResult {
+    msl,
+    pipeline_state,
+    timings,
+    source_files,
+  }
+}
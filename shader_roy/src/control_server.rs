@@ -0,0 +1,227 @@
+//! Lets editors and scripts drive the window over a local socket, with one
+//! JSON message per line.
+//!
+//! Requests are the commands of `cli_commands`, with an optional `id` which
+//! is echoed in the response:
+//!   {"id": 1, "command": "seek", "args": 12.5}
+//!   {"id": 1, "result": {"time": 12.5}}
+//!   {"id": 2, "command": "run", "args": "/path/to/shader"}
+//!   {"id": 2, "error": "Cannot start ShaderRoy: ..."}
+//! Every client also receives compile status events:
+//!   {"event": "compile_status", "status": "failed", "path": "...", "error": "..."}
+
+use super::cli_commands::{CLICommand, CompileStatus, Request, Responder};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::io::{BufRead, Write};
+use std::sync::{mpsc, Arc, Mutex};
+
+type Client = Arc<Mutex<Box<dyn Write + Send>>>;
+
+pub struct ControlServer {
+  clients: Arc<Mutex<Vec<Client>>>,
+}
+
+impl ControlServer {
+  /// Listens on TCP for `<host>:<port>` addresses, on a Unix domain socket
+  /// for paths.
+  pub fn start(address: &str, requests: mpsc::Sender<Request>) -> Result<Self> {
+    let clients = Arc::new(Mutex::new(Vec::new()));
+    if let Ok(socket_address) = address.parse::<std::net::SocketAddr>() {
+      let listener = std::net::TcpListener::bind(socket_address)
+        .with_context(|| format!("Failed to listen on {}", address))?;
+      accept_clients(
+        move || listener.accept().map(|(stream, _)| stream),
+        requests,
+        clients.clone(),
+      );
+    } else {
+      start_unix_socket(address, requests, clients.clone())?;
+    }
+    println!("Listening for control commands on {}", address);
+    Ok(ControlServer { clients })
+  }
+
+  pub fn broadcast_compile_status(&self, status: &CompileStatus) {
+    let mut message = serde_json::to_value(status).unwrap();
+    message["event"] = "compile_status".into();
+    // Disconnected clients fail to write and are dropped
+    self
+      .clients
+      .lock()
+      .unwrap()
+      .retain(|client| write_message(client, &message).is_ok());
+  }
+}
+
+#[cfg(unix)]
+fn start_unix_socket(
+  path: &str,
+  requests: mpsc::Sender<Request>,
+  clients: Arc<Mutex<Vec<Client>>>,
+) -> Result<()> {
+  // Left behind by a previous run, other files are kept
+  if let Ok(metadata) = std::fs::symlink_metadata(path) {
+    use std::os::unix::fs::FileTypeExt;
+    if !metadata.file_type().is_socket() {
+      anyhow::bail!(
+        "Cannot listen on {:?}, it already exists and is not a socket",
+        path
+      );
+    }
+    std::fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+  }
+  let listener = std::os::unix::net::UnixListener::bind(path)
+    .with_context(|| format!("Failed to listen on {:?}", path))?;
+  accept_clients(
+    move || listener.accept().map(|(stream, _)| stream),
+    requests,
+    clients,
+  );
+  Ok(())
+}
+
+#[cfg(not(unix))]
+fn start_unix_socket(
+  path: &str,
+  _requests: mpsc::Sender<Request>,
+  _clients: Arc<Mutex<Vec<Client>>>,
+) -> Result<()> {
+  anyhow::bail!(
+    "Unix domain sockets are not supported, use a TCP address like 127.0.0.1:7878 instead of {:?}",
+    path
+  )
+}
+
+trait Stream: std::io::Read + Write + Send + Sized + 'static {
+  fn try_clone(&self) -> std::io::Result<Self>;
+}
+
+impl Stream for std::net::TcpStream {
+  fn try_clone(&self) -> std::io::Result<Self> {
+    std::net::TcpStream::try_clone(self)
+  }
+}
+
+#[cfg(unix)]
+impl Stream for std::os::unix::net::UnixStream {
+  fn try_clone(&self) -> std::io::Result<Self> {
+    std::os::unix::net::UnixStream::try_clone(self)
+  }
+}
+
+fn accept_clients<S: Stream>(
+  mut accept: impl FnMut() -> std::io::Result<S> + Send + 'static,
+  requests: mpsc::Sender<Request>,
+  clients: Arc<Mutex<Vec<Client>>>,
+) {
+  std::thread::spawn(move || loop {
+    let (stream, writer) = match accept().and_then(|stream| {
+      let writer = stream.try_clone()?;
+      Ok((stream, writer))
+    }) {
+      Ok(streams) => streams,
+      // The client disconnected before it was accepted
+      Err(_) => continue,
+    };
+    let client: Client = Arc::new(Mutex::new(Box::new(writer)));
+    clients.lock().unwrap().push(client.clone());
+    let requests = requests.clone();
+    std::thread::spawn(move || serve_client(stream, client, requests));
+  });
+}
+
+// Handles the client's requests one at a time
+fn serve_client<S: Stream>(stream: S, client: Client, requests: mpsc::Sender<Request>) {
+  for line in std::io::BufReader::new(stream).lines() {
+    let line = match line {
+      Ok(line) => line,
+      Err(_) => return,
+    };
+    if line.trim().is_empty() {
+      continue;
+    }
+    let (id, result) = handle_request(&line, &requests);
+    let mut response = match result {
+      Ok(result) => serde_json::json!({ "result": result }),
+      Err(error) => serde_json::json!({ "error": error }),
+    };
+    if let Some(id) = id {
+      response["id"] = id;
+    }
+    if write_message(&client, &response).is_err() {
+      return;
+    }
+  }
+}
+
+fn handle_request(
+  line: &str,
+  requests: &mpsc::Sender<Request>,
+) -> (Option<serde_json::Value>, Result<serde_json::Value, String>) {
+  let message: serde_json::Value = match serde_json::from_str(line) {
+    Ok(message) => message,
+    Err(error) => return (None, Err(format!("Invalid JSON: {}", error))),
+  };
+  let id = message.get("id").cloned();
+  let command = match CLICommand::deserialize(&message) {
+    Ok(command) => command,
+    Err(error) => return (id, Err(format!("Invalid command: {}", error))),
+  };
+  if let Err(error) = command.check() {
+    return (id, Err(format!("Invalid command: {}", error)));
+  }
+  let (sender, receiver) = mpsc::channel();
+  let request = Request {
+    command,
+    responder: Responder::Client(sender),
+  };
+  let result = if requests.send(request).is_err() {
+    Err("The window was closed".to_owned())
+  } else {
+    receiver
+      .recv()
+      .unwrap_or_else(|_| Err("The window was closed".to_owned()))
+      .map(|reply| serde_json::to_value(reply).unwrap())
+  };
+  (id, result)
+}
+
+fn write_message(client: &Client, message: &serde_json::Value) -> std::io::Result<()> {
+  let mut writer = client.lock().unwrap();
+  writeln!(writer, "{}", message)?;
+  writer.flush()
+}
+
+#[test]
+fn test_handle_request() {
+  let (sender, receiver) = mpsc::channel::<Request>();
+  let window = std::thread::spawn(move || {
+    for request in receiver {
+      let reply = match request.command {
+        CLICommand::Seek(time_secs) => Ok(super::cli_commands::Reply::Time(time_secs)),
+        command => Err(format!("Unexpected {:?}", command)),
+      };
+      request.responder.send(reply);
+    }
+  });
+  let (id, result) = handle_request(r#"{"id": 7, "command": "seek", "args": 1.5}"#, &sender);
+  assert_eq!(id, Some(serde_json::json!(7)));
+  assert_eq!(result, Ok(serde_json::json!({ "time": 1.5 })));
+  let (_, result) = handle_request(r#"{"command": "pause"}"#, &sender);
+  assert_eq!(result, Err("Unexpected Pause".to_owned()));
+  let (_, result) = handle_request(r#"{"command": "seek"}"#, &sender);
+  assert!(result.unwrap_err().starts_with("Invalid command"));
+  for args in ["[2, 2]", "[2, 1]"] {
+    let (_, result) = handle_request(
+      &format!(r#"{{"command": "loop", "args": {}}}"#, args),
+      &sender,
+    );
+    assert_eq!(
+      result,
+      Err("Invalid command: The loop needs to start before it ends".to_owned())
+    );
+  }
+  drop(sender);
+  window.join().unwrap();
+}
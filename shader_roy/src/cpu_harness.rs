@@ -0,0 +1,115 @@
+//! The `main.rs` of the harness generated by `cpu_renderer.rs`, which runs
+//! the `pixel_color` of the `shader` dependency on the CPU.
+//!
+//! Usage:
+//!   shader_roy_cpu_harness png|png_srgb <width> <height>
+//!     Reads `<elapsed time secs> <time since last frame secs> <frame count> <output.png>`
+//!     lines from stdin and renders each frame to the PNG, encoding the
+//!     colors as sRGB for `png_srgb`.
+//!   shader_roy_cpu_harness samples <width> <height>
+//!     Reads `<x> <y> <elapsed time secs>` lines from stdin and prints
+//!     the `<r> <g> <b> <a>` color of each.
+
+use rayon::prelude::*;
+use shader_roy_metal_sl_interface::{with_input, Input, Vec2};
+use std::io::BufRead;
+
+fn main() {
+  let args: Vec<String> = std::env::args().skip(1).collect();
+  let width: usize = args[1].parse().expect("Invalid width");
+  let height: usize = args[2].parse().expect("Invalid height");
+  match args[0].as_str() {
+    "png" => render_pngs(width, height, false),
+    "png_srgb" => render_pngs(width, height, true),
+    "samples" => print_samples(width, height),
+    mode => panic!("Unknown mode `{}`", mode),
+  }
+}
+
+fn input(width: usize, height: usize, elapsed_time_secs: f32) -> Input {
+  Input {
+    window_size: Vec2::new(width as f32, height as f32),
+    elapsed_time_secs,
+    elapsed_time_since_last_frame_secs: 1.0 / 60.0,
+    frame_count: 1.0,
+    ..Input::default()
+  }
+}
+
+fn render_pngs(width: usize, height: usize, is_srgb: bool) {
+  for line in std::io::stdin().lock().lines() {
+    let line = line.expect("Failed to read a frame");
+    // The output path is last, as it can contain spaces
+    let mut values = line.splitn(4, ' ');
+    let mut value = || values.next().expect("Invalid frame");
+    let elapsed_time_secs = value().parse().expect("Invalid time");
+    let input = Input {
+      elapsed_time_since_last_frame_secs: value().parse().expect("Invalid time"),
+      frame_count: value().parse().expect("Invalid frame count"),
+      ..input(width, height, elapsed_time_secs)
+    };
+    render_png(&input, value(), is_srgb);
+  }
+}
+
+fn render_png(input: &Input, output_path: &str, is_srgb: bool) {
+  let width = input.window_size.x as usize;
+  let height = input.window_size.y as usize;
+  let mut pixels = vec![0u8; width * height * 4];
+  pixels
+    .par_chunks_mut(width * 4)
+    .enumerate()
+    .for_each(|(y, row)| {
+      // `INPUT` is set per thread
+      with_input(input, || {
+        for (x, pixel) in row.chunks_mut(4).enumerate() {
+          // Metal calls the fragment shader with the pixel's center
+          let color = shader::pixel_color(Vec2::new(x as f32 + 0.5, y as f32 + 0.5));
+          for (index, (channel, value)) in
+            pixel.iter_mut().zip(color.into_array().iter()).enumerate()
+          {
+            let value = value.clamp(0.0, 1.0);
+            // Alpha stays linear
+            let value = if is_srgb && index < 3 {
+              linear_to_srgb(value)
+            } else {
+              value
+            };
+            *channel = (value * 255.0).round() as u8;
+          }
+        }
+      })
+    });
+
+  let file = std::fs::File::create(output_path).expect("Failed to create the output file");
+  let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width as u32, height as u32);
+  encoder.set_color(png::ColorType::RGBA);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(&pixels))
+    .expect("Failed to write the PNG");
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+  if value <= 0.0031308 {
+    value * 12.92
+  } else {
+    1.055 * value.powf(1.0 / 2.4) - 0.055
+  }
+}
+
+fn print_samples(width: usize, height: usize) {
+  for line in std::io::stdin().lock().lines() {
+    let line = line.expect("Failed to read a sample");
+    let values: Vec<f32> = line
+      .split_whitespace()
+      .map(|value| value.parse().expect("Invalid sample"))
+      .collect();
+    let input = input(width, height, values[2]);
+    let color = with_input(&input, || {
+      shader::pixel_color(Vec2::new(values[0], values[1]))
+    });
+    println!("{} {} {} {}", color.x, color.y, color.z, color.w);
+  }
+}
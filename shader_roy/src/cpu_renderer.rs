@@ -0,0 +1,205 @@
+//! Renders shaders on the CPU, without Metal or a GPU.
+//!
+//! The shader source is regular Rust, so the shader crate is compiled as a
+//! dependency of a generated harness binary, which calls the entry point,
+//! `pixel_color` by default, for every pixel in parallel and writes the frames
+//! to PNGs, or for the given samples only.
+
+use super::frames::Frame;
+use super::project_config::{self, PixelFormat};
+use anyhow::{bail, Context, Result};
+
+/// Renders the frames, which all have the size of the first one, to PNGs.
+pub fn render_frames(shader_file_path: &std::path::Path, frames: &[Frame]) -> Result<()> {
+  let first_frame = match frames.first() {
+    Some(frame) => frame,
+    None => return Ok(()),
+  };
+  // Encoded like the sRGB textures of the Metal renderer
+  let mode = match project_config::load(shader_file_path)?.pixel_format {
+    PixelFormat::Bgra8unorm => "png",
+    PixelFormat::Bgra8unormSrgb => "png_srgb",
+  };
+  let input = frames
+    .iter()
+    .map(|frame| {
+      format!(
+        "{} {} {} {}\n",
+        frame.elapsed_time_secs,
+        frame.elapsed_time_since_last_frame_secs,
+        frame.frame_count,
+        frame.output_path.display()
+      )
+    })
+    .collect();
+  run_harness(
+    shader_file_path,
+    mode,
+    first_frame.width,
+    first_frame.height,
+    input,
+  )
+  .with_context(|| format!("Failed to render {:?} on the CPU", shader_file_path))?;
+  Ok(())
+}
+
+/// A pixel position and time at which to run the entry point.
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+  pub x: f32,
+  pub y: f32,
+  pub elapsed_time_secs: f32,
+}
+
+/// Returns the color of each sample, for a window of the given size.
+pub fn render_samples(
+  shader_file_path: &std::path::Path,
+  width: u32,
+  height: u32,
+  samples: &[Sample],
+) -> Result<Vec<[f32; 4]>> {
+  let input = samples
+    .iter()
+    .map(|sample| format!("{} {} {}\n", sample.x, sample.y, sample.elapsed_time_secs))
+    .collect();
+  let output = run_harness(shader_file_path, "samples", width, height, input)
+    .with_context(|| format!("Failed to run {:?} on the CPU", shader_file_path))?;
+  output
+    .lines()
+    .map(|line| {
+      let mut color = [0.0; 4];
+      let mut channels = line.split_whitespace();
+      for channel in color.iter_mut() {
+        *channel = channels
+          .next()
+          .and_then(|value| value.parse().ok())
+          .with_context(|| format!("Invalid color `{}` from the harness", line))?;
+      }
+      Ok(color)
+    })
+    .collect()
+}
+
+// Runs the harness in the given mode with `input` as its stdin and
+// returns its stdout
+fn run_harness(
+  shader_file_path: &std::path::Path,
+  mode: &str,
+  width: u32,
+  height: u32,
+  input: String,
+) -> Result<String> {
+  let harness_path = prepare_harness(shader_file_path)?;
+  let output = run_cargo(&harness_path, mode, width, height, input);
+  // Keeps the resolved dependency versions for the next run
+  let shared_directory = harness_path.parent().unwrap();
+  std::fs::rename(
+    harness_path.join("Cargo.lock"),
+    shared_directory.join("Cargo.lock"),
+  )
+  .ok();
+  std::fs::remove_dir_all(&harness_path).ok();
+  output
+}
+
+fn run_cargo(
+  harness_path: &std::path::Path,
+  mode: &str,
+  width: u32,
+  height: u32,
+  input: String,
+) -> Result<String> {
+  let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+  let mut child = std::process::Command::new(cargo)
+    .args(["run", "--release", "--quiet", "--manifest-path"])
+    .arg(harness_path.join("Cargo.toml"))
+    // Shared, so the dependencies are only built once. Cargo waits for
+    // concurrent builds to finish.
+    .arg("--target-dir")
+    .arg(harness_path.parent().unwrap().join("target"))
+    .arg("--")
+    .arg(mode)
+    .arg(width.to_string())
+    .arg(height.to_string())
+    .stdin(std::process::Stdio::piped())
+    .stdout(std::process::Stdio::piped())
+    .spawn()
+    .context("Failed to run cargo")?;
+  // Written from another thread, so that a full stdout doesn't block the harness
+  let mut stdin = child.stdin.take().unwrap();
+  let writer = std::thread::spawn(move || {
+    use std::io::Write;
+    stdin.write_all(input.as_bytes())
+  });
+  let output = child.wait_with_output()?;
+  if !output.status.success() {
+    bail!("The harness failed with {}", output.status);
+  }
+  writer.join().unwrap()?;
+  Ok(String::from_utf8(output.stdout)?)
+}
+
+// Writes the harness crate, which depends on the shader's crate, to a
+// temporary directory and returns its path. The directory is unique to this
+// process, so that concurrent runs don't overwrite each other's sources.
+fn prepare_harness(shader_file_path: &std::path::Path) -> Result<std::path::PathBuf> {
+  let shader_manifest_path = project_config::manifest_path(shader_file_path)
+    .with_context(|| format!("Could not find the Cargo.toml of {:?}", shader_file_path))?;
+  let shader_crate_path = shader_manifest_path.parent().unwrap();
+  let shader_manifest = std::fs::read_to_string(&shader_manifest_path)?
+    .parse::<toml::Value>()
+    .with_context(|| format!("Could not parse {:?}", shader_manifest_path))?;
+  let shader_crate_name = shader_manifest
+    .get("package")
+    .and_then(|package| package.get("name"))
+    .and_then(toml::Value::as_str)
+    .with_context(|| format!("Missing [package][name] in {:?}", shader_manifest_path))?;
+
+  let shared_directory = std::env::temp_dir()
+    .join("shader_roy_cpu")
+    .join(shader_crate_name);
+  let harness_path = shared_directory.join(std::process::id().to_string());
+  std::fs::create_dir_all(harness_path.join("src"))?;
+  // Missing before the first run
+  std::fs::copy(
+    shared_directory.join("Cargo.lock"),
+    harness_path.join("Cargo.lock"),
+  )
+  .ok();
+  // The harness isn't part of any workspace the temporary directory is in
+  let harness_manifest = format!(
+    "[package]\n\
+     name = \"shader_roy_cpu_harness\"\n\
+     version = \"0.1.0\"\n\
+     edition = \"2018\"\n\
+     \n\
+     [workspace]\n\
+     \n\
+     [dependencies]\n\
+     shader = {{ package = {shader_crate_name:?}, path = {shader_crate_path:?} }}\n\
+     shader_roy_metal_sl_interface = {{ path = {interface_path:?} }}\n\
+     rayon = \"1.5\"\n\
+     png = \"0.16\"\n",
+    shader_crate_name = shader_crate_name,
+    shader_crate_path = shader_crate_path.canonicalize()?,
+    interface_path = super::shader_file_path_arg::ROOT_PATH
+      .join("../shader_roy_metal_sl_interface")
+      .canonicalize()?,
+  );
+  write(&harness_path.join("Cargo.toml"), &harness_manifest)?;
+  write(
+    &harness_path.join("src/main.rs"),
+    &include_str!("cpu_harness.rs").replace(
+      "shader::pixel_color",
+      &format!(
+        "shader::{}",
+        project_config::load(shader_file_path)?.entry_point
+      ),
+    ),
+  )?;
+  Ok(harness_path)
+}
+
+fn write(path: &std::path::Path, contents: &str) -> Result<()> {
+  std::fs::write(path, contents).with_context(|| format!("Failed to write {:?}", path))
+}
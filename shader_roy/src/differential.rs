@@ -0,0 +1,182 @@
+//! Checks that the transpiled shader computes the same colors as its Rust
+//! source. The Rust source runs on the CPU in the harness of `cpu_renderer`,
+//! the transpiled shader in `rust_to_metal_sl::NagaInterpreter`, both over
+//! the same grid of pixels and input times.
+//!
+//! Metal needs a GPU, so the MSL output itself is not run, only the naga
+//! modules parsed from the WGSL output and built directly from the Rust AST.
+
+use super::cpu_renderer::{self, Sample};
+use super::project_config;
+use super::shader_compiler;
+use anyhow::{anyhow, Context, Result};
+use rust_to_metal_sl::{NagaInterpreter, Value};
+
+// The entry point both naga targets wrap `pixel_color` in
+const ENTRY_POINT_NAME: &str = "fragment_shader";
+
+pub struct Options {
+  pub width: u32,
+  pub height: u32,
+  // Number of sampled pixels in each row and column of the window
+  pub columns: u32,
+  pub rows: u32,
+  pub times: Vec<f32>,
+  pub tolerance: f32,
+}
+
+/// Prints the maximum per-channel error of each target and returns whether
+/// all of them are within the tolerance.
+pub fn check(shader_file_path: &std::path::Path, options: &Options) -> Result<bool> {
+  let samples = samples(options);
+  let expected =
+    cpu_renderer::render_samples(shader_file_path, options.width, options.height, &samples)?;
+
+  // The input struct needs to be part of the transpiled source
+  let source = format!(
+    "{}{}",
+    shader_compiler::read_shader_sources(shader_file_path)?,
+    std::fs::read_to_string(&*shader_compiler::SHADER_INTERFACE_PATH)?
+  );
+  let config =
+    shader_compiler::transpile_config(&project_config::load(shader_file_path)?.entry_point);
+  let wgsl =
+    rust_to_metal_sl::transpile_with_backend(&source, &config, &rust_to_metal_sl::WgslBackend)?;
+  let targets = vec![
+    (
+      "wgsl",
+      naga::front::wgsl::parse_str(&wgsl).map_err(|error| anyhow!(error.emit_to_string(&wgsl)))?,
+    ),
+    (
+      "naga",
+      rust_to_metal_sl::transpile_to_naga_module(&source, &config)?,
+    ),
+  ];
+
+  let mut is_within_tolerance = true;
+  for (target, module) in &targets {
+    let report = compare(module, options, &samples, &expected)
+      .with_context(|| format!("Failed to run the {} target", target))?;
+    println!(
+      "  {}: max error r {:.4} g {:.4} b {:.4} a {:.4}",
+      target,
+      report.max_errors[0],
+      report.max_errors[1],
+      report.max_errors[2],
+      report.max_errors[3]
+    );
+    if let Some(worst) = report.worst {
+      println!(
+        "    worst at ({}, {}) {}s: expected {:?}, got {:?}",
+        worst.sample.x,
+        worst.sample.y,
+        worst.sample.elapsed_time_secs,
+        worst.expected,
+        worst.actual
+      );
+    }
+    is_within_tolerance &= report
+      .max_errors
+      .iter()
+      .all(|error| *error <= options.tolerance);
+  }
+  Ok(is_within_tolerance)
+}
+
+// The centers of a grid of pixels, at each time
+fn samples(options: &Options) -> Vec<Sample> {
+  let mut samples = Vec::new();
+  for &elapsed_time_secs in &options.times {
+    for row in 0..options.rows {
+      for column in 0..options.columns {
+        let position = |index: u32, count: u32, size: u32| {
+          ((index as f32 + 0.5) * size as f32 / count as f32).floor() + 0.5
+        };
+        samples.push(Sample {
+          x: position(column, options.columns, options.width),
+          y: position(row, options.rows, options.height),
+          elapsed_time_secs,
+        });
+      }
+    }
+  }
+  samples
+}
+
+struct Report {
+  max_errors: [f32; 4],
+  worst: Option<Mismatch>,
+}
+
+struct Mismatch {
+  sample: Sample,
+  expected: [f32; 4],
+  actual: [f32; 4],
+}
+
+fn compare(
+  module: &naga::Module,
+  options: &Options,
+  samples: &[Sample],
+  expected: &[[f32; 4]],
+) -> Result<Report> {
+  let mut interpreter = NagaInterpreter::new(module)?;
+  let mut report = Report {
+    max_errors: [0.0; 4],
+    worst: None,
+  };
+  let mut worst_error = 0.0;
+  for (sample, expected) in samples.iter().zip(expected) {
+    // The same input as the harness passes to `with_input`
+    interpreter.set_uniform(
+      0,
+      0,
+      &[
+        (
+          "window_size",
+          vector(&[options.width as f32, options.height as f32]),
+        ),
+        ("elapsed_time_secs", Value::F32(sample.elapsed_time_secs)),
+        ("elapsed_time_since_last_frame_secs", Value::F32(1.0 / 60.0)),
+        ("frame_count", Value::F32(1.0)),
+      ],
+    )?;
+    let color = interpreter
+      .run_entry_point(
+        ENTRY_POINT_NAME,
+        vec![vector(&[sample.x, sample.y, 0.0, 1.0])],
+      )
+      .with_context(|| format!("At {:?}", sample))?
+      .to_f32s()
+      .filter(|color| color.len() == 4)
+      .context("Expected a `Vec4` color")?;
+    let mut actual = [0.0; 4];
+    actual.copy_from_slice(&color);
+    for channel in 0..4 {
+      let error = channel_error(expected[channel], actual[channel]);
+      report.max_errors[channel] = report.max_errors[channel].max(error);
+      if error > worst_error {
+        worst_error = error;
+        report.worst = Some(Mismatch {
+          sample: *sample,
+          expected: *expected,
+          actual,
+        });
+      }
+    }
+  }
+  Ok(report)
+}
+
+fn vector(components: &[f32]) -> Value {
+  Value::Composite(components.iter().copied().map(Value::F32).collect())
+}
+
+// Colors are clamped when written to the window, like in the harness
+fn channel_error(expected: f32, actual: f32) -> f32 {
+  match (expected.is_nan(), actual.is_nan()) {
+    (true, true) => 0.0,
+    (false, false) => (expected.clamp(0.0, 1.0) - actual.clamp(0.0, 1.0)).abs(),
+    _ => f32::INFINITY,
+  }
+}
@@ -0,0 +1,51 @@
+#include <metal_stdlib>
+
+using namespace metal;
+
+// Drawn instead of the user's shader when it fails to compile, so it can't
+// depend on anything the user's code touches
+
+vertex float4 overlay_vertex_shader(unsigned int vertex_index [[ vertex_id ]]) {
+    // A triangle strip covering the whole window
+    float2 corner = float2(vertex_index & 1, vertex_index >> 1);
+    return float4(corner * 2.0 - 1.0, 0, 1);
+}
+
+struct OverlayInput {
+    uint2 glyph_size;
+    uint2 text_origin;
+    uint scale;
+};
+
+fragment float4 overlay_fragment_shader(
+    float4 position [[ position ]],
+    constant OverlayInput &input [[ buffer(0) ]],
+    texture2d<float, access::read> last_frame [[ texture(0) ]],
+    texture2d<float, access::read> font [[ texture(1) ]],
+    texture2d<uint, access::read> text [[ texture(2) ]]
+) {
+    uint2 pixel = uint2(position.xy);
+    uint2 last_frame_size = uint2(last_frame.get_width(), last_frame.get_height());
+    float3 color = last_frame.read(min(pixel, last_frame_size - 1)).rgb * 0.25;
+    if (any(pixel < input.text_origin)) {
+        return float4(color, 1);
+    }
+    uint2 text_pixel = (pixel - input.text_origin) / input.scale;
+    uint2 cell = text_pixel / input.glyph_size;
+    if (cell.x >= text.get_width() || cell.y >= text.get_height()) {
+        return float4(color, 1);
+    }
+    uint code = text.read(cell).r;
+    uint glyph_index = code & 0x7F;
+    if (glyph_index == 0) {
+        return float4(color, 1);
+    }
+    uint2 glyph_pixel = text_pixel % input.glyph_size;
+    float coverage = font.read(uint2(
+        (glyph_index - 1) * input.glyph_size.x + glyph_pixel.x,
+        glyph_pixel.y
+    )).r;
+    // The header is red
+    float3 text_color = (code & 0x80) != 0 ? float3(1.0, 0.35, 0.3) : float3(1.0);
+    return float4(mix(color, text_color, coverage), 1);
+}
@@ -0,0 +1,174 @@
+//! Shows compile errors in the window, over the dimmed frames of the last
+//! working shader, so that stale output isn't mistaken for success.
+
+use super::error_text::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use anyhow::{anyhow, Result};
+use metal::*;
+
+#[repr(C)]
+struct OverlayInput {
+  glyph_size: [u32; 2],
+  text_origin: [u32; 2],
+  scale: u32,
+  // Matches the size of the Metal struct, which is aligned to its `uint2`s
+  _padding: u32,
+}
+
+pub struct ErrorOverlay {
+  pixel_format: MTLPixelFormat,
+  pipeline_state: RenderPipelineState,
+  font_texture: Texture,
+  last_frame_texture: Texture,
+  // The laid out message, for the message and drawable size it was laid
+  // out for
+  text: Option<(String, (u64, u64), Texture)>,
+}
+
+impl ErrorOverlay {
+  /// Renders into and keeps frames of the window's `pixel_format`.
+  pub fn new(device: &DeviceRef, pixel_format: MTLPixelFormat) -> Result<Self> {
+    let library = device
+      .new_library_with_source(include_str!("error_overlay.metal"), &CompileOptions::new())
+      .map_err(|error| anyhow!("Failed to compile the error overlay: {}", error))?;
+    let pipeline_state = super::metal_renderer::prepare_pipeline_state(
+      device,
+      &library,
+      "overlay_vertex_shader",
+      "overlay_fragment_shader",
+      pixel_format,
+    )
+    .map_err(|error| anyhow!("{}", error))?;
+    let font_atlas = error_text::font_atlas();
+    let font_texture = new_texture(
+      device,
+      MTLPixelFormat::R8Unorm,
+      (
+        (font_atlas.len() / GLYPH_HEIGHT) as u64,
+        GLYPH_HEIGHT as u64,
+      ),
+      &font_atlas,
+    );
+    // Black until a frame is kept, for errors before any shader compiled
+    let last_frame_texture = new_texture(device, pixel_format, (1, 1), &[0; 4]);
+    Ok(Self {
+      pixel_format,
+      pipeline_state,
+      font_texture,
+      last_frame_texture,
+      text: None,
+    })
+  }
+
+  /// Copies the drawable's frame, to show it under the error. Needs the
+  /// layer not to be `framebuffer_only`.
+  pub fn keep_frame(
+    &mut self,
+    device: &DeviceRef,
+    command_buffer: &CommandBufferRef,
+    drawable_texture: &TextureRef,
+  ) {
+    let size = (drawable_texture.width(), drawable_texture.height());
+    if (
+      self.last_frame_texture.width(),
+      self.last_frame_texture.height(),
+    ) != size
+    {
+      let descriptor = TextureDescriptor::new();
+      descriptor.set_pixel_format(self.pixel_format);
+      descriptor.set_width(size.0);
+      descriptor.set_height(size.1);
+      descriptor.set_storage_mode(MTLStorageMode::Private);
+      descriptor.set_usage(MTLTextureUsage::ShaderRead);
+      self.last_frame_texture = device.new_texture(&descriptor);
+    }
+    let blit_encoder = command_buffer.new_blit_command_encoder();
+    blit_encoder.copy_from_texture(
+      drawable_texture,
+      0,
+      0,
+      MTLOrigin { x: 0, y: 0, z: 0 },
+      MTLSize {
+        width: size.0,
+        height: size.1,
+        depth: 1,
+      },
+      &self.last_frame_texture,
+      0,
+      0,
+      MTLOrigin { x: 0, y: 0, z: 0 },
+    );
+    blit_encoder.end_encoding();
+  }
+
+  /// Draws the message over the last kept frame.
+  pub fn render(
+    &mut self,
+    device: &DeviceRef,
+    command_buffer: &CommandBufferRef,
+    drawable_texture: &TextureRef,
+    message: &str,
+  ) {
+    let size = (drawable_texture.width(), drawable_texture.height());
+    // Readable on both regular and Retina displays
+    let scale = (size.0 / 600).max(1).min(4);
+    let cell_size = (GLYPH_WIDTH as u64 * scale, GLYPH_HEIGHT as u64 * scale);
+    // With a margin of one cell on each side
+    let columns = (size.0 / cell_size.0).saturating_sub(2).max(1);
+    let rows = (size.1 / cell_size.1).saturating_sub(2).max(1);
+    let is_laid_out = matches!(
+      &self.text,
+      Some((laid_out_message, laid_out_size, _))
+        if laid_out_message == message && *laid_out_size == size
+    );
+    if !is_laid_out {
+      let grid = error_text::layout(message, columns as usize, rows as usize);
+      let texture = new_texture(device, MTLPixelFormat::R8Uint, (columns, rows), &grid);
+      self.text = Some((message.to_owned(), size, texture));
+    }
+    let text_texture = &self.text.as_ref().unwrap().2;
+
+    let render_pass_descriptor = RenderPassDescriptor::new();
+    super::metal_renderer::prepare_render_pass_descriptor(render_pass_descriptor, drawable_texture);
+    let encoder = command_buffer.new_render_command_encoder(render_pass_descriptor);
+    encoder.set_render_pipeline_state(&self.pipeline_state);
+    let input = OverlayInput {
+      glyph_size: [GLYPH_WIDTH as u32, GLYPH_HEIGHT as u32],
+      text_origin: [cell_size.0 as u32, cell_size.1 as u32],
+      scale: scale as u32,
+      _padding: 0,
+    };
+    encoder.set_fragment_bytes(
+      0,
+      std::mem::size_of::<OverlayInput>() as u64,
+      &input as *const OverlayInput as *const _,
+    );
+    encoder.set_fragment_texture(0, Some(&self.last_frame_texture));
+    encoder.set_fragment_texture(1, Some(&self.font_texture));
+    encoder.set_fragment_texture(2, Some(text_texture));
+    encoder.draw_primitives(MTLPrimitiveType::TriangleStrip, 0, 4);
+    encoder.end_encoding();
+  }
+}
+
+// A texture with one byte per pixel, or four for BGRA
+fn new_texture(
+  device: &DeviceRef,
+  pixel_format: MTLPixelFormat,
+  (width, height): (u64, u64),
+  bytes: &[u8],
+) -> Texture {
+  let bytes_per_pixel = bytes.len() as u64 / (width * height);
+  let descriptor = TextureDescriptor::new();
+  descriptor.set_pixel_format(pixel_format);
+  descriptor.set_width(width);
+  descriptor.set_height(height);
+  descriptor.set_usage(MTLTextureUsage::ShaderRead);
+  let texture = device.new_texture(&descriptor);
+  texture.replace_region(
+    MTLRegion::new_2d(0, 0, width, height),
+    0,
+    bytes.as_ptr() as *const _,
+    width * bytes_per_pixel,
+  );
+  texture
+}
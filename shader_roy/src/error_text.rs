@@ -0,0 +1,420 @@
+//! Lays out compile errors for the window's error overlay, as a grid of
+//! characters drawn with a bitmap font.
+
+pub const GLYPH_WIDTH: usize = 7;
+pub const GLYPH_HEIGHT: usize = 13;
+// Set on the characters of the header line, which is drawn in red
+pub const HEADER_FLAG: u8 = 0x80;
+
+const HEADER: &str = "Compile error, showing the last working frame";
+const FIRST_CHAR: u8 = b' ';
+
+// The printable ASCII characters of the public domain X11 misc-fixed 7x13
+// font, one byte per row with the leftmost pixel in bit 6
+const GLYPHS: [[u8; GLYPH_HEIGHT]; 95] = [
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // space
+  [
+    0x00, 0x00, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x08, 0x00, 0x00,
+  ], // !
+  [
+    0x00, 0x00, 0x14, 0x14, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // "
+  [
+    0x00, 0x00, 0x00, 0x14, 0x14, 0x3e, 0x14, 0x3e, 0x14, 0x14, 0x00, 0x00, 0x00,
+  ], // #
+  [
+    0x00, 0x00, 0x00, 0x08, 0x1e, 0x28, 0x1c, 0x0a, 0x3c, 0x08, 0x00, 0x00, 0x00,
+  ], // $
+  [
+    0x00, 0x00, 0x22, 0x52, 0x24, 0x08, 0x08, 0x10, 0x24, 0x4a, 0x44, 0x00, 0x00,
+  ], // %
+  [
+    0x00, 0x00, 0x00, 0x00, 0x30, 0x48, 0x48, 0x30, 0x4a, 0x44, 0x3a, 0x00, 0x00,
+  ], // &
+  [
+    0x00, 0x00, 0x08, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // '
+  [
+    0x00, 0x00, 0x04, 0x08, 0x08, 0x10, 0x10, 0x10, 0x08, 0x08, 0x04, 0x00, 0x00,
+  ], // (
+  [
+    0x00, 0x00, 0x10, 0x08, 0x08, 0x04, 0x04, 0x04, 0x08, 0x08, 0x10, 0x00, 0x00,
+  ], // )
+  [
+    0x00, 0x00, 0x00, 0x00, 0x24, 0x18, 0x7e, 0x18, 0x24, 0x00, 0x00, 0x00, 0x00,
+  ], // *
+  [
+    0x00, 0x00, 0x00, 0x00, 0x08, 0x08, 0x3e, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00,
+  ], // +
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1c, 0x18, 0x20, 0x00,
+  ], // ,
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3e, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // -
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00,
+  ], // .
+  [
+    0x00, 0x00, 0x02, 0x02, 0x04, 0x04, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00,
+  ], // /
+  [
+    0x00, 0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x42, 0x42, 0x24, 0x18, 0x00, 0x00,
+  ], // 0
+  [
+    0x00, 0x00, 0x08, 0x18, 0x28, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00,
+  ], // 1
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x02, 0x04, 0x18, 0x20, 0x40, 0x7e, 0x00, 0x00,
+  ], // 2
+  [
+    0x00, 0x00, 0x7e, 0x02, 0x04, 0x08, 0x1c, 0x02, 0x02, 0x42, 0x3c, 0x00, 0x00,
+  ], // 3
+  [
+    0x00, 0x00, 0x04, 0x0c, 0x14, 0x24, 0x44, 0x44, 0x7e, 0x04, 0x04, 0x00, 0x00,
+  ], // 4
+  [
+    0x00, 0x00, 0x7e, 0x40, 0x40, 0x5c, 0x62, 0x02, 0x02, 0x42, 0x3c, 0x00, 0x00,
+  ], // 5
+  [
+    0x00, 0x00, 0x1c, 0x20, 0x40, 0x40, 0x5c, 0x62, 0x42, 0x42, 0x3c, 0x00, 0x00,
+  ], // 6
+  [
+    0x00, 0x00, 0x7e, 0x02, 0x04, 0x08, 0x08, 0x10, 0x10, 0x20, 0x20, 0x00, 0x00,
+  ], // 7
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x3c, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00,
+  ], // 8
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x46, 0x3a, 0x02, 0x02, 0x04, 0x38, 0x00, 0x00,
+  ], // 9
+  [
+    0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00,
+  ], // :
+  [
+    0x00, 0x00, 0x00, 0x00, 0x08, 0x1c, 0x08, 0x00, 0x00, 0x1c, 0x18, 0x20, 0x00,
+  ], // ;
+  [
+    0x00, 0x00, 0x02, 0x04, 0x08, 0x10, 0x20, 0x10, 0x08, 0x04, 0x02, 0x00, 0x00,
+  ], // <
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x7e, 0x00, 0x00, 0x00, 0x00,
+  ], // =
+  [
+    0x00, 0x00, 0x20, 0x10, 0x08, 0x04, 0x02, 0x04, 0x08, 0x10, 0x20, 0x00, 0x00,
+  ], // >
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x02, 0x04, 0x08, 0x08, 0x00, 0x08, 0x00, 0x00,
+  ], // ?
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x4e, 0x52, 0x56, 0x4a, 0x40, 0x3c, 0x00, 0x00,
+  ], // @
+  [
+    0x00, 0x00, 0x18, 0x24, 0x42, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // A
+  [
+    0x00, 0x00, 0x7c, 0x22, 0x22, 0x22, 0x3c, 0x22, 0x22, 0x22, 0x7c, 0x00, 0x00,
+  ], // B
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x40, 0x40, 0x40, 0x40, 0x40, 0x42, 0x3c, 0x00, 0x00,
+  ], // C
+  [
+    0x00, 0x00, 0x7c, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x22, 0x7c, 0x00, 0x00,
+  ], // D
+  [
+    0x00, 0x00, 0x7e, 0x40, 0x40, 0x40, 0x78, 0x40, 0x40, 0x40, 0x7e, 0x00, 0x00,
+  ], // E
+  [
+    0x00, 0x00, 0x7e, 0x40, 0x40, 0x40, 0x78, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00,
+  ], // F
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x40, 0x40, 0x40, 0x4e, 0x42, 0x46, 0x3a, 0x00, 0x00,
+  ], // G
+  [
+    0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x7e, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // H
+  [
+    0x00, 0x00, 0x3e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00,
+  ], // I
+  [
+    0x00, 0x00, 0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x44, 0x38, 0x00, 0x00,
+  ], // J
+  [
+    0x00, 0x00, 0x42, 0x44, 0x48, 0x50, 0x60, 0x50, 0x48, 0x44, 0x42, 0x00, 0x00,
+  ], // K
+  [
+    0x00, 0x00, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x40, 0x7e, 0x00, 0x00,
+  ], // L
+  [
+    0x00, 0x00, 0x42, 0x66, 0x66, 0x5a, 0x5a, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // M
+  [
+    0x00, 0x00, 0x42, 0x42, 0x62, 0x52, 0x4a, 0x46, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // N
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00,
+  ], // O
+  [
+    0x00, 0x00, 0x7c, 0x42, 0x42, 0x42, 0x7c, 0x40, 0x40, 0x40, 0x40, 0x00, 0x00,
+  ], // P
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x42, 0x52, 0x4a, 0x3c, 0x02, 0x00,
+  ], // Q
+  [
+    0x00, 0x00, 0x7c, 0x42, 0x42, 0x42, 0x7c, 0x50, 0x48, 0x44, 0x42, 0x00, 0x00,
+  ], // R
+  [
+    0x00, 0x00, 0x3c, 0x42, 0x40, 0x40, 0x3c, 0x02, 0x02, 0x42, 0x3c, 0x00, 0x00,
+  ], // S
+  [
+    0x00, 0x00, 0x3e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00,
+  ], // T
+  [
+    0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00,
+  ], // U
+  [
+    0x00, 0x00, 0x42, 0x42, 0x42, 0x24, 0x24, 0x24, 0x18, 0x18, 0x18, 0x00, 0x00,
+  ], // V
+  [
+    0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x5a, 0x5a, 0x66, 0x66, 0x42, 0x00, 0x00,
+  ], // W
+  [
+    0x00, 0x00, 0x42, 0x42, 0x24, 0x24, 0x18, 0x24, 0x24, 0x42, 0x42, 0x00, 0x00,
+  ], // X
+  [
+    0x00, 0x00, 0x22, 0x22, 0x14, 0x14, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00,
+  ], // Y
+  [
+    0x00, 0x00, 0x7e, 0x02, 0x04, 0x08, 0x18, 0x10, 0x20, 0x40, 0x7e, 0x00, 0x00,
+  ], // Z
+  [
+    0x00, 0x3c, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x20, 0x3c, 0x00,
+  ], // [
+  [
+    0x00, 0x00, 0x20, 0x20, 0x10, 0x10, 0x08, 0x04, 0x04, 0x02, 0x02, 0x00, 0x00,
+  ], // \
+  [
+    0x00, 0x3c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x3c, 0x00,
+  ], // ]
+  [
+    0x00, 0x00, 0x08, 0x14, 0x22, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // ^
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x00,
+  ], // _
+  [
+    0x00, 0x10, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // `
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x02, 0x3e, 0x42, 0x46, 0x3a, 0x00, 0x00,
+  ], // a
+  [
+    0x00, 0x00, 0x40, 0x40, 0x40, 0x5c, 0x62, 0x42, 0x42, 0x62, 0x5c, 0x00, 0x00,
+  ], // b
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x40, 0x40, 0x42, 0x3c, 0x00, 0x00,
+  ], // c
+  [
+    0x00, 0x00, 0x02, 0x02, 0x02, 0x3a, 0x46, 0x42, 0x42, 0x46, 0x3a, 0x00, 0x00,
+  ], // d
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x7e, 0x40, 0x42, 0x3c, 0x00, 0x00,
+  ], // e
+  [
+    0x00, 0x00, 0x1c, 0x22, 0x20, 0x20, 0x78, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00,
+  ], // f
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x44, 0x44, 0x38, 0x40, 0x3c, 0x42, 0x3c,
+  ], // g
+  [
+    0x00, 0x00, 0x40, 0x40, 0x40, 0x5c, 0x62, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // h
+  [
+    0x00, 0x00, 0x00, 0x08, 0x00, 0x18, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00,
+  ], // i
+  [
+    0x00, 0x00, 0x00, 0x02, 0x00, 0x06, 0x02, 0x02, 0x02, 0x02, 0x22, 0x22, 0x1c,
+  ], // j
+  [
+    0x00, 0x00, 0x40, 0x40, 0x40, 0x44, 0x48, 0x70, 0x48, 0x44, 0x42, 0x00, 0x00,
+  ], // k
+  [
+    0x00, 0x00, 0x18, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x3e, 0x00, 0x00,
+  ], // l
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x34, 0x2a, 0x2a, 0x2a, 0x2a, 0x22, 0x00, 0x00,
+  ], // m
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x5c, 0x62, 0x42, 0x42, 0x42, 0x42, 0x00, 0x00,
+  ], // n
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x42, 0x42, 0x42, 0x3c, 0x00, 0x00,
+  ], // o
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x5c, 0x62, 0x42, 0x62, 0x5c, 0x40, 0x40, 0x40,
+  ], // p
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3a, 0x46, 0x42, 0x46, 0x3a, 0x02, 0x02, 0x02,
+  ], // q
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x5c, 0x22, 0x20, 0x20, 0x20, 0x20, 0x00, 0x00,
+  ], // r
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x3c, 0x42, 0x30, 0x0c, 0x42, 0x3c, 0x00, 0x00,
+  ], // s
+  [
+    0x00, 0x00, 0x00, 0x20, 0x20, 0x78, 0x20, 0x20, 0x20, 0x22, 0x1c, 0x00, 0x00,
+  ], // t
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x42, 0x46, 0x3a, 0x00, 0x00,
+  ], // u
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x22, 0x22, 0x14, 0x14, 0x08, 0x00, 0x00,
+  ], // v
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x22, 0x22, 0x2a, 0x2a, 0x2a, 0x14, 0x00, 0x00,
+  ], // w
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x24, 0x18, 0x18, 0x24, 0x42, 0x00, 0x00,
+  ], // x
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x42, 0x42, 0x46, 0x3a, 0x02, 0x42, 0x3c,
+  ], // y
+  [
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x7e, 0x04, 0x08, 0x10, 0x20, 0x7e, 0x00, 0x00,
+  ], // z
+  [
+    0x00, 0x0e, 0x10, 0x10, 0x10, 0x08, 0x30, 0x08, 0x10, 0x10, 0x10, 0x0e, 0x00,
+  ], // {
+  [
+    0x00, 0x00, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x08, 0x00, 0x00,
+  ], // |
+  [
+    0x00, 0x38, 0x04, 0x04, 0x04, 0x08, 0x06, 0x08, 0x04, 0x04, 0x04, 0x38, 0x00,
+  ], // }
+  [
+    0x00, 0x00, 0x12, 0x2a, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+  ], // ~
+];
+
+/// One byte per pixel, 0 or 255, with the glyphs side by side in ASCII order
+/// starting from the space.
+pub fn font_atlas() -> Vec<u8> {
+  let width = GLYPHS.len() * GLYPH_WIDTH;
+  let mut atlas = vec![0; width * GLYPH_HEIGHT];
+  for (index, glyph) in GLYPHS.iter().enumerate() {
+    for (y, row) in glyph.iter().enumerate() {
+      for x in 0..GLYPH_WIDTH {
+        if row & (1 << (GLYPH_WIDTH - 1 - x)) != 0 {
+          atlas[y * width + index * GLYPH_WIDTH + x] = 255;
+        }
+      }
+    }
+  }
+  atlas
+}
+
+/// The atlas index of each cell of a `columns` by `rows` grid, plus one, or
+/// 0 for empty cells. The header comes first, followed by the wrapped
+/// message, which is cut off with `...` when it doesn't fit.
+pub fn layout(message: &str, columns: usize, rows: usize) -> Vec<u8> {
+  let mut grid = vec![0; columns * rows];
+  if columns == 0 {
+    return grid;
+  }
+  let lines = std::iter::once(HEADER.to_owned())
+    .chain(std::iter::once(String::new()))
+    .chain(message.lines().flat_map(|line| wrap(line, columns)));
+  for (row, line) in lines.enumerate() {
+    if row == rows {
+      let last_row = &mut grid[(rows - 1) * columns..];
+      let ellipsis = columns.min(3);
+      for cell in &mut last_row[..columns - ellipsis] {
+        *cell = 0;
+      }
+      for cell in &mut last_row[columns - ellipsis..] {
+        *cell = cell_for(b'.');
+      }
+      break;
+    }
+    let flag = if row == 0 { HEADER_FLAG } else { 0 };
+    for (column, byte) in line.bytes().take(columns).enumerate() {
+      grid[row * columns + column] = cell_for(byte) | flag;
+    }
+  }
+  grid
+}
+
+// Breaks at the last space that fits, or mid-word for long words
+fn wrap(line: &str, columns: usize) -> Vec<String> {
+  let mut line: String = line
+    .replace('\t', "    ")
+    .chars()
+    .map(|char| {
+      if char.is_ascii() && !char.is_ascii_control() {
+        char
+      } else {
+        '?'
+      }
+    })
+    .collect();
+  let mut lines = Vec::new();
+  while line.len() > columns {
+    let split_at = match line[..=columns].rfind(' ') {
+      Some(index) if index > 0 => index,
+      _ => columns,
+    };
+    let rest = line[split_at..].trim_start().to_owned();
+    line.truncate(split_at);
+    lines.push(std::mem::replace(&mut line, rest));
+  }
+  lines.push(line);
+  lines
+}
+
+fn cell_for(byte: u8) -> u8 {
+  if byte == FIRST_CHAR {
+    0
+  } else {
+    byte - FIRST_CHAR + 1
+  }
+}
+
+#[test]
+fn test_layout() {
+  let grid = layout("error: expected `;`\n  --> a long line", 12, 5);
+  let text = grid
+    .chunks(12)
+    .map(|row| {
+      row
+        .iter()
+        .map(|&cell| match cell & !HEADER_FLAG {
+          0 => ' ',
+          cell => (cell - 1 + FIRST_CHAR) as char,
+        })
+        .collect::<String>()
+    })
+    .collect::<Vec<_>>();
+  assert_eq!(
+    text,
+    vec![
+      "Compile erro",
+      "            ",
+      "error:      ",
+      "expected `;`",
+      "         ...",
+    ]
+  );
+  assert!(grid[..12].iter().all(|cell| cell & HEADER_FLAG != 0));
+  assert_eq!(grid[12 * 2] & HEADER_FLAG, 0);
+
+  let atlas = font_atlas();
+  assert_eq!(atlas.len(), 95 * GLYPH_WIDTH * GLYPH_HEIGHT);
+  let pixel =
+    |index: usize, x: usize, y: usize| atlas[y * 95 * GLYPH_WIDTH + index * GLYPH_WIDTH + x];
+  // The space is blank, the `!` isn't
+  assert!((0..GLYPH_WIDTH).all(|x| (0..GLYPH_HEIGHT).all(|y| pixel(0, x, y) == 0)));
+  assert!((0..GLYPH_HEIGHT).any(|y| pixel(1, 3, y) == 255));
+}
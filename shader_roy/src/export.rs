@@ -0,0 +1,108 @@
+//! Encodes rendered frames as a looping GIF, or as a Y4M video stream which
+//! ffmpeg can convert to other formats.
+
+use super::frames::Frame;
+use anyhow::{bail, Context, Result};
+use std::io::Write;
+
+pub fn write_gif(
+  frames: &[Frame],
+  frames_per_sec: f32,
+  output_path: &std::path::Path,
+) -> Result<()> {
+  let (width, height) = frame_size(frames)?;
+  let file = std::fs::File::create(output_path)
+    .with_context(|| format!("Failed to create {:?}", output_path))?;
+  let mut encoder = gif::Encoder::new(std::io::BufWriter::new(file), width, height, &[])?;
+  encoder.set_repeat(gif::Repeat::Infinite)?;
+  // GIF frame delays are in hundredths of a second
+  let delay = (100.0 / frames_per_sec).round().max(1.0) as u16;
+  for frame in frames {
+    let mut pixels = read_png(&frame.output_path)?;
+    // Speed 10 of 30, a good trade-off between size and encoding time
+    let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+    gif_frame.delay = delay;
+    encoder.write_frame(&gif_frame)?;
+  }
+  Ok(())
+}
+
+/// Writes uncompressed 4:4:4 YCbCr frames with BT.601 limited range.
+pub fn write_y4m(
+  frames: &[Frame],
+  frames_per_sec: f32,
+  output_path: &std::path::Path,
+) -> Result<()> {
+  let (width, height) = frame_size(frames)?;
+  let file = std::fs::File::create(output_path)
+    .with_context(|| format!("Failed to create {:?}", output_path))?;
+  let mut writer = std::io::BufWriter::new(file);
+  // The frame rate is a ratio, with 3 decimals kept
+  writeln!(
+    writer,
+    "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444",
+    width,
+    height,
+    (frames_per_sec * 1000.0).round() as u32
+  )?;
+  let plane_size = width as usize * height as usize;
+  for frame in frames {
+    let pixels = read_png(&frame.output_path)?;
+    let mut planes = vec![0u8; plane_size * 3];
+    for (index, pixel) in pixels.chunks(4).enumerate() {
+      let [y, cb, cr] = rgb_to_ycbcr([pixel[0], pixel[1], pixel[2]]);
+      planes[index] = y;
+      planes[plane_size + index] = cb;
+      planes[plane_size * 2 + index] = cr;
+    }
+    writer.write_all(b"FRAME\n")?;
+    writer.write_all(&planes)?;
+  }
+  writer.flush()?;
+  Ok(())
+}
+
+// BT.601, with Y in 16..=235 and Cb, Cr in 16..=240
+fn rgb_to_ycbcr(rgb: [u8; 3]) -> [u8; 3] {
+  let [r, g, b] = [
+    rgb[0] as f32 / 255.0,
+    rgb[1] as f32 / 255.0,
+    rgb[2] as f32 / 255.0,
+  ];
+  [
+    (16.0 + 65.481 * r + 128.553 * g + 24.966 * b).round() as u8,
+    (128.0 - 37.797 * r - 74.203 * g + 112.0 * b).round() as u8,
+    (128.0 + 112.0 * r - 93.786 * g - 18.214 * b).round() as u8,
+  ]
+}
+
+fn frame_size(frames: &[Frame]) -> Result<(u16, u16)> {
+  let frame = frames.first().context("No frames to export")?;
+  if frame.width > u16::MAX as u32 || frame.height > u16::MAX as u32 {
+    bail!("{}x{} is too large to export", frame.width, frame.height);
+  }
+  Ok((frame.width as u16, frame.height as u16))
+}
+
+// Returns the RGBA pixels of a PNG written by a renderer
+fn read_png(path: &std::path::Path) -> Result<Vec<u8>> {
+  let file = std::fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+  let (info, mut reader) = png::Decoder::new(file)
+    .read_info()
+    .with_context(|| format!("Failed to read {:?}", path))?;
+  if info.color_type != png::ColorType::RGBA || info.bit_depth != png::BitDepth::Eight {
+    bail!("Expected an 8-bit RGBA PNG at {:?}", path);
+  }
+  let mut pixels = vec![0; info.buffer_size()];
+  reader
+    .next_frame(&mut pixels)
+    .with_context(|| format!("Failed to read {:?}", path))?;
+  Ok(pixels)
+}
+
+#[test]
+fn test_rgb_to_ycbcr() {
+  assert_eq!(rgb_to_ycbcr([0, 0, 0]), [16, 128, 128]);
+  assert_eq!(rgb_to_ycbcr([255, 255, 255]), [235, 128, 128]);
+  assert_eq!(rgb_to_ycbcr([255, 0, 0]), [81, 90, 240]);
+}
@@ -0,0 +1,62 @@
+//! Frames rendered without a window, with a fixed timestep, so that the
+//! same arguments always produce the same images.
+
+pub struct Frame {
+  pub width: u32,
+  pub height: u32,
+  pub elapsed_time_secs: f32,
+  pub elapsed_time_since_last_frame_secs: f32,
+  /// Starting with 1, like `Input::frame_count`
+  pub frame_count: u32,
+  pub output_path: std::path::PathBuf,
+}
+
+pub struct Sequence {
+  pub width: u32,
+  pub height: u32,
+  pub start_time_secs: f32,
+  pub frames_per_sec: f32,
+  pub frame_count: u32,
+}
+
+impl Sequence {
+  /// Frames named `frame_0001.png`, `frame_0002.png`... in the directory.
+  pub fn frames_in_directory(&self, directory: &std::path::Path) -> Vec<Frame> {
+    (0..self.frame_count)
+      .map(|index| self.frame(index, directory.join(format!("frame_{:04}.png", index + 1))))
+      .collect()
+  }
+
+  pub fn frame(&self, index: u32, output_path: std::path::PathBuf) -> Frame {
+    let time_step_secs = 1.0 / self.frames_per_sec as f64;
+    Frame {
+      width: self.width,
+      height: self.height,
+      // Computed from the index, so that the error doesn't accumulate
+      elapsed_time_secs: (self.start_time_secs as f64 + index as f64 * time_step_secs) as f32,
+      elapsed_time_since_last_frame_secs: time_step_secs as f32,
+      frame_count: index + 1,
+      output_path,
+    }
+  }
+}
+
+#[test]
+fn test_frames_have_a_fixed_timestep() {
+  let sequence = Sequence {
+    width: 4,
+    height: 3,
+    start_time_secs: 1.0,
+    frames_per_sec: 4.0,
+    frame_count: 3,
+  };
+  let frames = sequence.frames_in_directory(std::path::Path::new("out"));
+  let times: Vec<_> = frames.iter().map(|frame| frame.elapsed_time_secs).collect();
+  assert_eq!(times, vec![1.0, 1.25, 1.5]);
+  assert_eq!(frames[2].elapsed_time_since_last_frame_secs, 0.25);
+  assert_eq!(frames[2].frame_count, 3);
+  assert_eq!(
+    frames[2].output_path,
+    std::path::Path::new("out/frame_0003.png")
+  );
+}
@@ -0,0 +1,5 @@
+//! Generates the MSL of shaders, without a window or a Metal device, for tools
+//! which only need the source.
+
+pub mod project_config;
+pub mod shader_compiler;
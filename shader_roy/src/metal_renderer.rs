@@ -0,0 +1,207 @@
+//! Renders shaders with Metal, into offscreen textures without a window, and
+//! the pipeline setup shared with the window.
+
+use super::frames::Frame;
+use super::project_config::{self, PixelFormat};
+use super::shader_compiler;
+use anyhow::{anyhow, Context, Result};
+use metal::*;
+use objc::rc::autoreleasepool;
+
+// Used for passing per-frame input to pixel shader and for setting up the vertex shader
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Float2 {
+  pub x: f32,
+  pub y: f32,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct Float4 {
+  pub x: f32,
+  pub y: f32,
+  pub z: f32,
+  pub w: f32,
+}
+#[repr(C)]
+pub struct Input {
+  pub window_size: Float2,
+  pub window_position: Float2,
+  pub cursor_position: Float2,
+  pub is_cursor_inside_window: bool,
+  pub elapsed_time_secs: f32,
+  pub elapsed_time_since_last_frame_secs: f32,
+  pub frame_count: f32,
+  pub year_month_day_tz: Float4,
+}
+
+pub fn is_available() -> bool {
+  Device::system_default().is_some()
+}
+
+/// Renders the frames to PNGs, with the same input as the CPU renderer.
+pub fn render_frames(shader_file_path: &std::path::Path, frames: &[Frame]) -> Result<()> {
+  let device = Device::system_default().context("No Metal device found")?;
+  let library = shader_compiler::compile_shader(shader_file_path, &device, |_| {})?;
+  let pixel_format = metal_pixel_format(project_config::load(shader_file_path)?.pixel_format);
+  let pipeline_state = prepare_pipeline_state(
+    &device,
+    &library,
+    "vertex_shader",
+    "fragment_shader",
+    pixel_format,
+  )
+  .map_err(|error| anyhow!("{}", error))?;
+  let vertex_buffer = new_vertex_buffer(&device);
+  let command_queue = device.new_command_queue();
+  for frame in frames {
+    let input = Input {
+      window_size: Float2 {
+        x: frame.width as f32,
+        y: frame.height as f32,
+      },
+      window_position: Float2 { x: 0.0, y: 0.0 },
+      cursor_position: Float2 { x: 0.0, y: 0.0 },
+      is_cursor_inside_window: false,
+      elapsed_time_secs: frame.elapsed_time_secs,
+      elapsed_time_since_last_frame_secs: frame.elapsed_time_since_last_frame_secs,
+      frame_count: frame.frame_count as f32,
+      year_month_day_tz: Float4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 0.0,
+      },
+    };
+    render_to_png(
+      &device,
+      &command_queue,
+      &pipeline_state,
+      &vertex_buffer,
+      &input,
+      pixel_format,
+      &frame.output_path,
+    )?;
+  }
+  Ok(())
+}
+
+/// Renders one frame into an offscreen texture of the input's window size,
+/// in the pipeline's pixel format.
+pub fn render_to_png(
+  device: &DeviceRef,
+  command_queue: &CommandQueueRef,
+  pipeline_state: &RenderPipelineStateRef,
+  vertex_buffer: &BufferRef,
+  input: &Input,
+  pixel_format: MTLPixelFormat,
+  output_path: &std::path::Path,
+) -> Result<()> {
+  let width = input.window_size.x as u64;
+  let height = input.window_size.y as u64;
+  autoreleasepool(|| {
+    let texture_descriptor = TextureDescriptor::new();
+    texture_descriptor.set_pixel_format(pixel_format);
+    texture_descriptor.set_width(width);
+    texture_descriptor.set_height(height);
+    texture_descriptor.set_storage_mode(MTLStorageMode::Managed);
+    texture_descriptor.set_usage(MTLTextureUsage::RenderTarget);
+    let texture = device.new_texture(&texture_descriptor);
+
+    let render_pass_descriptor = RenderPassDescriptor::new();
+    prepare_render_pass_descriptor(render_pass_descriptor, &texture);
+    let command_buffer = command_queue.new_command_buffer();
+    let encoder = command_buffer.new_render_command_encoder(render_pass_descriptor);
+    encoder.set_render_pipeline_state(pipeline_state);
+    encoder.set_vertex_buffer(0, Some(vertex_buffer), 0);
+    encoder.set_fragment_bytes(
+      0,
+      std::mem::size_of::<Input>() as u64,
+      input as *const Input as *const _,
+    );
+    encoder.draw_primitives_instanced(MTLPrimitiveType::TriangleStrip, 0, 4, 1);
+    encoder.end_encoding();
+    // Copies the rendered texture to memory readable by the CPU
+    let blit_encoder = command_buffer.new_blit_command_encoder();
+    blit_encoder.synchronize_resource(&texture);
+    blit_encoder.end_encoding();
+    command_buffer.commit();
+    command_buffer.wait_until_completed();
+
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    texture.get_bytes(
+      pixels.as_mut_ptr() as *mut _,
+      width * 4,
+      MTLRegion::new_2d(0, 0, width, height),
+      0,
+    );
+    // BGRA to RGBA, the sRGB format's bytes are already encoded
+    for pixel in pixels.chunks_mut(4) {
+      pixel.swap(0, 2);
+    }
+    write_png(output_path, width as u32, height as u32, &pixels)
+  })
+}
+
+fn write_png(path: &std::path::Path, width: u32, height: u32, pixels: &[u8]) -> Result<()> {
+  let file = std::fs::File::create(path).with_context(|| format!("Failed to create {:?}", path))?;
+  let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), width, height);
+  encoder.set_color(png::ColorType::RGBA);
+  encoder.set_depth(png::BitDepth::Eight);
+  encoder
+    .write_header()
+    .and_then(|mut writer| writer.write_image_data(pixels))
+    .with_context(|| format!("Failed to write {:?}", path))
+}
+
+// A rectangle covering the whole viewport
+pub fn new_vertex_buffer(device: &DeviceRef) -> Buffer {
+  let vector_rect = vec![Float4 {
+    x: -1.0,
+    y: -1.0,
+    z: 2.0,
+    w: 2.0,
+  }];
+
+  device.new_buffer_with_data(
+    vector_rect.as_ptr() as *const _,
+    std::mem::size_of::<Float4>() as u64,
+    MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+  )
+}
+
+pub fn metal_pixel_format(pixel_format: PixelFormat) -> MTLPixelFormat {
+  match pixel_format {
+    PixelFormat::Bgra8unorm => MTLPixelFormat::BGRA8Unorm,
+    PixelFormat::Bgra8unormSrgb => MTLPixelFormat::BGRA8Unorm_sRGB,
+  }
+}
+
+pub fn prepare_pipeline_state(
+  device: &DeviceRef,
+  library: &LibraryRef,
+  vertex_shader: &str,
+  fragment_shader: &str,
+  pixel_format: MTLPixelFormat,
+) -> Result<RenderPipelineState, Box<dyn std::error::Error>> {
+  let vert = library.get_function(vertex_shader, None)?;
+  let frag = library.get_function(fragment_shader, None)?;
+
+  let pipeline_state_descriptor = RenderPipelineDescriptor::new();
+  pipeline_state_descriptor.set_vertex_function(Some(&vert));
+  pipeline_state_descriptor.set_fragment_function(Some(&frag));
+  let attachment = pipeline_state_descriptor
+    .color_attachments()
+    .object_at(0)
+    .ok_or("No attachment")?;
+  attachment.set_pixel_format(pixel_format);
+  Ok(device.new_render_pipeline_state(&pipeline_state_descriptor)?)
+}
+
+pub fn prepare_render_pass_descriptor(descriptor: &RenderPassDescriptorRef, texture: &TextureRef) {
+  let color_attachment = descriptor.color_attachments().object_at(0).unwrap();
+  color_attachment.set_texture(Some(texture));
+  color_attachment.set_load_action(MTLLoadAction::Clear);
+  color_attachment.set_clear_color(MTLClearColor::new(0.0, 0.0, 0.0, 1.0));
+  color_attachment.set_store_action(MTLStoreAction::Store);
+}
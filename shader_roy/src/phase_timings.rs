@@ -0,0 +1,51 @@
+//! Reports how long compiling shaders takes, phase by phase.
+
+/// How long each phase of compiling took, in the order the phases first ran.
+/// Phases which ran more than once, like transpiling the interface and the
+/// shader, are summed.
+#[derive(Default)]
+pub struct PhaseTimings {
+  phases: Vec<(&'static str, std::time::Duration)>,
+}
+
+impl PhaseTimings {
+  pub fn add(&mut self, phase: &'static str, duration: std::time::Duration) {
+    match self.phases.iter_mut().find(|(name, _)| *name == phase) {
+      Some((_, total)) => *total += duration,
+      None => self.phases.push((phase, duration)),
+    }
+  }
+
+  pub fn total(&self) -> std::time::Duration {
+    self.phases.iter().map(|(_, duration)| *duration).sum()
+  }
+}
+
+impl std::fmt::Display for PhaseTimings {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    write!(f, "{:.1}ms", millis(self.total()))?;
+    for (index, (name, duration)) in self.phases.iter().enumerate() {
+      let separator = if index == 0 { " (" } else { ", " };
+      write!(f, "{}{} {:.1}ms", separator, name, millis(*duration))?;
+    }
+    if !self.phases.is_empty() {
+      write!(f, ")")?;
+    }
+    Ok(())
+  }
+}
+
+fn millis(duration: std::time::Duration) -> f64 {
+  duration.as_secs_f64() * 1000.0
+}
+
+#[test]
+fn test_phase_timings() {
+  let mut timings = PhaseTimings::default();
+  assert_eq!(timings.to_string(), "0.0ms");
+  let millis = std::time::Duration::from_millis;
+  timings.add("read", millis(2));
+  timings.add("parse", millis(3));
+  timings.add("parse", millis(4));
+  assert_eq!(timings.to_string(), "9.0ms (read 2.0ms, parse 7.0ms)");
+}
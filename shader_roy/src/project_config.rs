@@ -0,0 +1,176 @@
+//! Per-shader settings, from the `[package.metadata.shader_roy]` table of the
+//! shader crate's Cargo.toml. All settings are optional:
+//!
+//! ```toml
+//! [package.metadata.shader_roy]
+//! entry_point = "pixel_color"
+//! fps = 60
+//! debounce_millis = 1000
+//! pixel_format = "bgra8unorm_srgb"
+//!
+//! [package.metadata.shader_roy.window]
+//! width = 800
+//! height = 600
+//! x = 0
+//! y = 0
+//! always_on_top = false
+//! ```
+//!
+//! Shaders only receive `INPUT`, configuring more channels, like textures,
+//! isn't supported yet.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+  /// The function which returns the color of each pixel.
+  pub entry_point: String,
+  /// How often the window renders a frame.
+  pub fps: f64,
+  /// How long the window waits for more changes after a save before
+  /// recompiling.
+  pub debounce_millis: u64,
+  pub pixel_format: PixelFormat,
+  pub window: WindowConfig,
+}
+
+impl Default for ProjectConfig {
+  fn default() -> Self {
+    Self {
+      entry_point: "pixel_color".to_owned(),
+      fps: 60.0,
+      debounce_millis: 1000,
+      pixel_format: PixelFormat::Bgra8unorm,
+      window: WindowConfig::default(),
+    }
+  }
+}
+
+/// The format of the rendered frames.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PixelFormat {
+  /// 8 bits per channel, with the colors the shader returns.
+  Bgra8unorm,
+  /// 8 bits per channel, with the shader's colors treated as linear and
+  /// encoded as sRGB.
+  Bgra8unormSrgb,
+}
+
+/// The size and position are in logical pixels, by default the window takes
+/// up 40% of the screen in its bottom right corner.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+  pub width: Option<f64>,
+  pub height: Option<f64>,
+  pub x: Option<f64>,
+  pub y: Option<f64>,
+  pub always_on_top: bool,
+}
+
+impl Default for WindowConfig {
+  fn default() -> Self {
+    Self {
+      width: None,
+      height: None,
+      x: None,
+      y: None,
+      always_on_top: true,
+    }
+  }
+}
+
+/// The Cargo.toml of the crate the shader file is in.
+pub fn manifest_path(shader_file_path: &std::path::Path) -> Option<std::path::PathBuf> {
+  shader_file_path
+    .ancestors()
+    .skip(1)
+    .map(|directory| directory.join("Cargo.toml"))
+    .find(|manifest_path| manifest_path.is_file())
+}
+
+/// The config of the shader's crate, the defaults for shaders outside of
+/// crates.
+pub fn load(shader_file_path: &std::path::Path) -> Result<ProjectConfig> {
+  match manifest_path(shader_file_path) {
+    Some(manifest_path) => {
+      let manifest = std::fs::read_to_string(&manifest_path)
+        .with_context(|| format!("Failed to read {:?}", manifest_path))?;
+      parse(&manifest).with_context(|| format!("Invalid config in {:?}", manifest_path))
+    }
+    None => Ok(ProjectConfig::default()),
+  }
+}
+
+fn parse(manifest: &str) -> Result<ProjectConfig> {
+  let manifest = manifest.parse::<toml::Value>()?;
+  let config = match manifest
+    .get("package")
+    .and_then(|package| package.get("metadata"))
+    .and_then(|metadata| metadata.get("shader_roy"))
+  {
+    Some(config) => {
+      for unsupported in ["channels", "inputs"] {
+        if config.get(unsupported).is_some() {
+          bail!(
+            "`{}` isn't supported yet, shaders only receive `INPUT`",
+            unsupported
+          );
+        }
+      }
+      config.clone().try_into::<ProjectConfig>()?
+    }
+    None => ProjectConfig::default(),
+  };
+  if config.fps <= 0.0 {
+    bail!("`fps` must be positive");
+  }
+  Ok(config)
+}
+
+#[test]
+fn test_parse() {
+  let config = parse(
+    r#"
+      [package]
+      name = "shader"
+
+      [package.metadata.shader_roy]
+      entry_point = "color"
+      pixel_format = "bgra8unorm_srgb"
+
+      [package.metadata.shader_roy.window]
+      width = 800
+      always_on_top = false
+    "#,
+  )
+  .unwrap();
+  assert_eq!(
+    config,
+    ProjectConfig {
+      entry_point: "color".to_owned(),
+      pixel_format: PixelFormat::Bgra8unormSrgb,
+      window: WindowConfig {
+        width: Some(800.0),
+        always_on_top: false,
+        ..WindowConfig::default()
+      },
+      ..ProjectConfig::default()
+    }
+  );
+  assert_eq!(
+    parse("[package]\nname = \"shader\"").unwrap(),
+    ProjectConfig::default()
+  );
+  assert_eq!(
+    parse("[package.metadata.shader_roy]\nchannels = 4")
+      .unwrap_err()
+      .to_string(),
+    "`channels` isn't supported yet, shaders only receive `INPUT`"
+  );
+  assert!(parse("[package.metadata.shader_roy]\nframes = 4").is_err());
+  assert!(parse("[package.metadata.shader_roy]\nfps = 0").is_err());
+}
@@ -1,17 +1,71 @@
 use anyhow::{Context, Result};
 use path_absolutize::Absolutize;
 
-const ENTRY_POINT_FN_NAME: &str = "pixel_color";
-
 lazy_static::lazy_static! {
   pub static ref ROOT_PATH: std::path::PathBuf =
     std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-  pub static ref SHADER_PRELUDE_PATH: std::path::PathBuf =
-    ROOT_PATH.join("src/shader_prelude.metal");
   pub static ref SHADER_INTERFACE_PATH: std::path::PathBuf =
     ROOT_PATH.join("../shader_roy_metal_sl_interface/src/shader_roy_metal_sl_interface.rs");
+  pub static ref SHADER_PRELUDE_PATH: std::path::PathBuf =
+    ROOT_PATH.join("src/shader_prelude.metal");
 }
 
+pub fn transpile_config(entry_point: &str) -> rust_to_metal_sl::EnhanceConfig {
+  rust_to_metal_sl::EnhanceConfig {
+    entry_point_fn_name: entry_point.to_owned(),
+    constant_name: "INPUT".to_owned(),
+    param_type: "Input".to_owned(),
+  }
+}
+
+/// The shading languages the shader can be transpiled to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+  Msl,
+  Wgsl,
+  Glsl,
+}
+
+impl std::str::FromStr for Target {
+  type Err = anyhow::Error;
+
+  fn from_str(name: &str) -> Result<Self> {
+    match name {
+      "msl" => Ok(Target::Msl),
+      "wgsl" => Ok(Target::Wgsl),
+      "glsl" => Ok(Target::Glsl),
+      _ => anyhow::bail!("Unknown target `{}`, expected msl, wgsl or glsl", name),
+    }
+  }
+}
+
+/// Transpiles the shader, together with the interface, without compiling it.
+/// The MSL is what the window inserts into the prelude, the WGSL and GLSL
+/// also declare the `Input` struct.
+pub fn transpile_shader(shader_file_path: &std::path::Path, target: Target) -> Result<String> {
+  let config = transpile_config(&super::project_config::load(shader_file_path)?.entry_point);
+  let shader = read_shader_sources(shader_file_path)?;
+  let interface = std::fs::read_to_string(&*SHADER_INTERFACE_PATH)?;
+  Ok(match target {
+    Target::Msl => rust_to_metal_sl::format_msl(&format!(
+      "{}{}",
+      rust_to_metal_sl::transpile(&interface, &config)?,
+      rust_to_metal_sl::transpile(&shader, &config)?
+    )),
+    Target::Wgsl => rust_to_metal_sl::format_wgsl(&rust_to_metal_sl::transpile_with_backend(
+      &format!("{}{}", shader, interface),
+      &config,
+      &rust_to_metal_sl::WgslBackend,
+    )?),
+    Target::Glsl => rust_to_metal_sl::format_msl(&rust_to_metal_sl::transpile_with_backend(
+      &format!("{}{}", shader, interface),
+      &config,
+      &rust_to_metal_sl::GlslBackend,
+    )?),
+  })
+}
+
+#[cfg(target_os = "macos")]
 pub fn compile_shader<F>(
   shader_file_path: &std::path::Path,
   device: &metal::Device,
@@ -20,36 +74,83 @@ pub fn compile_shader<F>(
 where
   F: FnMut(String),
 {
+  let msl = generate_msl(shader_file_path)?;
+  on_compiled(msl.fragment_shader);
+  compile_msl(device, &msl.combined)
+}
+
+/// Compiles the `combined` MSL of `generate_msl` with Metal.
+#[cfg(target_os = "macos")]
+pub fn compile_msl(device: &metal::Device, msl: &str) -> Result<metal::Library> {
+  device
+    .new_library_with_source(msl, &metal::CompileOptions::new())
+    .map_err(anyhow::Error::msg)
+}
+
+pub struct ShaderMsl {
+  /// The complete source Metal compiles, the prelude with the transpiled
+  /// interface and shader in place of its `/// SHADER_RS` line.
+  pub combined: String,
+  /// The transpiled shader on its own.
+  pub fragment_shader: String,
+}
+
+/// The MSL of the shader, generated without a Metal device, so on any OS.
+pub fn generate_msl(shader_file_path: &std::path::Path) -> Result<ShaderMsl> {
+  generate_msl_with_phases(shader_file_path, &mut |_, _| Ok(()), &mut Vec::new())
+}
+
+/// Like `generate_msl`, calling `after_phase` with how long reading the
+/// sources and each of the transpiler's phases took. Stops with the error of
+/// `after_phase` when it fails. Adds the files it read, or failed to read, to
+/// `source_files`.
+pub fn generate_msl_with_phases(
+  shader_file_path: &std::path::Path,
+  after_phase: &mut dyn FnMut(&'static str, std::time::Duration) -> Result<()>,
+  source_files: &mut Vec<std::path::PathBuf>,
+) -> Result<ShaderMsl> {
+  let read_start_time = std::time::Instant::now();
+  // The entry point can change with the project config
+  source_files.extend(super::project_config::manifest_path(shader_file_path));
+  let project_config = super::project_config::load(shader_file_path)?;
+  source_files.push(SHADER_PRELUDE_PATH.clone());
   let shader_prelude = std::fs::read_to_string(&*SHADER_PRELUDE_PATH)?;
+  source_files.push(SHADER_INTERFACE_PATH.clone());
   let shader_interface = std::fs::read_to_string(&*SHADER_INTERFACE_PATH)?;
-  let combined_shader = {
-    let fragment_shader_in_rust = read_shader_sources(shader_file_path)?;
-    let config = rust_to_metal_sl::EnhanceConfig {
-      entry_point_fn_name: ENTRY_POINT_FN_NAME.to_owned(),
-      constant_name: "INPUT".to_owned(),
-      param_type: "Input".to_owned(),
-    };
-    let fragment_interface_in_msl = rust_to_metal_sl::transpile(&shader_interface, &config)?;
-    let fragment_shader_in_msl = rust_to_metal_sl::transpile(&fragment_shader_in_rust, &config)?;
-    let combined_shader =
-      // voca_rs::manipulate::replace(&shader_prelude, "/// SHADER_RS", &fragment_shader_in_msl);
-      shader_prelude.replace(
-          "SHADER_RS_ENTRYPOINT",
-          "pixel_color",
-        ).replace(
-          "/// SHADER_RS",
-          &format!("{}{}", fragment_interface_in_msl, fragment_shader_in_msl),
-        );
-    on_compiled(fragment_shader_in_msl);
-    combined_shader
+  let fragment_shader_in_rust = read_shader_sources_into(shader_file_path, source_files)?;
+  after_phase("read", read_start_time.elapsed())?;
+  let config = transpile_config(&project_config.entry_point);
+  let mut transpile = |rust_source: &str| {
+    rust_to_metal_sl::transpile_with_phases(
+      rust_source,
+      &config,
+      &rust_to_metal_sl::MslBackend,
+      &mut |phase, duration| after_phase(phase.name(), duration),
+    )
   };
-  let library = device
-    .new_library_with_source(&combined_shader, &metal::CompileOptions::new())
-    .map_err(anyhow::Error::msg)?;
-  Ok(library)
+  let fragment_interface_in_msl = transpile(&shader_interface)?;
+  let fragment_shader_in_msl = transpile(&fragment_shader_in_rust)?;
+  Ok(ShaderMsl {
+    combined: shader_prelude.replace(
+      "/// SHADER_RS",
+      &format!("{}{}", fragment_interface_in_msl, fragment_shader_in_msl),
+    ),
+    fragment_shader: fragment_shader_in_msl,
+  })
+}
+
+// Inlines the files of `mod x;` declarations
+pub fn read_shader_sources(shader_file_path: &std::path::Path) -> Result<String> {
+  read_shader_sources_into(shader_file_path, &mut Vec::new())
 }
 
-fn read_shader_sources(shader_file_path: &std::path::Path) -> Result<String> {
+/// Like `read_shader_sources`, adding the files it read, or failed to read,
+/// to `source_files`.
+pub fn read_shader_sources_into(
+  shader_file_path: &std::path::Path,
+  source_files: &mut Vec<std::path::PathBuf>,
+) -> Result<String> {
+  source_files.push(shader_file_path.to_owned());
   let fragment_shader_in_rust = std::fs::read_to_string(shader_file_path).with_context(|| {
     format!(
       "Failed to read shader from `{:?}`",
@@ -65,11 +166,52 @@ fn read_shader_sources(shader_file_path: &std::path::Path) -> Result<String> {
   use regex_try::RegexTry;
   MODULE_DECLARATION
     .try_replace_all(&fragment_shader_in_rust, |captured: &regex::Captures| {
-      read_shader_sources(
+      read_shader_sources_into(
         &shader_directory
           .join(&captured["module_name"])
           .with_extension("rs"),
+        source_files,
       )
     })
     .map(|result| result.into_owned())
 }
+
+#[test]
+fn test_read_shader_sources_reports_files() {
+  let directory = std::env::temp_dir().join(format!("shader_roy_sources_{}", std::process::id()));
+  std::fs::create_dir_all(&directory).unwrap();
+  std::fs::write(directory.join("main.rs"), "mod helpers;\nmod missing;").unwrap();
+  std::fs::write(directory.join("helpers.rs"), "fn helper() {}").unwrap();
+  let mut source_files = Vec::new();
+  let result = read_shader_sources_into(&directory.join("main.rs"), &mut source_files);
+  std::fs::remove_dir_all(&directory).unwrap();
+  assert!(result.is_err());
+  assert_eq!(
+    source_files,
+    vec![
+      directory.join("main.rs"),
+      directory.join("helpers.rs"),
+      directory.join("missing.rs"),
+    ]
+  );
+}
+
+#[test]
+fn test_generate_msl() {
+  // Outside of the example's crate, with the default entry point
+  let directory = std::env::temp_dir().join(format!("shader_roy_msl_{}", std::process::id()));
+  std::fs::create_dir_all(&directory).unwrap();
+  let path = directory.join("simple.rs");
+  std::fs::copy(ROOT_PATH.join("../examples/simple/simple.rs"), &path).unwrap();
+  let mut source_files = Vec::new();
+  let msl = generate_msl_with_phases(&path, &mut |_, _| Ok(()), &mut source_files);
+  std::fs::remove_dir_all(&directory).unwrap();
+  let msl = msl.unwrap();
+  assert!(msl.combined.starts_with("#include <metal_stdlib>"));
+  assert!(!msl.combined.contains("/// SHADER_RS"));
+  assert!(msl.combined.contains("struct Input {"));
+  assert!(msl.combined.contains(&msl.fragment_shader));
+  assert!(msl.combined.contains("vertex float4 vertex_shader("));
+  assert!(source_files.contains(&path));
+  assert!(source_files.contains(&SHADER_PRELUDE_PATH));
+}
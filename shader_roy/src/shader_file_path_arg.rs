@@ -6,11 +6,19 @@ lazy_static::lazy_static! {
     std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
 }
 
-pub fn get_path() -> Result<std::path::PathBuf> {
-  let args: Vec<String> = std::env::args().collect();
-  get_path_for_argument(args.get(1).ok_or_else(|| {
-    anyhow!("Cannot start ShaderRoy: Missing shader entry point file path argument")
-  })?)
+/// The directories in `examples`, which are accepted by name.
+pub fn example_names() -> Vec<String> {
+  let mut names: Vec<String> = std::fs::read_dir(ROOT_PATH.join("../examples"))
+    .map(|entries| {
+      entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect()
+    })
+    .unwrap_or_default();
+  names.sort();
+  names
 }
 
 pub fn get_path_for_argument(argument: &str) -> Result<std::path::PathBuf> {
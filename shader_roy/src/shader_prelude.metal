@@ -13,14 +13,6 @@ vertex float4 vertex_shader(
     return float4(rect_vert(*clear_rect, vertex_index), 0, 1);
 }
 
-// The fragment shader
-fragment float4 fragment_shader(
-    float4 in [[position]],
-    constant Input& input [[ buffer(0) ]]
-) {
-    return SHADER_RS_ENTRYPOINT(float2(in.x, in.y), input);
-}
-
 // Helper for computing pixel position in vertext shader
 float2 rect_vert(float4 rect, uint vertex_index) {
     float left = rect.x;
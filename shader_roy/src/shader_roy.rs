@@ -1,566 +1,737 @@
-// Copyright 2016 metal-rs developers
-//
-// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
-// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
-// http://opensource.org/licenses/MIT>, at your option. This file may not be
-// copied, modified, or distributed except according to those terms.
-
+#[cfg(target_os = "macos")]
 extern crate objc;
 
-mod shader_compiler;
+// Only the window, which needs macOS, takes commands, reads the clock and the timeline
+// and shows compile errors and timings
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod cli_commands;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod clock;
+#[cfg(target_os = "macos")]
+mod compile_worker;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod control_server;
+mod cpu_renderer;
+mod differential;
+#[cfg(target_os = "macos")]
+mod error_overlay;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod error_text;
+mod export;
+mod frames;
+#[cfg(target_os = "macos")]
+mod metal_renderer;
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod phase_timings;
 mod shader_file_path_arg;
-
-use anyhow::{Context, Result};
-
-use cocoa::{appkit::NSView, base::id as cocoa_id};
-
-use metal::*;
-use objc::{rc::autoreleasepool, runtime::YES};
-use std::mem;
-use winit::platform::macos::WindowExtMacOS;
-
-use winit::{
-    event::{Event, WindowEvent},
-    event_loop::ControlFlow,
-};
-
-// Used for passing per-frame input to pixel shader and for setting up the vertex shader
-#[repr(C)]
-#[derive(Copy, Clone)]
-struct Float2 {
-    pub x: f32,
-    pub y: f32,
-}
-#[repr(C)]
-#[derive(Copy, Clone)]
-struct Float4 {
-    pub x: f32,
-    pub y: f32,
-    pub z: f32,
-    pub w: f32,
-}
-#[repr(C)]
-struct Input {
-    window_size: Float2,
-    window_position: Float2,
-    cursor_position: Float2,
-    is_cursor_inside_window: bool,
-    elapsed_time_secs: f32,
-    elapsed_time_since_last_frame_secs: f32,
-    frame_count: f32,
-    year_month_day_tz: Float4,
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+mod timeline;
+#[cfg(target_os = "macos")]
+mod window;
+
+use anyhow::{anyhow, bail, Context, Result};
+// Also used by other tools, to generate the shader's MSL
+use shader_roy::{project_config, shader_compiler};
+
+// Exit codes, for scripts
+const EXIT_FAILURE: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let command_name = args.get(1).map(String::as_str);
+    let command = COMMANDS
+        .iter()
+        .find(|command| Some(command.name) == command_name);
+    let result = match (command_name, command) {
+        (None, _) => Err(UsageError(help()).into()),
+        (Some("help" | "--help" | "-h"), _) => print_help(args.get(2).map(String::as_str)),
+        (_, Some(command)) if args[2..].iter().any(|arg| arg == "--help" || arg == "-h") => {
+            print_help(Some(command.name))
+        }
+        (_, Some(command)) => (command.run)(&args[2..]),
+        // `shader_roy <path>` is short for `shader_roy run <path>`
+        (Some(_), None) => run_window(&args[1..]),
+    };
+    if let Err(error) = result {
+        if error.is::<UsageError>() {
+            eprintln!("Error: {}", error);
+            std::process::exit(EXIT_USAGE);
+        }
+        eprintln!("Error: {:?}", error);
+        std::process::exit(EXIT_FAILURE);
+    }
 }
 
-enum CLICommand {
-    Pause,
-    Restart,
-    Run(String),
+struct Command {
+    name: &'static str,
+    summary: &'static str,
+    usage: &'static str,
+    options: &'static str,
+    run: fn(&[String]) -> Result<()>,
 }
 
-fn main() -> Result<()> {
-    let events_loop = winit::event_loop::EventLoop::new();
-    let (window_size, window_position) = window_sizing((0.4, 0.4), &events_loop);
-    let window = winit::window::WindowBuilder::new()
-        .with_inner_size(window_size)
-        .with_outer_position(window_position)
-        .with_title("ShaderRoy")
-        .with_always_on_top(true)
-        .build(&events_loop)
-        .unwrap();
-
-    let device = Device::system_default().expect("no device found");
-
-    let layer = MetalLayer::new();
-    layer.set_device(&device);
-    layer.set_pixel_format(MTLPixelFormat::BGRA8Unorm);
-    layer.set_presents_with_transaction(false);
-
-    unsafe {
-        let view = window.ns_view() as cocoa_id;
-        view.setWantsLayer(YES);
-        view.setLayer(mem::transmute(layer.as_ref()));
+const COMMANDS: &[Command] = &[
+    Command {
+        name: "run",
+        summary: "Opens the shader in a window, which recompiles it on changes (macOS)",
+        usage: RUN_USAGE,
+        options: "  --width, --height <pixels>  The window size, overrides the project config
+  --fps <frames per sec>      How often the window renders, overrides the project config
+  --paused                    Starts paused at 0 seconds
+  --no-always-on-top          Lets other windows cover the window
+  --clock <clock>             `wall` time (the default), `fixed` time steps per frame
+                              or a `manual` time
+  --control <address>         Starts a control server on a Unix socket or a TCP port
+  --debounce <millis>         How long to wait for more changes before recompiling",
+        run: run_window,
+    },
+    Command {
+        name: "transpile",
+        summary: "Prints the shader in a shading language, without compiling it",
+        usage: TRANSPILE_USAGE,
+        options: "  --target <language>         `msl` (the default), `wgsl` or `glsl`
+  --combined                  Prints the complete MSL Metal compiles, with the prelude
+  --output, -o <file>         Writes to the file instead of printing",
+        run: transpile,
+    },
+    Command {
+        name: "check",
+        summary: "Checks that the shader transpiles and compiles, without rendering it",
+        usage: CHECK_USAGE,
+        options: "",
+        run: check,
+    },
+    Command {
+        name: "render",
+        summary: "Renders frames to PNGs, with Metal or on the CPU",
+        usage: RENDER_USAGE,
+        options: "  --output, -o <path>         A PNG for one frame, a directory for more (`frame.png` or `.`)
+  --size <width>x<height>     The frame size (800x600)
+  --start <secs>              The time of the first frame (0)
+  --fps <frames per sec>      The time between frames (60)
+  --frames <count>            How many frames to render (1)
+  --cpu                       Runs the Rust source on the CPU instead of Metal",
+        run: render,
+    },
+    Command {
+        name: "export",
+        summary: "Exports an animation as a GIF or a Y4M video",
+        usage: EXPORT_USAGE,
+        options: "  --gif, --y4m <file>         The outputs, at least one is needed
+  --seconds <secs>            The length of the animation (5)
+  --fps <frames per sec>      The frame rate (30)
+  --size <width>x<height>     The frame size (400x300)
+  --start <secs>              The time of the first frame (0)
+  --cpu                       Runs the Rust source on the CPU instead of Metal",
+        run: export,
+    },
+    Command {
+        name: "diff",
+        summary: "Checks that the transpiled shaders compute the same colors as the Rust source",
+        usage: DIFF_USAGE,
+        options: "  --size <width>x<height>     The window size the shaders run in (800x600)
+  --grid <columns>x<rows>     How many pixels to compare (16x12)
+  --times <secs>,...          The times to compare at (0,1,2.5)
+  --tolerance <max error>     The largest difference of a color channel (1/255)",
+        run: diff,
+    },
+    Command {
+        name: "list",
+        summary: "Lists the examples, which the other commands accept by name",
+        usage: LIST_USAGE,
+        options: "",
+        run: list,
+    },
+];
+
+fn help() -> String {
+    let mut help = "Usage: shader_roy <command> [<args>]\n\nCommands:\n".to_owned();
+    for command in COMMANDS {
+        help += &format!("  {:<10}{}\n", command.name, command.summary);
     }
-
-    let draw_size = window.inner_size();
-    layer.set_drawable_size(CGSize::new(draw_size.width as f64, draw_size.height as f64));
-
-    let vector_rect = vec![Float4 {
-        x: -1.0,
-        y: -1.0,
-        z: 2.0,
-        w: 2.0,
-    }];
-
-    let vector_buffer = device.new_buffer_with_data(
-        vector_rect.as_ptr() as *const _,
-        mem::size_of::<Float4>() as u64,
-        MTLResourceOptions::CPUCacheModeDefaultCache | MTLResourceOptions::StorageModeManaged,
+    help += &format!(
+        "  {:<10}Prints the help of a command\n\n\
+        `shader_roy <path>` is short for `shader_roy run <path>`. \
+        A path can be a shader file, a crate directory or the name of an example.\n\n\
+        Exits with {} when the shader fails and {} when the arguments are invalid.",
+        "help", EXIT_FAILURE, EXIT_USAGE
     );
+    help
+}
 
-    let command_queue = device.new_command_queue();
-    let mut run_error: Option<()> = None;
-
-    let mut shader_file_path = shader_file_path_arg::get_path()?;
-    let mut shader_files_watcher = ShaderFilesWatcher::new(std::time::Duration::from_secs(1))?;
-    shader_files_watcher.watch_directory(&shader_file_path)?;
-    let cli_commands_watcher = CLICommandsWatcher::new();
-    let mut pipeline_state: Option<RenderPipelineState> = None;
-    let mut frame_rate_reporter = FrameRateReporter::new();
-    let mut input_computer = InputComputer::new();
-
-    events_loop.run(move |event, _, control_flow| {
-        autoreleasepool(|| {
-            let res = (|| -> Result<(), Box<dyn std::error::Error>> {
-                *control_flow = ControlFlow::WaitUntil(
-                    std::time::Instant::now() + std::time::Duration::from_secs_f64(1.0 / 60.0),
-                );
-
-                match event {
-                    Event::WindowEvent { event, .. } => match event {
-                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                        WindowEvent::Resized(size) => {
-                            layer.set_drawable_size(CGSize::new(
-                                size.width as f64,
-                                size.height as f64,
-                            ));
-                        }
-                        WindowEvent::Focused(is_focused) => {
-                            input_computer.set_is_focused(is_focused);
-                        }
-                        WindowEvent::CursorEntered { .. } => {
-                            input_computer.set_cursor_presence(true);
-                        }
-                        WindowEvent::CursorLeft { .. } => {
-                            input_computer.set_cursor_presence(false);
-                        }
-                        WindowEvent::CursorMoved { position, .. } => {
-                            input_computer.set_cursor_position(position);
-                        }
-                        WindowEvent::MouseInput { state, button, .. } => {
-                            input_computer.set_cursor_click_state(button, state)
-                        }
-                        _ => (),
-                    },
-                    Event::MainEventsCleared => {
-                        window.request_redraw();
-                    }
-                    Event::RedrawRequested(_) => {
-                        match cli_commands_watcher.try_recv() {
-                            Some(CLICommand::Pause) => {
-                                input_computer.toggle_paused();
-                                CLICommandsWatcher::print_prompt();
-                            }
-                            Some(CLICommand::Restart) => {
-                                input_computer.reset();
-                                CLICommandsWatcher::print_prompt();
-                            }
-                            Some(CLICommand::Run(new_path)) => {
-                                shader_file_path =
-                                    shader_file_path_arg::get_path_for_argument(&new_path)?;
-                                shader_files_watcher.watch_directory(&shader_file_path)?;
-                                pipeline_state = None;
-                                run_error = None;
-                                input_computer.reset();
-                            }
-                            None => {}
-                        }
-                        if input_computer.is_paused() {
-                            return Ok(());
-                        }
-
-                        if pipeline_state.is_none() && run_error.is_none()
-                            || shader_files_watcher.has_changes()
-                        {
-                            let library = shader_compiler::compile_shader(
-                                &shader_file_path,
-                                &device,
-                                |fragment_shader_in_msl| {
-                                    println!("{}", fragment_shader_in_msl);
-                                    CLICommandsWatcher::print_instructions();
-                                },
-                            )?;
-                            pipeline_state = Some(prepare_pipeline_state(
-                                &device,
-                                &library,
-                                "vertex_shader",
-                                "fragment_shader",
-                            )?);
-                        }
-                        if pipeline_state.is_none() {
-                            return Ok(());
-                        }
-
-                        let render_pass_descriptor = RenderPassDescriptor::new();
-
-                        let drawable = layer.next_drawable().ok_or("No drawable")?;
-                        prepare_render_pass_descriptor(&render_pass_descriptor, drawable.texture());
-
-                        let command_buffer = command_queue.new_command_buffer();
-                        let encoder =
-                            command_buffer.new_render_command_encoder(&render_pass_descriptor);
-
-                        encoder.set_render_pipeline_state(pipeline_state.as_ref().unwrap());
-                        encoder.set_vertex_buffer(0, Some(&vector_buffer), 0);
-                        encoder.set_fragment_bytes(
-                            0,
-                            std::mem::size_of::<Input>() as u64,
-                            &input_computer.current_input(&window) as *const Input as *const _,
-                        );
-                        encoder.draw_primitives_instanced(
-                            metal::MTLPrimitiveType::TriangleStrip,
-                            0,
-                            4,
-                            1,
-                        );
-
-                        encoder.end_encoding();
-                        command_buffer.present_drawable(&drawable);
-                        command_buffer.commit();
-
-                        frame_rate_reporter.calculate_frame_rate_and_maybe_report(&window);
-                    }
-                    _ => {}
-                };
-                Ok(())
-            })();
-            if let Err(err) = res {
-                println!("{:?}", err);
-                CLICommandsWatcher::print_instructions();
-                run_error = Some(());
+// `shader_roy help [<command>]`
+fn print_help(command_name: Option<&str>) -> Result<()> {
+    match command_name {
+        None => println!("{}", help()),
+        Some(name) => {
+            let command = COMMANDS
+                .iter()
+                .find(|command| command.name == name)
+                .ok_or_else(|| UsageError(format!("Unknown command `{}`\n{}", name, help())))?;
+            println!("{}\n\n{}", command.summary, command.usage);
+            if !command.options.is_empty() {
+                println!("\nOptions:\n{}", command.options);
             }
-        });
-    });
+        }
+    }
+    Ok(())
 }
 
-struct CLICommandsWatcher {
-    #[allow(dead_code)] // Required to avoid dropping child
-    watcher_thread: std::thread::JoinHandle<()>,
-    receiver: std::sync::mpsc::Receiver<CLICommand>,
+// Invalid arguments, as opposed to shaders which failed
+#[derive(Debug)]
+struct UsageError(String);
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str(&self.0)
+    }
 }
 
-impl CLICommandsWatcher {
-    fn new() -> Self {
-        let (tx, receiver) = std::sync::mpsc::channel();
-        let watcher_thread = std::thread::spawn(move || loop {
-            let mut cli_input = String::new();
-            let _ = std::io::stdin().read_line(&mut cli_input);
-            let event = match &cli_input[0..1] {
-                "p" => CLICommand::Pause,
-                "r" => match &cli_input[1..2] {
-                    " " => CLICommand::Run(cli_input[2..cli_input.len() - 1].to_owned()),
-                    _ => CLICommand::Restart,
-                },
-                _ => {
-                    continue;
-                }
-            };
-            tx.send(event).unwrap();
-        });
-
-        CLICommandsWatcher {
-            watcher_thread,
-            receiver,
+impl std::error::Error for UsageError {}
+
+fn usage_error(message: impl std::fmt::Display, usage: &str) -> anyhow::Error {
+    UsageError(format!("{}\n{}", message, usage)).into()
+}
+
+const RUN_USAGE: &str = "Usage: shader_roy run <path> [--width <pixels>] [--height <pixels>] \
+    [--fps <frames per sec>] [--paused] [--no-always-on-top] \
+    [--clock wall|fixed[:<frames per sec>]|manual[:<secs>]] [--control <socket path or host:port>] \
+    [--debounce <millis>]";
+
+// `shader_roy run <path> ...`
+fn run_window(args: &[String]) -> Result<()> {
+    let mut shader_path = None;
+    let mut clock: Box<dyn clock::Clock> = Box::new(clock::WallClock::new());
+    let mut control_address = None;
+    let mut debounce_millis = None;
+    let mut width = None;
+    let mut height = None;
+    let mut fps = None;
+    let mut is_paused = false;
+    let mut is_always_on_top = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("Missing value for `{}`", arg), RUN_USAGE))
+        };
+        match arg.as_str() {
+            "--clock" => {
+                clock = clock::parse(value()?).map_err(|error| usage_error(error, RUN_USAGE))?
+            }
+            "--control" => control_address = Some(value()?.as_str()),
+            "--debounce" => debounce_millis = Some(parse_number(value()?)?),
+            "--width" => width = Some(parse_number(value()?)?),
+            "--height" => height = Some(parse_number(value()?)?),
+            "--fps" => fps = Some(parse_number(value()?)?),
+            "--paused" => is_paused = true,
+            "--no-always-on-top" => is_always_on_top = false,
+            _ if shader_path.is_none() && !arg.starts_with('-') => shader_path = Some(arg),
+            _ => {
+                return Err(usage_error(
+                    format!("Unexpected argument `{}`", arg),
+                    RUN_USAGE,
+                ))
+            }
         }
     }
-
-    fn try_recv(&self) -> Option<CLICommand> {
-        self.receiver.try_recv().ok()
+    let shader_path = shader_path.ok_or_else(|| {
+        usage_error(
+            "Cannot start ShaderRoy: Missing shader entry point file path argument",
+            RUN_USAGE,
+        )
+    })?;
+    if matches!(fps, Some(fps) if fps <= 0.0) {
+        return Err(usage_error("The frame rate must be positive", RUN_USAGE));
     }
-
-    fn print_instructions() {
-        println!("[p] to pause, [r] to restart, [r <path>] to run another shader ");
-        Self::print_prompt();
+    let mut config =
+        project_config::load(&shader_file_path_arg::get_path_for_argument(shader_path)?)?;
+    // Flags override the project config
+    if let Some(debounce_millis) = debounce_millis {
+        config.debounce_millis = debounce_millis;
     }
+    config.window.width = width.or(config.window.width);
+    config.window.height = height.or(config.window.height);
+    config.fps = fps.unwrap_or(config.fps);
+    config.window.always_on_top &= is_always_on_top;
+    open_window(shader_path, clock, control_address, &config, is_paused)
+}
 
-    fn print_prompt() {
-        print!("> ");
-        use std::io::Write;
-        let _ = std::io::stdout().flush();
-    }
+#[cfg(target_os = "macos")]
+fn open_window(
+    shader_path: &str,
+    clock: Box<dyn clock::Clock>,
+    control_address: Option<&str>,
+    config: &project_config::ProjectConfig,
+    is_paused: bool,
+) -> Result<()> {
+    window::run(shader_path, clock, control_address, config, is_paused)
 }
 
-struct InputComputer {
-    start_time: std::time::Instant,
-    last_frame_time: std::time::Instant,
-    frame_count: u64,
-    is_window_focused: bool,
-    is_cursor_inside_window: bool,
-    cursor_position: Float2,
-    pointer_device_state: Float2,
-    date: Float4,
-    pause_time: Option<std::time::Instant>,
+#[cfg(not(target_os = "macos"))]
+fn open_window(
+    _shader_path: &str,
+    _clock: Box<dyn clock::Clock>,
+    _control_address: Option<&str>,
+    _config: &project_config::ProjectConfig,
+    _is_paused: bool,
+) -> Result<()> {
+    bail!(
+        "The ShaderRoy window needs Metal, which is only available on macOS. \
+        Use `shader_roy render <path>` to render the shader to a PNG instead."
+    )
 }
 
-impl InputComputer {
-    fn new() -> Self {
-        Self {
-            start_time: std::time::Instant::now(),
-            last_frame_time: std::time::Instant::now(),
-            frame_count: 0,
-            is_window_focused: true,
-            is_cursor_inside_window: false,
-            cursor_position: Float2 { x: 0.0, y: 0.0 },
-            pointer_device_state: Float2 { x: 0.0, y: 0.0 },
-            date: current_date_as_float4(),
-            pause_time: None,
+const TRANSPILE_USAGE: &str =
+    "Usage: shader_roy transpile <path> [--target msl|wgsl|glsl] [--combined] [--output <file>]";
+
+// `shader_roy transpile <path> ...`
+fn transpile(args: &[String]) -> Result<()> {
+    let mut shader_path = None;
+    let mut target = shader_compiler::Target::Msl;
+    let mut is_combined = false;
+    let mut output_path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("Missing value for `{}`", arg), TRANSPILE_USAGE))
+        };
+        match arg.as_str() {
+            "--target" => {
+                target = value()?
+                    .parse()
+                    .map_err(|error| usage_error(error, TRANSPILE_USAGE))?
+            }
+            "--combined" => is_combined = true,
+            "--output" | "-o" => output_path = Some(std::path::PathBuf::from(value()?)),
+            _ if shader_path.is_none() && !arg.starts_with('-') => shader_path = Some(arg),
+            _ => {
+                return Err(usage_error(
+                    format!("Unexpected argument `{}`", arg),
+                    TRANSPILE_USAGE,
+                ))
+            }
         }
     }
-
-    fn reset(&mut self) {
-        self.start_time = std::time::Instant::now();
-        self.last_frame_time = std::time::Instant::now();
-        self.frame_count = 0;
-        self.date = current_date_as_float4();
-        self.pause_time = None;
+    let shader_path =
+        shader_path.ok_or_else(|| usage_error("Missing shader path", TRANSPILE_USAGE))?;
+    if is_combined && target != shader_compiler::Target::Msl {
+        return Err(usage_error(
+            "Only the MSL can be combined with the prelude",
+            TRANSPILE_USAGE,
+        ));
     }
-
-    fn toggle_paused(&mut self) {
-        if let Some(pause_time) = self.pause_time {
-            let pause_duration = pause_time.elapsed();
-            self.start_time += pause_duration;
-            self.last_frame_time = std::time::Instant::now();
-            self.pause_time = None;
-        } else {
-            self.pause_time = Some(std::time::Instant::now());
-        }
+    let shader_file_path = shader_file_path_arg::get_path_for_argument(shader_path)?;
+    let shader = if is_combined {
+        shader_compiler::generate_msl(&shader_file_path)?.combined
+    } else {
+        shader_compiler::transpile_shader(&shader_file_path, target)?
+    };
+    match output_path {
+        Some(output_path) => std::fs::write(&output_path, shader)
+            .with_context(|| format!("Failed to write {:?}", output_path))?,
+        None => print!("{}", shader),
     }
+    Ok(())
+}
 
-    fn is_paused(&self) -> bool {
-        self.pause_time.is_some()
-    }
+const CHECK_USAGE: &str = "Usage: shader_roy check <path>...";
 
-    fn set_is_focused(&mut self, is_focused: bool) {
-        self.is_window_focused = is_focused;
+// `shader_roy check <path>...`
+fn check(args: &[String]) -> Result<()> {
+    let mut shader_paths = Vec::new();
+    for arg in args {
+        if arg.starts_with('-') {
+            return Err(usage_error(
+                format!("Unexpected argument `{}`", arg),
+                CHECK_USAGE,
+            ));
+        }
+        shader_paths.push(arg);
     }
-
-    fn set_cursor_presence(&mut self, is_present: bool) {
-        self.is_cursor_inside_window = self.is_window_focused && is_present;
+    if shader_paths.is_empty() {
+        return Err(usage_error("Missing shader path", CHECK_USAGE));
     }
-
-    fn set_cursor_position(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
-        self.is_cursor_inside_window = true;
-        self.cursor_position = Float2 {
-            x: position.x as f32,
-            y: position.y as f32,
+    let mut failed = Vec::new();
+    for shader_path in shader_paths {
+        println!("{}", shader_path);
+        let shader_file_path = match shader_file_path_arg::get_path_for_argument(shader_path) {
+            Ok(shader_file_path) => shader_file_path,
+            Err(error) => {
+                report_check("path", Err(error));
+                failed.push(shader_path.as_str());
+                continue;
+            }
         };
-    }
-
-    fn set_cursor_click_state(
-        &mut self,
-        button: winit::event::MouseButton,
-        state: winit::event::ElementState,
-    ) {
-        if !self.is_cursor_inside_window {
-            return;
+        let mut is_ok = true;
+        for target_name in &["msl", "wgsl", "glsl"] {
+            let result = shader_compiler::transpile_shader(&shader_file_path, target_name.parse()?)
+                .and_then(|shader| {
+                    if *target_name == "wgsl" {
+                        validate_wgsl(&shader)?;
+                    }
+                    Ok(())
+                });
+            is_ok &= report_check(target_name, result.map(|_| "OK"));
         }
-        let state_value = match state {
-            winit::event::ElementState::Pressed => 1.0,
-            winit::event::ElementState::Released => 0.0,
-        };
-        match button {
-            winit::event::MouseButton::Left => {
-                self.pointer_device_state.x = state_value;
-            }
-            winit::event::MouseButton::Right => {
-                self.pointer_device_state.y = state_value;
-            }
-            _ => {}
+        is_ok &= report_check("metal", check_with_metal(&shader_file_path));
+        if !is_ok {
+            failed.push(shader_path.as_str());
         }
     }
-
-    fn current_input(&mut self, window: &winit::window::Window) -> Input {
-        let physical_size = window.inner_size();
-        let physical_position = window.inner_position().unwrap();
-        self.frame_count += 1;
-        let result = Input {
-            window_size: Float2 {
-                x: physical_size.width as f32,
-                y: physical_size.height as f32,
-            },
-            window_position: Float2 {
-                x: physical_position.x as f32,
-                y: physical_position.y as f32,
-            },
-            cursor_position: self.cursor_position,
-            is_cursor_inside_window: self.is_cursor_inside_window,
-            elapsed_time_since_last_frame_secs: self.last_frame_time.elapsed().as_secs_f32(),
-            elapsed_time_secs: self.start_time.elapsed().as_secs_f32(),
-            frame_count: self.frame_count as f32,
-            year_month_day_tz: self.date,
-        };
-        self.last_frame_time = std::time::Instant::now();
-        result
+    if !failed.is_empty() {
+        bail!("Failed to compile: {}", failed.join(", "));
     }
+    Ok(())
 }
 
-fn current_date_as_float4() -> Float4 {
-    let today = chrono::prelude::Local::now();
-    use chrono::Datelike;
-    Float4 {
-        x: today.year() as f32,
-        y: today.month() as f32,
-        z: today.day() as f32,
-        w: today.offset().local_minus_utc() as f32,
+// Prints the result and returns whether it's OK
+fn report_check(target_name: &str, result: Result<&str>) -> bool {
+    match result {
+        Ok(message) => {
+            println!("  {}: {}", target_name, message);
+            true
+        }
+        Err(error) => {
+            println!("  {}: {:#}", target_name, error);
+            false
+        }
     }
 }
 
-fn prepare_pipeline_state(
-    device: &DeviceRef,
-    library: &LibraryRef,
-    vertex_shader: &str,
-    fragment_shader: &str,
-) -> Result<RenderPipelineState, Box<dyn std::error::Error>> {
-    let vert = library.get_function(vertex_shader, None)?;
-    let frag = library.get_function(fragment_shader, None)?;
-
-    let pipeline_state_descriptor = RenderPipelineDescriptor::new();
-    pipeline_state_descriptor.set_vertex_function(Some(&vert));
-    pipeline_state_descriptor.set_fragment_function(Some(&frag));
-    let attachment = pipeline_state_descriptor
-        .color_attachments()
-        .object_at(0)
-        .ok_or("No attachment")?;
-    attachment.set_pixel_format(MTLPixelFormat::BGRA8Unorm);
-    Ok(device.new_render_pipeline_state(&pipeline_state_descriptor)?)
-}
-
-fn prepare_render_pass_descriptor(descriptor: &RenderPassDescriptorRef, texture: &TextureRef) {
-    let color_attachment = descriptor.color_attachments().object_at(0).unwrap();
-    color_attachment.set_texture(Some(texture));
-    color_attachment.set_load_action(MTLLoadAction::Clear);
-    color_attachment.set_clear_color(MTLClearColor::new(0.0, 0.0, 0.0, 1.0));
-    color_attachment.set_store_action(MTLStoreAction::Store);
+// Type checks the shader like wgpu would
+fn validate_wgsl(wgsl: &str) -> Result<()> {
+    let module = naga::front::wgsl::parse_str(wgsl)
+        .map_err(|error| anyhow!(error.emit_to_string(wgsl).trim_end().to_owned()))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|error| anyhow!(error.emit_to_string(wgsl).trim_end().to_owned()))?;
+    Ok(())
 }
 
-fn window_sizing(
-    scale: (f32, f32),
-    events_loop: &winit::event_loop::EventLoop<()>,
-) -> (
-    winit::dpi::LogicalSize<f32>,
-    winit::dpi::LogicalPosition<f32>,
-) {
-    let size_scale: vek::Vec2<_> = scale.into();
-    let screen = events_loop.primary_monitor().unwrap();
-    let screen_size: vek::Vec2<f32> = {
-        let size: (f32, f32) = screen
-            .size()
-            .to_logical::<f32>(screen.scale_factor())
-            .into();
-        size.into()
+// Compiles the MSL together with the prelude
+#[cfg(target_os = "macos")]
+fn check_with_metal(shader_file_path: &std::path::Path) -> Result<&'static str> {
+    let device = match metal::Device::system_default() {
+        Some(device) => device,
+        None => return Ok("skipped, no Metal device found"),
     };
-    let window_size = (screen_size * size_scale).into_tuple().into();
-    let window_position = ((screen_size * (vek::Vec2::from(1.0) - size_scale)).into_tuple()).into();
-    (window_size, window_position)
+    shader_compiler::compile_shader(shader_file_path, &device, |_| {})?;
+    Ok("OK")
 }
 
-struct ShaderFilesWatcher {
-    watcher: notify::RecommendedWatcher,
-    receiver: std::sync::mpsc::Receiver<notify::DebouncedEvent>,
-    watched_path: Option<std::path::PathBuf>,
+#[cfg(not(target_os = "macos"))]
+fn check_with_metal(_shader_file_path: &std::path::Path) -> Result<&'static str> {
+    Ok("skipped, Metal is only available on macOS")
 }
 
-impl ShaderFilesWatcher {
-    fn new(delay: std::time::Duration) -> Result<Self> {
-        let (tx, receiver) = std::sync::mpsc::channel();
-        let watcher = notify::watcher(tx, delay)?;
-        let mut files_watcher = ShaderFilesWatcher {
-            watcher,
-            receiver,
-            watched_path: None,
+const RENDER_USAGE: &str = "Usage: shader_roy render <path> [--output <file.png or directory>] \
+    [--size <width>x<height>] [--start <secs>] [--fps <frames per sec>] [--frames <count>] [--cpu]";
+
+// `shader_roy render <path> ...`
+fn render(args: &[String]) -> Result<()> {
+    let mut is_cpu = false;
+    let mut shader_path = None;
+    let mut output_path = None;
+    let mut sequence = frames::Sequence {
+        width: 800,
+        height: 600,
+        start_time_secs: 0.0,
+        frames_per_sec: 60.0,
+        frame_count: 1,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("Missing value for `{}`", arg), RENDER_USAGE))
         };
-        files_watcher.start_watching(&*shader_compiler::SHADER_PRELUDE_PATH)?;
-        files_watcher.start_watching(&*shader_compiler::SHADER_INTERFACE_PATH)?;
-        Ok(files_watcher)
+        match arg.as_str() {
+            "--cpu" => is_cpu = true,
+            "--output" | "-o" => output_path = Some(std::path::PathBuf::from(value()?)),
+            "--size" => {
+                let (width, height) = parse_dimensions(value()?)?;
+                sequence.width = width;
+                sequence.height = height;
+            }
+            "--start" | "--time" => sequence.start_time_secs = parse_number(value()?)?,
+            "--fps" => sequence.frames_per_sec = parse_number(value()?)?,
+            "--frames" => sequence.frame_count = parse_number(value()?)?,
+            _ if shader_path.is_none() && !arg.starts_with('-') => shader_path = Some(arg),
+            _ => {
+                return Err(usage_error(
+                    format!("Unexpected argument `{}`", arg),
+                    RENDER_USAGE,
+                ))
+            }
+        }
+    }
+    let shader_path =
+        shader_path.ok_or_else(|| usage_error("Missing shader path", RENDER_USAGE))?;
+    if sequence.frames_per_sec <= 0.0 {
+        return Err(usage_error("The frame rate must be positive", RENDER_USAGE));
     }
+    let shader_file_path = shader_file_path_arg::get_path_for_argument(shader_path)?;
 
-    fn watch_directory(&mut self, path: &std::path::Path) -> Result<()> {
-        use notify::Watcher;
-        if let Some(watched_path) = &self.watched_path {
-            let _ = self.watcher.unwatch(watched_path);
+    // A single frame can be written to the given file, a sequence
+    // to `frame_0001.png`... in the given directory
+    let output_path = output_path.unwrap_or_else(|| {
+        if sequence.frame_count == 1 {
+            "frame.png".into()
+        } else {
+            ".".into()
         }
-        self.start_watching(path.parent().unwrap())?;
-        self.watched_path = Some(path.to_owned());
-        Ok(())
+    });
+    let frames = if output_path.extension() == Some(std::ffi::OsStr::new("png")) {
+        if sequence.frame_count != 1 {
+            return Err(usage_error(
+                format!(
+                    "Rendering {} frames needs an output directory, not {:?}",
+                    sequence.frame_count, output_path
+                ),
+                RENDER_USAGE,
+            ));
+        }
+        vec![sequence.frame(0, output_path)]
+    } else {
+        std::fs::create_dir_all(&output_path)
+            .with_context(|| format!("Failed to create {:?}", output_path))?;
+        sequence.frames_in_directory(&output_path)
+    };
+
+    render_frames(&shader_file_path, &frames, is_cpu)?;
+    for frame in &frames {
+        println!("Rendered {:?}", frame.output_path);
     }
+    Ok(())
+}
 
-    fn has_changes(&self) -> bool {
-        self.receiver.try_recv().is_ok()
+fn render_frames(
+    shader_file_path: &std::path::Path,
+    frames: &[frames::Frame],
+    is_cpu: bool,
+) -> Result<()> {
+    if is_cpu {
+        cpu_renderer::render_frames(shader_file_path, frames)
+    } else {
+        render_frames_with_metal(shader_file_path, frames)
     }
+}
 
-    fn start_watching(&mut self, path: &std::path::Path) -> Result<()> {
-        use notify::Watcher;
-        self.watcher
-            .watch(path, notify::RecursiveMode::Recursive)
-            .with_context(|| format!("Failed to watch path {:?}", path))?;
-        Ok(())
+#[cfg(target_os = "macos")]
+fn render_frames_with_metal(
+    shader_file_path: &std::path::Path,
+    frames: &[frames::Frame],
+) -> Result<()> {
+    if !metal_renderer::is_available() {
+        println!("No Metal device found, rendering on the CPU");
+        return cpu_renderer::render_frames(shader_file_path, frames);
     }
+    metal_renderer::render_frames(shader_file_path, frames)
 }
 
-struct FrameRateReporter {
-    frame_start_time: std::time::Instant,
-    frame_rate_in_frames_per_sec: f64,
-    rate_limiter: RateLimiter,
+#[cfg(not(target_os = "macos"))]
+fn render_frames_with_metal(
+    shader_file_path: &std::path::Path,
+    frames: &[frames::Frame],
+) -> Result<()> {
+    // Metal is only available on macOS
+    cpu_renderer::render_frames(shader_file_path, frames)
 }
 
-impl FrameRateReporter {
-    fn new() -> Self {
-        Self {
-            frame_start_time: std::time::Instant::now(),
-            frame_rate_in_frames_per_sec: 30.0, // initial guess
-            rate_limiter: RateLimiter::new(std::time::Duration::from_secs(1)),
+const EXPORT_USAGE: &str = "Usage: shader_roy export <path> [--gif <file.gif>] [--y4m <file.y4m>] \
+    [--seconds <secs>] [--fps <frames per sec>] [--size <width>x<height>] [--start <secs>] [--cpu]";
+
+// `shader_roy export <path> --gif out.gif ...`
+fn export(args: &[String]) -> Result<()> {
+    let mut is_cpu = false;
+    let mut shader_path = None;
+    let mut gif_path = None;
+    let mut y4m_path = None;
+    let mut duration_secs: f32 = 5.0;
+    let mut sequence = frames::Sequence {
+        width: 400,
+        height: 300,
+        start_time_secs: 0.0,
+        frames_per_sec: 30.0,
+        frame_count: 0,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("Missing value for `{}`", arg), EXPORT_USAGE))
+        };
+        match arg.as_str() {
+            "--cpu" => is_cpu = true,
+            "--gif" => gif_path = Some(std::path::PathBuf::from(value()?)),
+            "--y4m" => y4m_path = Some(std::path::PathBuf::from(value()?)),
+            "--seconds" => duration_secs = parse_number(value()?)?,
+            "--fps" => sequence.frames_per_sec = parse_number(value()?)?,
+            "--size" => {
+                let (width, height) = parse_dimensions(value()?)?;
+                sequence.width = width;
+                sequence.height = height;
+            }
+            "--start" => sequence.start_time_secs = parse_number(value()?)?,
+            _ if shader_path.is_none() && !arg.starts_with('-') => shader_path = Some(arg),
+            _ => {
+                return Err(usage_error(
+                    format!("Unexpected argument `{}`", arg),
+                    EXPORT_USAGE,
+                ))
+            }
         }
     }
-
-    fn calculate_frame_rate_and_maybe_report(&mut self, window: &winit::window::Window) {
-        let frame_duration = self.frame_start_time.elapsed().as_millis().max(1) as f64;
-        let num_frames_averaged = 10.0;
-        self.frame_rate_in_frames_per_sec +=
-            (1000.0 / frame_duration - self.frame_rate_in_frames_per_sec) / num_frames_averaged;
-        let frame_rate_in_frames_per_sec = self.frame_rate_in_frames_per_sec;
-        self.rate_limiter.maybe_call(|| {
-            window.set_title(&format!(
-                "ShaderRoy [FPS: {:.0}]",
-                frame_rate_in_frames_per_sec
-            ));
-            use std::io::Write;
-            let _ = std::io::stdout().lock().flush();
-        });
-        self.frame_start_time = std::time::Instant::now();
+    let shader_path =
+        shader_path.ok_or_else(|| usage_error("Missing shader path", EXPORT_USAGE))?;
+    if gif_path.is_none() && y4m_path.is_none() {
+        return Err(usage_error(
+            "Missing `--gif` or `--y4m` output",
+            EXPORT_USAGE,
+        ));
     }
+    if sequence.frames_per_sec <= 0.0 || duration_secs <= 0.0 {
+        return Err(usage_error(
+            "The duration and frame rate must be positive",
+            EXPORT_USAGE,
+        ));
+    }
+    sequence.frame_count = ((duration_secs * sequence.frames_per_sec).round() as u32).max(1);
+    let shader_file_path = shader_file_path_arg::get_path_for_argument(shader_path)?;
+
+    // The renderers write PNGs, which are encoded once all frames are rendered
+    let frames_directory =
+        std::env::temp_dir().join(format!("shader_roy_export_{}", std::process::id()));
+    std::fs::create_dir_all(&frames_directory)
+        .with_context(|| format!("Failed to create {:?}", frames_directory))?;
+    let frames = sequence.frames_in_directory(&frames_directory);
+    let result = render_frames(&shader_file_path, &frames, is_cpu).and_then(|_| {
+        if let Some(gif_path) = &gif_path {
+            export::write_gif(&frames, sequence.frames_per_sec, gif_path)?;
+            println!("Exported {:?}", gif_path);
+        }
+        if let Some(y4m_path) = &y4m_path {
+            export::write_y4m(&frames, sequence.frames_per_sec, y4m_path)?;
+            println!("Exported {:?}", y4m_path);
+        }
+        Ok(())
+    });
+    std::fs::remove_dir_all(&frames_directory).ok();
+    result
 }
 
-struct RateLimiter {
-    delay: std::time::Duration,
-    last_call_time: std::time::Instant,
-}
-
-impl RateLimiter {
-    fn new(delay: std::time::Duration) -> Self {
-        Self {
-            delay,
-            last_call_time: std::time::Instant::now(),
+const DIFF_USAGE: &str =
+    "Usage: shader_roy diff <path>... [--size <width>x<height>] [--grid <columns>x<rows>] \
+    [--times <secs>,<secs>,...] [--tolerance <max error>]";
+
+// `shader_roy diff <path>... ...`
+fn diff(args: &[String]) -> Result<()> {
+    let mut shader_paths = Vec::new();
+    let mut options = differential::Options {
+        width: 800,
+        height: 600,
+        columns: 16,
+        rows: 12,
+        times: vec![0.0, 1.0, 2.5],
+        // One step of an 8-bit channel
+        tolerance: 1.0 / 255.0,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| usage_error(format!("Missing value for `{}`", arg), DIFF_USAGE))
+        };
+        match arg.as_str() {
+            "--size" => {
+                let (width, height) = parse_dimensions(value()?)?;
+                options.width = width;
+                options.height = height;
+            }
+            "--grid" => {
+                let (columns, rows) = parse_dimensions(value()?)?;
+                options.columns = columns;
+                options.rows = rows;
+            }
+            "--times" => {
+                let times = value()?;
+                options.times = times
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| {
+                        UsageError(format!("Invalid times `{}`, expected e.g. `0,1.5`", times))
+                    })?;
+            }
+            "--tolerance" => options.tolerance = parse_number(value()?)?,
+            _ if !arg.starts_with('-') => shader_paths.push(arg),
+            _ => {
+                return Err(usage_error(
+                    format!("Unexpected argument `{}`", arg),
+                    DIFF_USAGE,
+                ))
+            }
         }
     }
-
-    fn maybe_call<F: FnOnce()>(&mut self, func: F) {
-        if self.last_call_time.elapsed() > self.delay {
-            func();
-            self.last_call_time = std::time::Instant::now();
+    if shader_paths.is_empty() {
+        return Err(usage_error("Missing shader path", DIFF_USAGE));
+    }
+    let mut failed = Vec::new();
+    for shader_path in shader_paths {
+        println!("{}", shader_path);
+        let shader_file_path = shader_file_path_arg::get_path_for_argument(shader_path)?;
+        if !differential::check(&shader_file_path, &options)? {
+            failed.push(shader_path.as_str());
         }
     }
+    if !failed.is_empty() {
+        bail!(
+            "The transpiled shader differs from the Rust source by more than {} for: {}",
+            options.tolerance,
+            failed.join(", ")
+        );
+    }
+    Ok(())
 }
 
-#[test]
-fn test() -> Result<()> {
-    let examples_dir = shader_compiler::ROOT_PATH.join("../examples");
-    for example in std::fs::read_dir(examples_dir)? {
-        let example_name = example?.file_name().into_string().unwrap();
-        let path = shader_file_path_arg::get_path_for_argument(&example_name)?;
-        shader_compiler::compile_shader(
-            &path,
-            &metal::Device::system_default().unwrap(),
-            |shader_in_msl| println!("{:?}\n\n{}", &path, shader_in_msl),
-        )?;
+// `800x600`
+fn parse_dimensions(value: &str) -> Result<(u32, u32)> {
+    value
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| {
+            UsageError(format!(
+                "Invalid dimensions `{}`, expected e.g. `800x600`",
+                value
+            ))
+            .into()
+        })
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| UsageError(format!("Invalid number `{}`", value)).into())
+}
+
+const LIST_USAGE: &str = "Usage: shader_roy list";
+
+// `shader_roy list`
+fn list(args: &[String]) -> Result<()> {
+    if let Some(arg) = args.first() {
+        return Err(usage_error(
+            format!("Unexpected argument `{}`", arg),
+            LIST_USAGE,
+        ));
+    }
+    for name in shader_file_path_arg::example_names() {
+        println!("{}", name);
     }
     Ok(())
 }
@@ -0,0 +1,124 @@
+//! Maps the clock's time to the shader's `INPUT.elapsed_time_secs`, which can
+//! be paused, sped up, reversed, moved to any time and looped.
+
+// The step of `step` commands
+pub const FRAME_STEP_SECS: f64 = 1.0 / 60.0;
+
+pub struct Timeline {
+  // The shader time at `anchor_clock_secs`, from which the time moves with
+  // the clock at `speed`
+  anchor_time_secs: f64,
+  anchor_clock_secs: f64,
+  speed: f64,
+  is_paused: bool,
+  loop_range_secs: Option<(f64, f64)>,
+}
+
+impl Timeline {
+  pub fn new(clock_secs: f64) -> Self {
+    Self {
+      anchor_time_secs: 0.0,
+      anchor_clock_secs: clock_secs,
+      speed: 1.0,
+      is_paused: false,
+      loop_range_secs: None,
+    }
+  }
+
+  /// Starts playing from 0, keeping the speed and the loop.
+  pub fn restart(&mut self, clock_secs: f64) {
+    self.is_paused = false;
+    self.seek(clock_secs, 0.0);
+  }
+
+  pub fn time_secs(&self, clock_secs: f64) -> f64 {
+    let time_secs = if self.is_paused {
+      self.anchor_time_secs
+    } else {
+      self.anchor_time_secs + (clock_secs - self.anchor_clock_secs) * self.speed
+    };
+    match self.loop_range_secs {
+      Some((start, end)) => start + (time_secs - start).rem_euclid(end - start),
+      None => time_secs,
+    }
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.is_paused
+  }
+
+  pub fn toggle_paused(&mut self, clock_secs: f64) {
+    self.anchor(clock_secs);
+    self.is_paused = !self.is_paused;
+  }
+
+  pub fn seek(&mut self, clock_secs: f64, time_secs: f64) {
+    self.anchor_time_secs = time_secs;
+    self.anchor_clock_secs = clock_secs;
+  }
+
+  /// Negative speeds play backwards.
+  pub fn set_speed(&mut self, clock_secs: f64, speed: f64) {
+    self.anchor(clock_secs);
+    self.speed = speed;
+  }
+
+  /// Moves by `frames` steps of `FRAME_STEP_SECS`, backwards when negative,
+  /// and pauses.
+  pub fn step(&mut self, clock_secs: f64, frames: i64) {
+    self.anchor(clock_secs);
+    self.is_paused = true;
+    self.anchor_time_secs += frames as f64 * FRAME_STEP_SECS;
+  }
+
+  /// Plays `start..end` repeatedly, or the whole timeline for `None`.
+  pub fn set_loop(&mut self, clock_secs: f64, range_secs: Option<(f64, f64)>) {
+    self.anchor(clock_secs);
+    self.loop_range_secs = range_secs;
+    if let Some((start, end)) = range_secs {
+      if !(start..end).contains(&self.anchor_time_secs) {
+        self.anchor_time_secs = start;
+      }
+    }
+  }
+
+  // Continues from the current time, so that changes apply from now on
+  fn anchor(&mut self, clock_secs: f64) {
+    let time_secs = self.time_secs(clock_secs);
+    self.seek(clock_secs, time_secs);
+  }
+}
+
+#[test]
+fn test_timeline() {
+  let mut timeline = Timeline::new(10.0);
+  assert_eq!(timeline.time_secs(12.0), 2.0);
+
+  timeline.toggle_paused(12.0);
+  assert_eq!(timeline.time_secs(20.0), 2.0);
+  timeline.toggle_paused(20.0);
+  assert_eq!(timeline.time_secs(21.0), 3.0);
+
+  timeline.set_speed(21.0, -0.5);
+  assert_eq!(timeline.time_secs(23.0), 2.0);
+  timeline.seek(23.0, 12.5);
+  assert_eq!(timeline.time_secs(24.0), 12.0);
+
+  timeline.step(24.0, 3);
+  assert!(timeline.is_paused());
+  assert_eq!(timeline.time_secs(30.0), 12.0 + 3.0 * FRAME_STEP_SECS);
+  timeline.step(30.0, -3);
+  assert_eq!(timeline.time_secs(30.0), 12.0);
+
+  timeline.toggle_paused(30.0);
+  timeline.set_speed(30.0, 1.0);
+  timeline.set_loop(30.0, Some((1.0, 3.0)));
+  assert_eq!(timeline.time_secs(30.5), 1.5);
+  assert_eq!(timeline.time_secs(32.5), 1.5);
+  timeline.set_speed(32.5, -1.0);
+  assert_eq!(timeline.time_secs(33.5), 2.5);
+
+  timeline.set_loop(33.5, None);
+  timeline.restart(40.0);
+  assert_eq!(timeline.time_secs(41.0), -1.0);
+}
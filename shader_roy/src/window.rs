@@ -0,0 +1,739 @@
+// Copyright 2016 metal-rs developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::cli_commands::{
+    CLICommand, CLICommandsWatcher, CompileStatus, Reply, Request, Responder,
+};
+use super::clock::Clock;
+use super::compile_worker::CompileWorker;
+use super::control_server::ControlServer;
+use super::error_overlay::ErrorOverlay;
+use super::metal_renderer::{prepare_render_pass_descriptor, Float2, Float4, Input};
+use super::project_config::{ProjectConfig, WindowConfig};
+use super::timeline::Timeline;
+use super::{metal_renderer, shader_file_path_arg};
+use anyhow::{Context, Result};
+
+use cocoa::{appkit::NSView, base::id as cocoa_id};
+
+use metal::*;
+use objc::{rc::autoreleasepool, runtime::YES};
+use std::mem;
+use winit::platform::macos::WindowExtMacOS;
+
+use winit::{
+    event::{Event, WindowEvent},
+    event_loop::ControlFlow,
+};
+
+pub fn run(
+    shader_path: &str,
+    clock: Box<dyn Clock>,
+    control_address: Option<&str>,
+    config: &ProjectConfig,
+    is_paused: bool,
+) -> Result<()> {
+    let events_loop = winit::event_loop::EventLoop::new();
+    let (window_size, window_position) = window_sizing(&config.window, &events_loop);
+    let window = winit::window::WindowBuilder::new()
+        .with_inner_size(window_size)
+        .with_outer_position(window_position)
+        .with_title("ShaderRoy")
+        .with_always_on_top(config.window.always_on_top)
+        .build(&events_loop)
+        .unwrap();
+
+    let device = Device::system_default().expect("no device found");
+
+    let layer = MetalLayer::new();
+    layer.set_device(&device);
+    let pixel_format = metal_renderer::metal_pixel_format(config.pixel_format);
+    layer.set_pixel_format(pixel_format);
+    layer.set_presents_with_transaction(false);
+    // The error overlay copies the frames
+    layer.set_framebuffer_only(false);
+
+    unsafe {
+        let view = window.ns_view() as cocoa_id;
+        view.setWantsLayer(YES);
+        view.setLayer(mem::transmute(layer.as_ref()));
+    }
+
+    let draw_size = window.inner_size();
+    layer.set_drawable_size(CGSize::new(draw_size.width as f64, draw_size.height as f64));
+
+    let vector_buffer = metal_renderer::new_vertex_buffer(&device);
+
+    let command_queue = device.new_command_queue();
+    let mut error_overlay = ErrorOverlay::new(&device, pixel_format)?;
+    // Shown over the last compiled shader until the next compile succeeds
+    let mut compile_error: Option<String> = None;
+
+    let mut shader_file_path = shader_file_path_arg::get_path_for_argument(shader_path)?;
+    // Watches the files of each compile once it's done
+    let mut shader_files_watcher =
+        ShaderFilesWatcher::new(std::time::Duration::from_millis(config.debounce_millis))?;
+    let (request_sender, requests) = std::sync::mpsc::channel();
+    let _cli_commands_watcher = CLICommandsWatcher::new(request_sender.clone());
+    let control_server = control_address
+        .map(|address| ControlServer::start(address, request_sender))
+        .transpose()?;
+    let mut compile_status = CompileStatus::Compiling {
+        path: shader_file_path.clone(),
+    };
+    let mut last_msl: Option<String> = None;
+    let mut pending_screenshots: Vec<(std::path::PathBuf, Responder)> = Vec::new();
+    // Replaced only once a new shader compiles, which happens in the background
+    let mut pipeline_state: Option<RenderPipelineState> = None;
+    let compile_worker = CompileWorker::new(&device, pixel_format);
+    let mut needs_compile = true;
+    let mut frame_rate_reporter = FrameRateReporter::new();
+    let mut input_computer = InputComputer::new(clock);
+    if is_paused {
+        // Shows the first frame once the shader compiles
+        input_computer.toggle_paused();
+    }
+    let frame_duration = std::time::Duration::from_secs_f64(1.0 / config.fps);
+
+    events_loop.run(move |event, _, control_flow| {
+        autoreleasepool(|| {
+            let res = (|| -> Result<(), Box<dyn std::error::Error>> {
+                *control_flow = ControlFlow::WaitUntil(std::time::Instant::now() + frame_duration);
+
+                match event {
+                    Event::WindowEvent { event, .. } => match event {
+                        WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
+                        WindowEvent::Resized(size) => {
+                            layer.set_drawable_size(CGSize::new(
+                                size.width as f64,
+                                size.height as f64,
+                            ));
+                        }
+                        WindowEvent::Focused(is_focused) => {
+                            input_computer.set_is_focused(is_focused);
+                        }
+                        WindowEvent::CursorEntered { .. } => {
+                            input_computer.set_cursor_presence(true);
+                        }
+                        WindowEvent::CursorLeft { .. } => {
+                            input_computer.set_cursor_presence(false);
+                        }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            input_computer.set_cursor_position(position);
+                        }
+                        WindowEvent::MouseInput { state, button, .. } => {
+                            input_computer.set_cursor_click_state(button, state)
+                        }
+                        _ => (),
+                    },
+                    Event::MainEventsCleared => {
+                        window.request_redraw();
+                    }
+                    Event::RedrawRequested(_) => {
+                        if let Ok(Request { command, responder }) = requests.try_recv() {
+                            match command {
+                                // Replied to once the next frame is rendered
+                                CLICommand::Screenshot(path)
+                                    if pipeline_state.is_some() && compile_error.is_none() =>
+                                {
+                                    pending_screenshots.push((path.into(), responder));
+                                    input_computer.request_frame();
+                                }
+                                command => responder.send(match command {
+                                    CLICommand::Pause => {
+                                        input_computer.toggle_paused();
+                                        Ok(Reply::Done)
+                                    }
+                                    CLICommand::Restart => {
+                                        input_computer.reset();
+                                        Ok(Reply::Done)
+                                    }
+                                    CLICommand::Run(new_path) => {
+                                        shader_file_path_arg::get_path_for_argument(&new_path)
+                                            .map(|new_shader_file_path| {
+                                                shader_file_path = new_shader_file_path;
+                                                needs_compile = true;
+                                                input_computer.reset();
+                                                Reply::Done
+                                            })
+                                            .map_err(|error| format!("{:?}", error))
+                                    }
+                                    CLICommand::Seek(time_secs) => {
+                                        input_computer.seek(time_secs);
+                                        Ok(Reply::Time(input_computer.time_secs()))
+                                    }
+                                    CLICommand::Speed(speed) => {
+                                        input_computer.set_speed(speed);
+                                        Ok(Reply::Done)
+                                    }
+                                    CLICommand::Step(frames) => {
+                                        input_computer.step(frames);
+                                        Ok(Reply::Time(input_computer.time_secs()))
+                                    }
+                                    CLICommand::Loop(range_secs) => {
+                                        input_computer.set_loop(range_secs);
+                                        Ok(Reply::Done)
+                                    }
+                                    CLICommand::Screenshot(_) => {
+                                        Err("There is no compiled shader to take a screenshot of"
+                                            .to_owned())
+                                    }
+                                    CLICommand::Status => Ok(Reply::Status(compile_status.clone())),
+                                    CLICommand::Msl => last_msl
+                                        .clone()
+                                        .map(Reply::Msl)
+                                        .ok_or_else(|| "No shader was compiled yet".to_owned()),
+                                    CLICommand::Quit => {
+                                        *control_flow = ControlFlow::Exit;
+                                        Ok(Reply::Done)
+                                    }
+                                }),
+                            }
+                        }
+                        let changed_files = shader_files_watcher.take_changed_files();
+                        if !changed_files.is_empty() {
+                            println!(
+                                "Recompiling after changes to {}",
+                                changed_files
+                                    .iter()
+                                    .map(|path| format!("{:?}", path))
+                                    .collect::<Vec<_>>()
+                                    .join(", ")
+                            );
+                        }
+                        if needs_compile || !changed_files.is_empty() {
+                            needs_compile = false;
+                            update_compile_status(
+                                &mut compile_status,
+                                control_server.as_ref(),
+                                &mut frame_rate_reporter,
+                                &window,
+                                CompileStatus::Compiling {
+                                    path: shader_file_path.clone(),
+                                },
+                            );
+                            compile_worker.compile(&shader_file_path);
+                        }
+                        if let Some(compiled) = compile_worker.try_recv() {
+                            if !compiled.source_files.is_empty() {
+                                if let Err(error) =
+                                    shader_files_watcher.watch_files(&compiled.source_files)
+                                {
+                                    println!("{:?}", error);
+                                }
+                            }
+                            if let Some(msl) = compiled.msl {
+                                println!("{}", msl);
+                                last_msl = Some(msl);
+                            }
+                            let status = match compiled.pipeline_state {
+                                Ok(new_pipeline_state) => {
+                                    println!("Compiled in {}", compiled.timings);
+                                    pipeline_state = Some(new_pipeline_state);
+                                    compile_error = None;
+                                    CompileStatus::Compiled {
+                                        path: shader_file_path.clone(),
+                                    }
+                                }
+                                Err(error) => {
+                                    println!("{}", error);
+                                    println!("Failed after {}", compiled.timings);
+                                    for (_, responder) in pending_screenshots.drain(..) {
+                                        responder.send(Err(error.clone()));
+                                    }
+                                    compile_error = Some(error.clone());
+                                    CompileStatus::Failed {
+                                        path: shader_file_path.clone(),
+                                        error,
+                                    }
+                                }
+                            };
+                            update_compile_status(
+                                &mut compile_status,
+                                control_server.as_ref(),
+                                &mut frame_rate_reporter,
+                                &window,
+                                status,
+                            );
+                            CLICommandsWatcher::print_instructions();
+                            // Shows the result also while paused
+                            input_computer.request_frame();
+                        }
+
+                        if input_computer.is_paused() {
+                            return Ok(());
+                        }
+                        if pipeline_state.is_none() && compile_error.is_none() {
+                            return Ok(());
+                        }
+
+                        let drawable = layer.next_drawable().ok_or("No drawable")?;
+                        let command_buffer = command_queue.new_command_buffer();
+                        let input = input_computer.current_input(&window);
+                        if let Some(pipeline_state) = &pipeline_state {
+                            let render_pass_descriptor = RenderPassDescriptor::new();
+                            prepare_render_pass_descriptor(
+                                &render_pass_descriptor,
+                                drawable.texture(),
+                            );
+                            let encoder =
+                                command_buffer.new_render_command_encoder(&render_pass_descriptor);
+
+                            encoder.set_render_pipeline_state(pipeline_state);
+                            encoder.set_vertex_buffer(0, Some(&vector_buffer), 0);
+                            encoder.set_fragment_bytes(
+                                0,
+                                std::mem::size_of::<Input>() as u64,
+                                &input as *const Input as *const _,
+                            );
+                            encoder.draw_primitives_instanced(
+                                metal::MTLPrimitiveType::TriangleStrip,
+                                0,
+                                4,
+                                1,
+                            );
+
+                            encoder.end_encoding();
+                        }
+                        // The last working shader keeps running, dimmed under the error
+                        if let Some(compile_error) = &compile_error {
+                            if pipeline_state.is_some() {
+                                error_overlay.keep_frame(
+                                    &device,
+                                    command_buffer,
+                                    drawable.texture(),
+                                );
+                            }
+                            error_overlay.render(
+                                &device,
+                                command_buffer,
+                                drawable.texture(),
+                                compile_error,
+                            );
+                        }
+                        command_buffer.present_drawable(&drawable);
+                        command_buffer.commit();
+
+                        for (path, responder) in pending_screenshots.drain(..) {
+                            responder.send(
+                                metal_renderer::render_to_png(
+                                    &device,
+                                    &command_queue,
+                                    pipeline_state.as_ref().unwrap(),
+                                    &vector_buffer,
+                                    &input,
+                                    pixel_format,
+                                    &path,
+                                )
+                                .map(|()| Reply::Screenshot(path))
+                                .map_err(|error| format!("{:?}", error)),
+                            );
+                        }
+
+                        frame_rate_reporter.calculate_frame_rate_and_maybe_report(&window);
+                    }
+                    _ => {}
+                };
+                Ok(())
+            })();
+            if let Err(err) = res {
+                println!("{:?}", err);
+                CLICommandsWatcher::print_instructions();
+                for (_, responder) in pending_screenshots.drain(..) {
+                    responder.send(Err(format!("{:?}", err)));
+                }
+            }
+        });
+    });
+}
+
+// Also sends the status to the control server's clients and shows it in the
+// window's title
+fn update_compile_status(
+    compile_status: &mut CompileStatus,
+    control_server: Option<&ControlServer>,
+    frame_rate_reporter: &mut FrameRateReporter,
+    window: &winit::window::Window,
+    status: CompileStatus,
+) {
+    if let Some(control_server) = control_server {
+        control_server.broadcast_compile_status(&status);
+    }
+    frame_rate_reporter.set_compile_status(
+        window,
+        match status {
+            CompileStatus::Compiling { .. } => "compiling",
+            CompileStatus::Compiled { .. } => "OK",
+            CompileStatus::Failed { .. } => "error",
+        },
+    );
+    *compile_status = status;
+}
+
+struct InputComputer {
+    clock: Box<dyn Clock>,
+    timeline: Timeline,
+    last_frame_time_secs: f64,
+    // Set when the timeline changes while paused, to render one frame
+    is_frame_pending: bool,
+    frame_count: u64,
+    is_window_focused: bool,
+    is_cursor_inside_window: bool,
+    cursor_position: Float2,
+    pointer_device_state: Float2,
+    date: Float4,
+}
+
+impl InputComputer {
+    fn new(clock: Box<dyn Clock>) -> Self {
+        let timeline = Timeline::new(clock.now_secs());
+        let mut input_computer = Self {
+            clock,
+            timeline,
+            last_frame_time_secs: 0.0,
+            is_frame_pending: false,
+            frame_count: 0,
+            is_window_focused: true,
+            is_cursor_inside_window: false,
+            cursor_position: Float2 { x: 0.0, y: 0.0 },
+            pointer_device_state: Float2 { x: 0.0, y: 0.0 },
+            date: Float4 {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+                w: 0.0,
+            },
+        };
+        input_computer.reset();
+        input_computer
+    }
+
+    fn reset(&mut self) {
+        self.clock.restart();
+        self.timeline.restart(self.clock.now_secs());
+        self.last_frame_time_secs = 0.0;
+        self.frame_count = 0;
+        let [year, month, day, time_zone] = self.clock.date();
+        self.date = Float4 {
+            x: year,
+            y: month,
+            z: day,
+            w: time_zone,
+        };
+    }
+
+    fn time_secs(&self) -> f64 {
+        self.timeline.time_secs(self.clock.now_secs())
+    }
+
+    fn toggle_paused(&mut self) {
+        self.timeline.toggle_paused(self.clock.now_secs());
+    }
+
+    fn seek(&mut self, time_secs: f64) {
+        self.timeline.seek(self.clock.now_secs(), time_secs);
+        self.is_frame_pending = true;
+    }
+
+    fn set_speed(&mut self, speed: f64) {
+        self.timeline.set_speed(self.clock.now_secs(), speed);
+    }
+
+    fn step(&mut self, frames: i64) {
+        self.timeline.step(self.clock.now_secs(), frames);
+        self.is_frame_pending = true;
+    }
+
+    fn set_loop(&mut self, range_secs: Option<(f64, f64)>) {
+        self.timeline.set_loop(self.clock.now_secs(), range_secs);
+        self.is_frame_pending = true;
+    }
+
+    fn request_frame(&mut self) {
+        self.is_frame_pending = true;
+    }
+
+    // Paused with no changes to show
+    fn is_paused(&self) -> bool {
+        self.timeline.is_paused() && !self.is_frame_pending
+    }
+
+    fn set_is_focused(&mut self, is_focused: bool) {
+        self.is_window_focused = is_focused;
+    }
+
+    fn set_cursor_presence(&mut self, is_present: bool) {
+        self.is_cursor_inside_window = self.is_window_focused && is_present;
+    }
+
+    fn set_cursor_position(&mut self, position: winit::dpi::PhysicalPosition<f64>) {
+        self.is_cursor_inside_window = true;
+        self.cursor_position = Float2 {
+            x: position.x as f32,
+            y: position.y as f32,
+        };
+    }
+
+    fn set_cursor_click_state(
+        &mut self,
+        button: winit::event::MouseButton,
+        state: winit::event::ElementState,
+    ) {
+        if !self.is_cursor_inside_window {
+            return;
+        }
+        let state_value = match state {
+            winit::event::ElementState::Pressed => 1.0,
+            winit::event::ElementState::Released => 0.0,
+        };
+        match button {
+            winit::event::MouseButton::Left => {
+                self.pointer_device_state.x = state_value;
+            }
+            winit::event::MouseButton::Right => {
+                self.pointer_device_state.y = state_value;
+            }
+            _ => {}
+        }
+    }
+
+    fn current_input(&mut self, window: &winit::window::Window) -> Input {
+        let physical_size = window.inner_size();
+        let physical_position = window.inner_position().unwrap();
+        let time_secs = self.time_secs();
+        self.frame_count += 1;
+        let result = Input {
+            window_size: Float2 {
+                x: physical_size.width as f32,
+                y: physical_size.height as f32,
+            },
+            window_position: Float2 {
+                x: physical_position.x as f32,
+                y: physical_position.y as f32,
+            },
+            cursor_position: self.cursor_position,
+            is_cursor_inside_window: self.is_cursor_inside_window,
+            // Follows the timeline, so it's negative when playing backwards
+            elapsed_time_since_last_frame_secs: (time_secs - self.last_frame_time_secs) as f32,
+            elapsed_time_secs: time_secs as f32,
+            frame_count: self.frame_count as f32,
+            year_month_day_tz: self.date,
+        };
+        self.last_frame_time_secs = time_secs;
+        self.is_frame_pending = false;
+        self.clock.finish_frame();
+        result
+    }
+}
+
+fn window_sizing(
+    config: &WindowConfig,
+    events_loop: &winit::event_loop::EventLoop<()>,
+) -> (
+    winit::dpi::LogicalSize<f32>,
+    winit::dpi::LogicalPosition<f32>,
+) {
+    let screen = events_loop.primary_monitor().unwrap();
+    let screen_size: vek::Vec2<f32> = {
+        let size: (f32, f32) = screen
+            .size()
+            .to_logical::<f32>(screen.scale_factor())
+            .into();
+        size.into()
+    };
+    // 40% of the screen in its bottom right corner by default
+    let default_size = screen_size * 0.4;
+    let window_size = vek::Vec2::new(
+        config.width.map_or(default_size.x, |width| width as f32),
+        config.height.map_or(default_size.y, |height| height as f32),
+    );
+    let default_position = screen_size - window_size;
+    let window_position = vek::Vec2::new(
+        config.x.map_or(default_position.x, |x| x as f32),
+        config.y.map_or(default_position.y, |y| y as f32),
+    );
+    (
+        window_size.into_tuple().into(),
+        window_position.into_tuple().into(),
+    )
+}
+
+// Watches the files the shader was compiled from, and no others
+struct ShaderFilesWatcher {
+    watcher: notify::RecommendedWatcher,
+    receiver: std::sync::mpsc::Receiver<notify::DebouncedEvent>,
+    source_files: std::collections::HashSet<std::path::PathBuf>,
+    watched_directories: std::collections::HashSet<std::path::PathBuf>,
+}
+
+impl ShaderFilesWatcher {
+    fn new(delay: std::time::Duration) -> Result<Self> {
+        let (tx, receiver) = std::sync::mpsc::channel();
+        let watcher = notify::watcher(tx, delay)?;
+        Ok(ShaderFilesWatcher {
+            watcher,
+            receiver,
+            source_files: Default::default(),
+            watched_directories: Default::default(),
+        })
+    }
+
+    /// Replaces the watched files. Watches their directories, because editors
+    /// often save by replacing the file, and ignores the directories' other
+    /// files.
+    fn watch_files(&mut self, files: &[std::path::PathBuf]) -> Result<()> {
+        use notify::Watcher;
+        self.source_files = files.iter().map(|file| canonical_path(file)).collect();
+        let directories: std::collections::HashSet<_> = self
+            .source_files
+            .iter()
+            .filter_map(|file| file.parent().map(std::path::Path::to_owned))
+            .collect();
+        for directory in self.watched_directories.difference(&directories) {
+            let _ = self.watcher.unwatch(directory);
+        }
+        for directory in directories.difference(&self.watched_directories) {
+            self.watcher
+                .watch(directory, notify::RecursiveMode::NonRecursive)
+                .with_context(|| format!("Failed to watch directory {:?}", directory))?;
+        }
+        self.watched_directories = directories;
+        Ok(())
+    }
+
+    /// Drains the pending events, returning each changed source file once, in
+    /// the order they changed. Bursts of saves then cause a single recompile.
+    fn take_changed_files(&self) -> Vec<std::path::PathBuf> {
+        use notify::DebouncedEvent::*;
+        let mut changed_files = Vec::new();
+        for event in self.receiver.try_iter() {
+            let paths = match event {
+                // Followed by the debounced events
+                NoticeWrite(_) | NoticeRemove(_) => vec![],
+                Create(path) | Write(path) | Chmod(path) | Remove(path) => vec![path],
+                Rename(from, to) => vec![from, to],
+                // Events were missed, so any file could have changed
+                Rescan => self.source_files.iter().cloned().collect(),
+                Error(_, path) => path.into_iter().collect(),
+            };
+            for path in paths {
+                if self.source_files.contains(&path) && !changed_files.contains(&path) {
+                    changed_files.push(path);
+                }
+            }
+        }
+        changed_files
+    }
+}
+
+// Resolves symlinks like the paths of the watcher's events, also for files
+// which don't exist
+fn canonical_path(path: &std::path::Path) -> std::path::PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| {
+        match (
+            path.parent()
+                .and_then(|parent| std::fs::canonicalize(parent).ok()),
+            path.file_name(),
+        ) {
+            (Some(parent), Some(file_name)) => parent.join(file_name),
+            _ => path.to_owned(),
+        }
+    })
+}
+
+// Also shows the compile status in the title
+struct FrameRateReporter {
+    frame_start_time: std::time::Instant,
+    frame_rate_in_frames_per_sec: f64,
+    compile_status: &'static str,
+    rate_limiter: RateLimiter,
+}
+
+impl FrameRateReporter {
+    fn new() -> Self {
+        Self {
+            frame_start_time: std::time::Instant::now(),
+            frame_rate_in_frames_per_sec: 30.0, // initial guess
+            compile_status: "compiling",
+            rate_limiter: RateLimiter::new(std::time::Duration::from_secs(1)),
+        }
+    }
+
+    fn calculate_frame_rate_and_maybe_report(&mut self, window: &winit::window::Window) {
+        let frame_duration = self.frame_start_time.elapsed().as_millis().max(1) as f64;
+        let num_frames_averaged = 10.0;
+        self.frame_rate_in_frames_per_sec +=
+            (1000.0 / frame_duration - self.frame_rate_in_frames_per_sec) / num_frames_averaged;
+        let frame_rate_in_frames_per_sec = self.frame_rate_in_frames_per_sec;
+        let compile_status = self.compile_status;
+        self.rate_limiter.maybe_call(|| {
+            update_title(window, frame_rate_in_frames_per_sec, compile_status);
+            use std::io::Write;
+            let _ = std::io::stdout().lock().flush();
+        });
+        self.frame_start_time = std::time::Instant::now();
+    }
+
+    // Shown right away, unlike the frame rate
+    fn set_compile_status(&mut self, window: &winit::window::Window, compile_status: &'static str) {
+        self.compile_status = compile_status;
+        update_title(window, self.frame_rate_in_frames_per_sec, compile_status);
+    }
+}
+
+fn update_title(
+    window: &winit::window::Window,
+    frame_rate_in_frames_per_sec: f64,
+    compile_status: &str,
+) {
+    window.set_title(&format!(
+        "ShaderRoy [FPS: {:.0}] [{}]",
+        frame_rate_in_frames_per_sec, compile_status
+    ));
+}
+
+struct RateLimiter {
+    delay: std::time::Duration,
+    last_call_time: std::time::Instant,
+}
+
+impl RateLimiter {
+    fn new(delay: std::time::Duration) -> Self {
+        Self {
+            delay,
+            last_call_time: std::time::Instant::now(),
+        }
+    }
+
+    fn maybe_call<F: FnOnce()>(&mut self, func: F) {
+        if self.last_call_time.elapsed() > self.delay {
+            func();
+            self.last_call_time = std::time::Instant::now();
+        }
+    }
+}
+
+#[test]
+fn test() -> Result<()> {
+    let examples_dir = super::shader_compiler::ROOT_PATH.join("../examples");
+    for example in std::fs::read_dir(examples_dir)? {
+        let example_name = example?.file_name().into_string().unwrap();
+        let path = shader_file_path_arg::get_path_for_argument(&example_name)?;
+        super::shader_compiler::compile_shader(
+            &path,
+            &metal::Device::system_default().unwrap(),
+            |shader_in_msl| {
+                println!(
+                    "{:?}\n\n{}",
+                    &path,
+                    rust_to_metal_sl::format_msl(&shader_in_msl)
+                )
+            },
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+//! `INPUT` as a runtime value, for running shaders as regular Rust on the CPU,
+//! for example in unit tests:
+//!
+//! ```ignore
+//! let input = Input {
+//!   window_size: Vec2::new(100.0, 100.0),
+//!   ..Input::default()
+//! };
+//! let color = with_input(&input, || pixel_color(Vec2::new(50.0, 50.0)));
+//! ```
+//!
+//! Shaders keep using `INPUT.field`, which the transpiler turns into
+//! accesses to the Metal `constant` parameter.
+
+use super::Input;
+use std::cell::Cell;
+
+/// The input of the current frame, see [`with_input`].
+pub const INPUT: CurrentInput = CurrentInput;
+
+/// Dereferences to the input passed to the innermost [`with_input`] call
+/// on the current thread.
+#[derive(Copy, Clone)]
+pub struct CurrentInput;
+
+thread_local! {
+  static CURRENT_INPUT: Cell<Option<&'static Input>> = const { Cell::new(None) };
+  // The last input `with_input` leaked on this thread
+  static LAST_LEAKED_INPUT: Cell<Option<&'static Input>> = const { Cell::new(None) };
+}
+
+/// Calls `f` with `INPUT` referring to `input` on the current thread.
+///
+/// Calls can be nested, the outer input is restored when `f` returns or
+/// panics. Threads spawned by `f` need their own `with_input` call.
+///
+/// References to `INPUT` can outlive the call, so the input is copied into
+/// memory which is never freed. Calls with the same input as the previous
+/// one on the thread reuse its copy, so rendering leaks one copy per frame.
+pub fn with_input<R>(input: &Input, f: impl FnOnce() -> R) -> R {
+  struct Restore(Option<&'static Input>);
+
+  impl Drop for Restore {
+    fn drop(&mut self) {
+      CURRENT_INPUT.with(|current| current.set(self.0));
+    }
+  }
+
+  let leaked_input = LAST_LEAKED_INPUT.with(|last| match last.get() {
+    Some(leaked_input) if leaked_input == input => leaked_input,
+    _ => {
+      let leaked_input: &'static Input = Box::leak(Box::new(*input));
+      last.set(Some(leaked_input));
+      leaked_input
+    }
+  });
+  let _restore = Restore(CURRENT_INPUT.with(|current| current.replace(Some(leaked_input))));
+  f()
+}
+
+impl std::ops::Deref for CurrentInput {
+  type Target = Input;
+
+  fn deref(&self) -> &Input {
+    CURRENT_INPUT
+      .with(Cell::get)
+      .expect("`INPUT` can only be used inside `with_input`")
+  }
+}
+
+#[test]
+fn test_with_input_nests_and_restores() {
+  let input = |elapsed_time_secs| Input {
+    elapsed_time_secs,
+    ..Input::default()
+  };
+  let time = || INPUT.elapsed_time_secs;
+  let times = with_input(&input(1.0), || {
+    (time(), with_input(&input(2.0), time), time())
+  });
+  assert_eq!(times, (1.0, 2.0, 1.0));
+  assert!(std::panic::catch_unwind(time).is_err());
+}
+
+#[test]
+fn test_references_to_input_outlive_with_input() {
+  let input = |elapsed_time_secs| Input {
+    elapsed_time_secs,
+    ..Input::default()
+  };
+  let outer = with_input(&input(1.0), || {
+    let outer: &'static Input = &INPUT;
+    with_input(&input(2.0), || assert_eq!(INPUT.elapsed_time_secs, 2.0));
+    outer
+  });
+  with_input(&input(3.0), || {});
+  assert_eq!(outer.elapsed_time_secs, 1.0);
+}
@@ -1,7 +1,11 @@
 pub use metal_sl_prelude::*;
 
+mod runtime_input;
+
+pub use runtime_input::{with_input, CurrentInput, INPUT};
+
 /// This is the `constant` information passed to the pixel shader from ShaderRoy on each frame.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default, PartialEq)]
 pub struct Input {
   /// Window size in physical units (at the native resolution of the display device).
   pub window_size: Vec2,
@@ -23,7 +27,9 @@ pub struct Input {
   pub year_month_day_tz: Vec4,
 }
 
-// The values here are dummy values for type checking, real values are passed at runtime.
+// The transpiler infers the type of `INPUT` from this declaration, it's not
+// compiled as Rust. On the CPU `INPUT` comes from `runtime_input` instead.
+#[cfg(any())]
 pub const INPUT: Input = Input {
   window_size: Vec2 { x: 0.0, y: 0.0 },
   window_position: Vec2 { x: 0.0, y: 0.0 },